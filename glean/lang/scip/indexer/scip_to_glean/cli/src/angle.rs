@@ -6,8 +6,9 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::borrow::Cow;
+use std::cell::OnceCell;
 use std::fmt;
-use std::path::Component;
 use std::path::Path;
 
 // Use ahash instead of std hashmap for a slight performance gain.
@@ -32,9 +33,22 @@ use serde::Serialize;
 use crate::GleanRange;
 use crate::ToolInfo;
 use crate::decode_scip_range;
+use crate::digests::ExpectedDigests;
+use crate::digests::sha256_hex;
+use crate::filter::DocumentFilter;
+use crate::languages::LanguageMap;
+use crate::line_directives::LineRemapper;
+use crate::line_directives::line_remapper;
 use crate::lsif::LanguageId;
 use crate::lsif::SymbolKind;
 use crate::output::GleanJSONOutput;
+use crate::paths::PathMapping;
+use crate::paths::PathSkip;
+use crate::paths::Rewritten;
+use crate::paths::normalize_filepath;
+use crate::paths::project_root_path;
+use crate::policy::FailurePolicy;
+use crate::policy::SkippedItem;
 #[cfg(not(feature = "facebook"))]
 use crate::proto::scip::Document;
 #[cfg(not(feature = "facebook"))]
@@ -45,6 +59,13 @@ use crate::proto::scip::Occurrence;
 use crate::proto::scip::SymbolInformation;
 #[cfg(not(feature = "facebook"))]
 use crate::proto::scip::symbol_information;
+use crate::ranges::LineTable;
+use crate::ranges::RangeCheck;
+use crate::ranges::RangeValidation;
+use crate::scip_range_was_clamped;
+use crate::source_maps::source_map_remapper;
+use crate::sources::SourceProvider;
+use crate::stats::ConversionStats;
 
 // Key used to distinguish different fact hashmaps in Env.
 // Would probably be more efficient to just use an array of hashmaps,
@@ -66,6 +87,9 @@ impl fmt::Display for ScipId {
     }
 }
 
+/// Conversion state: the facts emitted so far and the ids of the values
+/// they refer to, so facts of several documents and indexes share ids.
+/// Feed it indexes with `decode_index`, then take the facts with `output`.
 pub struct Env {
     unique: u64,
     fact_id: HashMap<StringPredicate, HashMap<Box<str>, ScipId>>,
@@ -78,34 +102,70 @@ pub struct Env {
     /// fall back to the descriptor-derived kind and the same symbol ends up
     /// with contradictory `scip.SymbolKind` facts.
     kind_overrides: HashMap<Box<str>, SymbolKind>,
-    go_line_directive_maps: HashMap<ScipId, GoLineDirectiveMap>,
+    /// Remappers of files with line directives, see `crate::line_directives`.
+    line_remappers: HashMap<ScipId, Box<dyn LineRemapper>>,
+    stats: ConversionStats,
+    failure_policy: FailurePolicy,
+    /// The SCIP file currently being decoded, for `skipped` records.
+    input: Box<str>,
+    skipped: Vec<SkippedItem>,
+    range_validation: RangeValidation,
+    /// Emit occurrences in files with line directives against the file the
+    /// directive names, see `set_directive_file_attribution`.
+    attribute_to_directive_files: bool,
+    /// Line tables of files with known contents, kept only when
+    /// `range_validation` is enabled.
+    line_tables: HashMap<ScipId, LineTable>,
+    document_filter: DocumentFilter,
+    /// The directory of the current index's `Metadata.project_root`, see
+    /// `project_root_path`.
+    project_root: Option<String>,
+    language_map: LanguageMap,
+    /// Digests the files are expected to have, see `set_expected_digests`.
+    expected_digests: Option<ExpectedDigests>,
+    /// The size limit of `src.FileContent` facts, if they are emitted.
+    max_file_content_bytes: Option<usize>,
 }
 
-/// Normalize a filepath by removing .. and . components
-/// Returns None if the path cannot be properly normalized (e.g., too many .. components)
-fn normalize_filepath(path: &str) -> Option<String> {
-    let path = Path::new(path);
-    let max_len = path.as_os_str().len();
-    let mut ret = std::path::PathBuf::with_capacity(max_len);
-
-    for component in path.components() {
-        match component {
-            Component::Normal(_) | Component::RootDir => ret.push(component),
-            Component::ParentDir => {
-                // Go up a directory by popping the last component
-                if let Some(Component::Normal(_)) = ret.components().next_back() {
-                    ret.pop();
-                } else {
-                    // Cannot resolve .. properly, return None
-                    return None;
-                }
-            }
-            Component::CurDir => {} // Skip "."
-            Component::Prefix(prefix) => ret.push(prefix.as_os_str()),
-        }
+/// The source of a document: its `text`, else the file at `relative_path`
+/// in `sources`. `Ok(None)` if there is neither.
+fn read_source<'a>(
+    text: &'a str,
+    relative_path: &str,
+    sources: Option<&dyn SourceProvider>,
+) -> std::io::Result<Option<Cow<'a, [u8]>>> {
+    if !text.is_empty() {
+        Ok(Some(Cow::Borrowed(text.as_bytes())))
+    } else if let Some(sources) = sources {
+        sources
+            .read(relative_path)
+            .map(|bytes| Some(Cow::Owned(bytes)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The path of the file a line directive or source map of `filepath` names,
+/// relative to the directory of `filepath`. `None` for absolute paths, which
+/// are outside the repository, and paths escaping it.
+fn original_file_path(filepath: &str, target: &str) -> Option<Box<str>> {
+    if target.starts_with('/') {
+        return None;
     }
+    let path = match filepath.rsplit_once('/') {
+        Some((dir, _)) => normalize_filepath(&format!("{}/{}", dir, target)),
+        None => normalize_filepath(target),
+    }?;
+    Some(path.into_boxed_str())
+}
 
-    Some(ret.to_string_lossy().to_string())
+/// `bytes` as text, or `None` if they look binary: they contain a NUL byte
+/// in the first 8000 (as Git checks), or are not UTF-8.
+fn text_contents(bytes: &[u8]) -> Option<&str> {
+    if bytes[..bytes.len().min(8000)].contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(bytes).ok()
 }
 
 /// Compute src.FileLines data from raw file bytes.
@@ -135,226 +195,114 @@ fn compute_file_lines(bytes: &[u8]) -> (Vec<u64>, bool, bool) {
     (lengths, ends_in_newline, has_unicode_or_tabs)
 }
 
-#[derive(Clone)]
-struct GoLineDirectiveMap {
-    source: Vec<u8>,
-    line_starts: Vec<usize>,
-    segments: Vec<GoLineDirectiveSegment>,
-}
-
-#[derive(Clone, Copy)]
-struct GoLineDirectiveSegment {
-    physical_start_line: u64,
-    physical_end_line: u64,
-    virtual_start_line: u64,
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl GoLineDirectiveMap {
-    fn from_source(bytes: &[u8]) -> Option<Self> {
-        let line_starts = Self::compute_line_starts(bytes);
-        let line_count = line_starts.len().saturating_sub(1) as u64;
-        let mut segments = Vec::new();
-        let mut open_segment = None;
-
-        for physical_line in 1..=line_count {
-            let line = Self::line_text_from(bytes, &line_starts, physical_line);
-            if let Some(virtual_start_line) = parse_go_line_directive(line) {
-                let prior_segment = open_segment.take();
-                if let Some((physical_start_line, prior_virtual_start_line)) = prior_segment {
-                    if physical_start_line < physical_line {
-                        segments.push(GoLineDirectiveSegment {
-                            physical_start_line,
-                            physical_end_line: physical_line,
-                            virtual_start_line: prior_virtual_start_line,
-                        });
-                    }
-                }
-                open_segment = Some((physical_line + 1, virtual_start_line));
-            }
-        }
-
-        if let Some((physical_start_line, virtual_start_line)) = open_segment {
-            if physical_start_line <= line_count {
-                segments.push(GoLineDirectiveSegment {
-                    physical_start_line,
-                    physical_end_line: line_count + 1,
-                    virtual_start_line,
-                });
-            }
-        }
-
-        if segments.is_empty() {
-            return None;
+impl Env {
+    pub fn new() -> Self {
+        Self {
+            unique: 1,
+            fact_id: HashMap::new(),
+            out: GleanJSONOutput::default(),
+            kind_overrides: HashMap::new(),
+            line_remappers: HashMap::new(),
+            stats: ConversionStats::default(),
+            failure_policy: FailurePolicy::default(),
+            input: "".into(),
+            skipped: Vec::new(),
+            range_validation: RangeValidation::Off,
+            attribute_to_directive_files: false,
+            line_tables: HashMap::new(),
+            document_filter: DocumentFilter::default(),
+            project_root: None,
+            language_map: LanguageMap::default(),
+            expected_digests: None,
+            max_file_content_bytes: None,
         }
-
-        Some(Self {
-            source: bytes.to_vec(),
-            line_starts,
-            segments,
-        })
     }
 
-    fn compute_line_starts(bytes: &[u8]) -> Vec<usize> {
-        if bytes.is_empty() {
-            return vec![0];
-        }
-
-        let mut line_starts = vec![0];
-        line_starts.extend(bytes.iter().enumerate().filter_map(|(idx, byte)| {
-            if *byte == b'\n' && idx + 1 < bytes.len() {
-                Some(idx + 1)
-            } else {
-                None
-            }
-        }));
-        line_starts.push(bytes.len());
-        line_starts
+    /// The facts decoded so far.
+    pub fn output(self) -> GleanJSONOutput {
+        self.out
     }
 
-    fn remap_range(&self, range: GleanRange, symbol_hint: Option<&str>) -> GleanRange {
-        let Some(segment) = self.choose_segment(range.line_begin, symbol_hint) else {
-            return range;
-        };
-        let Some(line_begin) = segment.physical_line_for(range.line_begin) else {
-            return range;
-        };
-        let Some(line_end) = segment.physical_line_for(range.line_end) else {
-            return range;
-        };
-
-        GleanRange {
-            line_begin,
-            line_end,
-            ..range
-        }
+    pub fn stats_mut(&mut self) -> &mut ConversionStats {
+        &mut self.stats
     }
 
-    fn choose_segment(
-        &self,
-        virtual_line: u64,
-        symbol_hint: Option<&str>,
-    ) -> Option<GoLineDirectiveSegment> {
-        let candidates: Vec<_> = self
-            .segments
-            .iter()
-            .copied()
-            .filter(|segment| segment.contains_virtual_line(virtual_line))
-            .collect();
-
-        match candidates.as_slice() {
-            [] => None,
-            [segment] => Some(*segment),
-            _ => symbol_hint.and_then(|hint| {
-                candidates.into_iter().find(|segment| {
-                    segment
-                        .physical_line_for(virtual_line)
-                        .is_some_and(|line| self.physical_line_contains(line, hint))
-                })
-            }),
-        }
+    pub fn failure_policy(&self) -> FailurePolicy {
+        self.failure_policy
     }
 
-    fn physical_line_contains(&self, physical_line: u64, needle: &str) -> bool {
-        if needle.is_empty() {
-            return false;
-        }
-
-        std::str::from_utf8(Self::line_text_from(
-            &self.source,
-            &self.line_starts,
-            physical_line,
-        ))
-        .is_ok_and(|line| line.contains(needle))
+    pub fn set_failure_policy(&mut self, policy: FailurePolicy) {
+        self.failure_policy = policy;
     }
 
-    fn line_text_from<'a>(bytes: &'a [u8], line_starts: &[usize], line: u64) -> &'a [u8] {
-        if line == 0 {
-            return &[];
-        }
-
-        let line_idx = (line - 1) as usize;
-        if line_idx + 1 >= line_starts.len() {
-            return &[];
-        }
-
-        let start = line_starts[line_idx];
-        let mut end = line_starts[line_idx + 1];
-        while end > start && matches!(bytes[end - 1], b'\n' | b'\r') {
-            end -= 1;
-        }
-        &bytes[start..end]
+    /// Forget the metadata of the previously decoded index.
+    pub fn start_index(&mut self) {
+        self.project_root = None;
     }
-}
 
-impl GoLineDirectiveSegment {
-    fn contains_virtual_line(&self, virtual_line: u64) -> bool {
-        virtual_line >= self.virtual_start_line
-            && virtual_line < self.virtual_start_line + self.physical_line_count()
+    /// The directory the current index's `Metadata.project_root` names, with
+    /// a trailing `/`. `None` without metadata or for non-`file://` roots.
+    pub fn project_root(&self) -> Option<&str> {
+        self.project_root.as_deref()
     }
 
-    fn physical_line_for(&self, virtual_line: u64) -> Option<u64> {
-        if !self.contains_virtual_line(virtual_line) {
-            return None;
-        }
-        Some(self.physical_start_line + virtual_line - self.virtual_start_line)
+    /// Note the SCIP file whose documents are decoded next.
+    pub fn start_input(&mut self, input: &Path) {
+        self.input = input.display().to_string().into_boxed_str();
     }
 
-    fn physical_line_count(&self) -> u64 {
-        self.physical_end_line - self.physical_start_line
+    pub fn record_skipped_document(&mut self, document: &str, reason: String) {
+        self.skipped.push(SkippedItem {
+            input: self.input.to_string(),
+            document: document.to_owned(),
+            symbol: None,
+            reason,
+        });
     }
-}
-
-fn parse_go_line_directive(line: &[u8]) -> Option<u64> {
-    let line = trim_ascii(line);
-    let directive = if let Some(rest) = line.strip_prefix(b"//line ") {
-        rest
-    } else if let Some(rest) = line.strip_prefix(b"/*line ") {
-        trim_ascii(rest).strip_suffix(b"*/")?
-    } else {
-        return None;
-    };
-
-    let directive = std::str::from_utf8(trim_ascii(directive)).ok()?;
-    let mut parts = directive.rsplit(':');
-    let last = parts.next()?;
-    let before_last = parts.next()?;
-    let line_number = if last.parse::<u64>().is_ok() && before_last.parse::<u64>().is_ok() {
-        before_last
-    } else {
-        last
-    };
 
-    let line_number = line_number.parse::<u64>().ok()?;
-    (line_number > 0).then_some(line_number)
-}
+    pub fn set_range_validation(&mut self, validation: RangeValidation) {
+        self.range_validation = validation;
+    }
 
-fn trim_ascii(bytes: &[u8]) -> &[u8] {
-    let mut start = 0;
-    let mut end = bytes.len();
+    /// Emit the `scip.FileRange` of occurrences after a line directive
+    /// naming another file against that file, at their unmapped lines, with
+    /// a `scip.GeneratedRange` linking back to the generated location.
+    pub fn set_directive_file_attribution(&mut self, enabled: bool) {
+        self.attribute_to_directive_files = enabled;
+    }
 
-    while start < end && matches!(bytes[start], b' ' | b'\t' | b'\n' | b'\r') {
-        start += 1;
+    /// Only decode documents matching `filter`. Kind overrides are still
+    /// registered from every document, so kept documents see the same kinds
+    /// as in an unfiltered conversion.
+    pub fn set_document_filter(&mut self, filter: DocumentFilter) {
+        self.document_filter = filter;
     }
-    while end > start && matches!(bytes[end - 1], b' ' | b'\t' | b'\n' | b'\r') {
-        end -= 1;
+
+    /// Use `map` to infer the language of documents without one.
+    pub fn set_language_map(&mut self, map: LanguageMap) {
+        self.language_map = map;
     }
 
-    &bytes[start..end]
-}
+    /// Check the digests of files with known contents against `expected`,
+    /// recording those that differ in `files_with_unexpected_digests`.
+    pub fn set_expected_digests(&mut self, expected: ExpectedDigests) {
+        self.expected_digests = Some(expected);
+    }
 
-impl Env {
-    pub fn new() -> Self {
-        Self {
-            unique: 1,
-            fact_id: HashMap::new(),
-            out: GleanJSONOutput::default(),
-            kind_overrides: HashMap::new(),
-            go_line_directive_maps: HashMap::new(),
-        }
+    /// Emit the contents of text files up to `max_bytes` long as
+    /// `src.FileContent`.
+    pub fn set_file_contents(&mut self, max_bytes: usize) {
+        self.max_file_content_bytes = Some(max_bytes);
     }
 
-    pub fn output(self) -> GleanJSONOutput {
-        self.out
+    pub fn skipped_items(&self) -> &[SkippedItem] {
+        &self.skipped
     }
 
     fn next_id(&mut self) -> ScipId {
@@ -401,68 +349,85 @@ impl Env {
             }
             v => v,
         };
+        if !metadata.project_root.is_empty() {
+            self.project_root = project_root_path(&metadata.project_root);
+            if self.project_root.is_none() {
+                tracing::warn!(
+                    "Ignoring project root `{}`, it is not a file:// URI",
+                    metadata.project_root
+                );
+            }
+        }
         self.out
             .metadata(version, metadata.text_document_encoding.value(), tool_info);
     }
 
     /// Determine a document's language: prefer the explicit `doc.language`,
-    /// then optionally infer from the file extension, then fall back to the
-    /// caller-supplied default. The filepath used for extension matching is
+    /// then optionally infer from the file name, then fall back to the
+    /// caller-supplied default. The filepath used for matching is
     /// `doc.relative_path` — path-prefix adjustments performed elsewhere do
-    /// not affect extensions, so they are not relevant here.
-    fn infer_lang_for_doc(
+    /// not affect extensions, so they are not relevant here. Files with
+    /// unknown or ambiguous names are sniffed if their source is in
+    /// `doc.text` or `sources`.
+    pub fn infer_lang_for_doc(
         &self,
         default_lang: Option<LanguageId>,
         infer_language: bool,
         doc: &Document,
+        sources: Option<&dyn SourceProvider>,
     ) -> LanguageId {
-        LanguageId::new(&doc.language)
+        self.infer_lang_reading_source(default_lang, infer_language, doc, sources)
+            .0
+    }
+
+    /// Like `infer_lang_for_doc`, but also returns the result of reading the
+    /// document's source from `sources` if sniffing did, so that
+    /// `decode_scip_doc` need not read it again.
+    pub fn infer_lang_reading_source(
+        &self,
+        default_lang: Option<LanguageId>,
+        infer_language: bool,
+        doc: &Document,
+        sources: Option<&dyn SourceProvider>,
+    ) -> (LanguageId, Option<std::io::Result<Vec<u8>>>) {
+        let source = OnceCell::new();
+        let lang = LanguageId::new(&doc.language)
             .known()
             .or_else(|| {
                 if infer_language {
-                    self.file_language_of(&doc.relative_path)
+                    self.language_map.infer(&doc.relative_path, || {
+                        source
+                            .get_or_init(|| read_source(&doc.text, &doc.relative_path, sources))
+                            .as_ref()
+                            .ok()
+                            .and_then(Option::as_deref)
+                    })
                 } else {
                     None
                 }
             })
             .or(default_lang)
-            .unwrap_or_default()
+            .unwrap_or_default();
+        // Sources borrowed from `doc.text` are still there to decode.
+        let read = source.into_inner().and_then(|read| match read {
+            Ok(Some(Cow::Owned(bytes))) => Some(Ok(bytes)),
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        });
+        (lang, read)
     }
 
-    /// Compute the qualified filepath for a document: apply `strip_prefix` /
-    /// `path_prefix` adjustments, then normalize `..` components for
-    /// TypeScript paths. Returns `None` when a TypeScript path cannot be
-    /// normalized (e.g., too many `..` components) so the caller can skip
-    /// the document with the same semantics as before extraction.
+    /// Compute the qualified filepath for a document. Fails when a rewrite
+    /// rule drops the document, or its path cannot be normalized (e.g., too
+    /// many `..` components), so the caller can skip the document.
     fn qualified_filepath_for_doc(
         lang: LanguageId,
-        path_prefix: Option<&str>,
-        strip_prefix: Option<&str>,
+        paths: &PathMapping,
         doc: &Document,
-    ) -> Option<Box<str>> {
-        let mut filepath = doc.relative_path.to_owned();
-        if let Some(strip_prefix) = strip_prefix {
-            filepath = filepath
-                .strip_prefix(strip_prefix)
-                .unwrap_or(&filepath)
-                .to_owned();
-        }
-        if let Some(path_prefix) = path_prefix {
-            filepath = format!("{}{}", path_prefix, filepath);
-        }
-
-        // Normalize paths for TypeScript files only
-        // TODO T240234639: Remove once SCIP stops returning paths with ../
-        if matches!(lang, LanguageId::TypeScript | LanguageId::TypeScriptReact) {
-            match normalize_filepath(&filepath) {
-                Some(normalized) => filepath = normalized,
-                // Cannot normalize path properly (e.g., too many .. components);
-                // signal to the caller to skip this document.
-                None => return None,
-            }
-        }
-
-        Some(filepath.into_boxed_str())
+    ) -> Result<Box<str>, PathSkip> {
+        paths
+            .map(&doc.relative_path, lang)
+            .map(String::into_boxed_str)
     }
 
     /// Pre-pass: register the `SymbolInformation.kind` overrides for a single
@@ -470,17 +435,14 @@ impl Env {
     /// in the SCIP index *before* any call to `decode_scip_doc`, so that
     /// occurrences in any document can see overrides for symbols defined in
     /// any other document. See the field doc on `Env::kind_overrides`.
+    /// `lang` is the document's language, from `infer_lang_for_doc`.
     pub fn register_kind_overrides_for_doc(
         &mut self,
-        default_lang: Option<LanguageId>,
-        infer_language: bool,
-        path_prefix: Option<&str>,
-        strip_prefix: Option<&str>,
+        lang: LanguageId,
+        paths: &PathMapping,
         doc: &Document,
     ) {
-        let lang = self.infer_lang_for_doc(default_lang, infer_language, doc);
-        let Some(filepath) = Self::qualified_filepath_for_doc(lang, path_prefix, strip_prefix, doc)
-        else {
+        let Ok(filepath) = Self::qualified_filepath_for_doc(lang, paths, doc) else {
             return;
         };
         for info in &doc.symbols {
@@ -497,27 +459,70 @@ impl Env {
         }
     }
 
+    /// Decode a document whose language and, if sniffing read it, source
+    /// are `lang` and `source_read`, from `infer_lang_reading_source`.
     pub fn decode_scip_doc(
         &mut self,
-        default_lang: Option<LanguageId>,
-        infer_language: bool,
-        path_prefix: Option<&str>,
-        strip_prefix: Option<&str>,
-        source_root: Option<&Path>,
+        lang: LanguageId,
+        paths: &PathMapping,
+        sources: Option<&dyn SourceProvider>,
         mut doc: Document,
+        source_read: Option<std::io::Result<Vec<u8>>>,
     ) -> Result<()> {
-        let lang = self.infer_lang_for_doc(default_lang, infer_language, &doc);
-        let Some(filepath) =
-            Self::qualified_filepath_for_doc(lang, path_prefix, strip_prefix, &doc)
-        else {
-            // Cannot normalize path properly (e.g., too many .. components)
-            // Log error and skip this document
-            tracing::warn!(
-                "Cannot normalize filepath '{}', will most likely break browser clients",
-                doc.relative_path,
+        // Prefer inline document text, fall back to the input's sources. Read
+        // at most once, and only if needed.
+        let doc_text = std::mem::take(&mut doc.text);
+        let source = OnceCell::new();
+        if let Some(read) = source_read {
+            let _ = source.set(read.map(|bytes| Some(Cow::Owned(bytes))));
+        }
+        let read = || {
+            source
+                .get_or_init(|| read_source(&doc_text, &doc.relative_path, sources))
+                .as_ref()
+                .ok()
+                .and_then(Option::as_deref)
+        };
+        if !self.document_filter.matches(&doc.relative_path, lang) {
+            self.stats.documents_filtered += 1;
+            self.record_skipped_document(
+                &doc.relative_path,
+                "excluded by the document filter".to_string(),
             );
             return Ok(());
+        }
+        if !paths.rewrites.is_empty()
+            && paths.rewrites.rewrite(&doc.relative_path) == Rewritten::Unmatched
+        {
+            self.stats
+                .paths_unmatched_by_rewrites
+                .push(&doc.relative_path);
+        }
+        let filepath = match Self::qualified_filepath_for_doc(lang, paths, &doc) {
+            Ok(filepath) => filepath,
+            Err(PathSkip::Dropped) => {
+                self.stats.documents_dropped_by_rewrites += 1;
+                self.record_skipped_document(
+                    &doc.relative_path,
+                    "dropped by a --rewrite rule".to_string(),
+                );
+                return Ok(());
+            }
+            Err(PathSkip::Unnormalizable) => {
+                // The path climbs above its root and --escaping-paths=skip.
+                tracing::warn!(
+                    "Cannot normalize filepath '{}', will most likely break browser clients",
+                    doc.relative_path,
+                );
+                self.stats.paths_failed_normalization += 1;
+                self.record_skipped_document(
+                    &doc.relative_path,
+                    "path escapes its root".to_string(),
+                );
+                return Ok(());
+            }
         };
+        let facts_before = self.out.total_facts_count();
 
         // SCIP allows multiple Documents to share the same `relative_path`:
         // the Index proto comment in third-party/scip/scip.proto says
@@ -537,50 +542,72 @@ impl Env {
         // symbols.
         let (src_file_id, already_seen) =
             self.get_or_set_fact(StringPredicate::File, filepath.clone());
-        let doc_text = std::mem::take(&mut doc.text);
-        let file_bytes: Option<Vec<u8>> = if !doc_text.is_empty() {
-            Some(doc_text.into_bytes())
-        } else if let Some(source_root) = source_root {
-            std::fs::read(source_root.join(&doc.relative_path)).ok()
-        } else {
-            None
-        };
         if !already_seen {
             self.out.src_file(src_file_id, filepath.clone());
+            let file_bytes = read();
+            if doc_text.is_empty() && sources.is_some() {
+                self.record_source_read(&doc.relative_path, source.get());
+            }
 
-            // Emit src.FileLines: prefer inline document text, fall back to
-            // disk read. FileLines is per-file metadata; emit it once.
-            if let Some(bytes) = file_bytes.as_deref() {
+            // Emit src.FileLines. FileLines is per-file metadata; emit it once.
+            if let Some(bytes) = file_bytes {
                 let (lengths, ends_in_newline, has_unicode_or_tabs) = compute_file_lines(bytes);
+                if self.range_validation != RangeValidation::Off {
+                    self.line_tables
+                        .insert(src_file_id, LineTable::new(&lengths, ends_in_newline));
+                }
                 self.out
                     .file_lines(src_file_id, lengths, ends_in_newline, has_unicode_or_tabs);
+                self.decode_file_digest(src_file_id, &filepath, bytes);
+                self.decode_file_content(src_file_id, bytes);
             }
 
             // file_lang is also per-file metadata; emit it once.
             let lang_file_id = self.next_id();
             self.out.file_lang(lang_file_id, src_file_id, lang);
         }
-        let has_no_go_line_directive_map = !self.go_line_directive_maps.contains_key(&src_file_id);
-        let needs_go_line_directive_map =
-            matches!(lang, LanguageId::Go) && has_no_go_line_directive_map;
-        if needs_go_line_directive_map {
-            if let Some(bytes) = file_bytes.as_deref() {
-                if let Some(map) = GoLineDirectiveMap::from_source(bytes) {
-                    self.go_line_directive_maps.insert(src_file_id, map);
-                }
+        // Later same-path documents only add a remapper from their own text,
+        // their sources were read with the first one's.
+        if (!already_seen || !doc_text.is_empty())
+            && !self.line_remappers.contains_key(&src_file_id)
+        {
+            let remapper = read().and_then(|bytes| {
+                line_remapper(lang, bytes)
+                    .or_else(|| source_map_remapper(lang, sources, &doc.relative_path, bytes))
+            });
+            if let Some(remapper) = remapper {
+                self.line_remappers.insert(src_file_id, remapper);
             }
         }
 
         // Occurrences and SymbolInformation are additive across same-path
         // Documents; always process them.
         let mut empty_occ_count = 0;
+        self.stats.occurrences += doc.occurrences.len();
         for occ in doc.occurrences {
             if occ.symbol.is_empty() {
                 // scip-go emits empty occurrences, skip them.
                 empty_occ_count += 1;
                 continue;
             }
-            self.decode_scip_occurrence(src_file_id, &filepath, occ)?;
+            let symbol = self
+                .failure_policy
+                .skip_bad_occurrences
+                .then(|| occ.symbol.clone());
+            match self.decode_scip_occurrence(src_file_id, &filepath, occ) {
+                Ok(()) => {}
+                Err(e) if self.failure_policy.skip_bad_occurrences => {
+                    tracing::warn!("Skipping bad scip.Occurrence in file {}: {:#}", filepath, e);
+                    self.stats.occurrences_skipped_error += 1;
+                    self.skipped.push(SkippedItem {
+                        input: self.input.to_string(),
+                        document: doc.relative_path.clone(),
+                        symbol,
+                        reason: format!("{:#}", e),
+                    });
+                }
+                Err(e) => return Err(e),
+            }
         }
         if empty_occ_count > 0 {
             tracing::warn!(
@@ -588,61 +615,17 @@ impl Env {
                 empty_occ_count,
                 filepath,
             );
+            self.stats.occurrences_skipped_empty_symbol += empty_occ_count;
         }
 
         for info in doc.symbols {
             self.decode_scip_info(&filepath, info)?;
         }
 
-        Ok(())
-    }
+        let facts = self.out.total_facts_count() - facts_before;
+        self.stats.record_document_facts(&filepath, lang, facts);
 
-    fn file_language_of(&self, filepath: &str) -> Option<LanguageId> {
-        if filepath.ends_with(".kt") {
-            return Some(LanguageId::Kotlin);
-        } else if filepath.ends_with(".java") {
-            return Some(LanguageId::Java);
-        } else if filepath.ends_with(".ts") {
-            return Some(LanguageId::TypeScript);
-        } else if filepath.ends_with(".tsx") {
-            return Some(LanguageId::TypeScriptReact);
-        } else if filepath.ends_with(".js") {
-            return Some(LanguageId::JavaScript);
-        } else if filepath.ends_with(".jsx") {
-            return Some(LanguageId::JavaScriptReact);
-        } else if filepath.ends_with(".rs") {
-            return Some(LanguageId::Rust);
-        } else if filepath.ends_with(".go") {
-            return Some(LanguageId::Go);
-        } else if filepath.ends_with(".py") {
-            return Some(LanguageId::Python);
-        } else if filepath.ends_with(".cpp") || filepath.ends_with(".h") {
-            return Some(LanguageId::Cpp);
-        } else if filepath.ends_with(".c") {
-            return Some(LanguageId::C);
-        } else if filepath.ends_with(".cs") {
-            return Some(LanguageId::CSharp);
-        } else if filepath.ends_with(".m") {
-            return Some(LanguageId::ObjectiveC);
-        } else if filepath.ends_with(".mm") {
-            return Some(LanguageId::ObjectiveCpp);
-        } else if filepath.ends_with(".php") {
-            return Some(LanguageId::PHP);
-        } else if filepath.ends_with(".rb") {
-            return Some(LanguageId::Ruby);
-        } else if filepath.ends_with(".dart") {
-            return Some(LanguageId::Dart);
-        } else if filepath.ends_with(".pm")
-            || filepath.ends_with(".pl")
-            || filepath.ends_with(".PL")
-        {
-            // `.pl` is shared with Prolog, but `LanguageId` has no Prolog variant so no
-            // correct labelling is lost. `.t`, `.pod` and `.xs` are deliberately left
-            // unmapped: `.t` is widely used outside Perl, `.pod` is documentation rather
-            // than source, and `.xs` bodies are C.
-            return Some(LanguageId::Perl);
-        }
-        None
+        Ok(())
     }
 
     /// Process an external symbol from `Index.external_symbols`.
@@ -772,13 +755,23 @@ impl Env {
         else {
             // Neither a flat `range` nor a typed range: skip this occurrence rather
             // than aborting the whole index.
+            self.stats.occurrences_skipped_missing_range += 1;
             return Ok(());
         };
+        let Some(range) = self.validate_range(file_id, range) else {
+            return Ok(());
+        };
+        // Decode the enclosing range before emitting anything, so a bad
+        // enclosing range does not leave a dangling `scip.FileRange` behind
+        // when the occurrence is skipped.
+        let occ_enclosing_range = occurrence_enclosing_range(&occ);
+        let enclosing_range = self
+            .decode_range_for_file(file_id, &occ_enclosing_range, symbol_hint.as_deref())?
+            .and_then(|range| self.validate_range(file_id, range));
+        let original_location =
+            self.original_location(file_id, filepath, &occ_range, symbol_hint.as_deref());
         let file_range_id = self.next_id();
         self.out.file_range(file_range_id, file_id, range);
-        let occ_enclosing_range = occurrence_enclosing_range(&occ);
-        let enclosing_range =
-            self.decode_range_for_file(file_id, &occ_enclosing_range, symbol_hint.as_deref())?;
         match enclosing_range {
             None => {}
             Some(enclosing_range) => {
@@ -794,12 +787,26 @@ impl Env {
             }
         }
 
+        // The definition or reference is at the range in the original file,
+        // linked back to where it was generated.
+        let location_id = match original_location {
+            Some((target_id, original_range)) => {
+                let target_range_id = self.next_id();
+                self.out
+                    .file_range(target_range_id, target_id, original_range);
+                self.out.generated_range(target_range_id, file_range_id);
+                self.stats.ranges_attributed_to_original_files += 1;
+                target_range_id
+            }
+            None => file_range_id,
+        };
+
         match symbol {
             ScipSymbol::Local { id } => {
                 self.decode_local_occurrence(
                     format!("local {}", id),
                     SymbolRoleSet(occ.symbol_roles),
-                    file_range_id,
+                    location_id,
                     filepath,
                 );
             }
@@ -807,7 +814,7 @@ impl Env {
                 self.decode_global_occurrence(
                     occ.symbol,
                     SymbolRoleSet(occ.symbol_roles),
-                    file_range_id,
+                    location_id,
                     descriptors,
                 );
             }
@@ -817,22 +824,132 @@ impl Env {
     }
 
     fn decode_range_for_file(
-        &self,
+        &mut self,
         file_id: ScipId,
-        range: &[i32],
+        scip_range: &[i32],
         symbol_hint: Option<&str>,
     ) -> Result<Option<GleanRange>> {
-        let Some(range) = decode_scip_range(range)? else {
+        let Some(range) = decode_scip_range(scip_range)? else {
             return Ok(None);
         };
+        if scip_range_was_clamped(scip_range) {
+            self.stats.ranges_clamped += 1;
+        }
 
         Ok(Some(
-            self.go_line_directive_maps
+            self.line_remappers
                 .get(&file_id)
                 .map_or(range.clone(), |map| map.remap_range(range, symbol_hint)),
         ))
     }
 
+    /// The `src.File` `scip_range` was generated from and the range in it:
+    /// from a source map, or under `set_directive_file_attribution` the file
+    /// a line directive names, with the range's unmapped lines. `None` if
+    /// nothing covers the range, or it names the generated file itself.
+    fn original_location(
+        &mut self,
+        file_id: ScipId,
+        filepath: &str,
+        scip_range: &[i32],
+        symbol_hint: Option<&str>,
+    ) -> Option<(ScipId, GleanRange)> {
+        let remapper = self.line_remappers.get(&file_id)?;
+        if !self.attribute_to_directive_files && !remapper.always_attribute() {
+            return None;
+        }
+        let range = decode_scip_range(scip_range).ok()??;
+        let (target, range) = remapper.original_location(&range, symbol_hint)?;
+        let target = original_file_path(filepath, target)?;
+        if *target == *filepath {
+            return None;
+        }
+        let (target_id, seen) = self.get_or_set_fact(StringPredicate::File, target.clone());
+        if !seen {
+            let lang = self.language_map.language_of(&target);
+            self.out.src_file(target_id, target);
+            if let Some(lang) = lang {
+                let lang_file_id = self.next_id();
+                self.out.file_lang(lang_file_id, target_id, lang);
+            }
+        }
+        Some((target_id, range))
+    }
+
+    /// Record whether the source of a document without text could be read.
+    fn record_source_read<T>(&mut self, relative_path: &str, read: Option<&std::io::Result<T>>) {
+        self.stats.source_files_requested += 1;
+        match read {
+            Some(Err(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                self.stats.source_files_missing.push(relative_path);
+            }
+            Some(Err(err)) => {
+                tracing::warn!("Cannot read the source of {}: {}", relative_path, err);
+                self.stats.source_files_unreadable.push(relative_path);
+            }
+            Some(Ok(_)) | None => {}
+        }
+    }
+
+    /// Emit the digest of the file's contents and check it against the
+    /// expected one.
+    fn decode_file_digest(&mut self, file_id: ScipId, filepath: &str, bytes: &[u8]) {
+        let digest = sha256_hex(bytes);
+        match self
+            .expected_digests
+            .as_ref()
+            .map(|expected| expected.matches(filepath, &digest))
+        {
+            None | Some(Some(true)) => {}
+            Some(Some(false)) => {
+                tracing::warn!(
+                    "Contents of {} differ from the expected revision's",
+                    filepath
+                );
+                self.stats.files_with_unexpected_digests.push(filepath);
+            }
+            Some(None) => self.stats.files_without_expected_digests += 1,
+        }
+        self.out.file_digest(file_id, digest.into_boxed_str());
+    }
+
+    fn decode_file_content(&mut self, file_id: ScipId, bytes: &[u8]) {
+        let Some(max_bytes) = self.max_file_content_bytes else {
+            return;
+        };
+        if bytes.len() > max_bytes {
+            self.stats.file_contents_too_large += 1;
+            return;
+        }
+        match text_contents(bytes) {
+            Some(text) => self.out.file_content(file_id, text.into()),
+            None => self.stats.file_contents_binary += 1,
+        }
+    }
+
+    /// Check `range` against the file's line table, if known. Returns `None`
+    /// when the range-validation policy drops it.
+    fn validate_range(&mut self, file_id: ScipId, range: GleanRange) -> Option<GleanRange> {
+        let Some(table) = self.line_tables.get(&file_id) else {
+            return Some(range);
+        };
+        match table.check(range, self.range_validation) {
+            RangeCheck::InBounds(range) => Some(range),
+            RangeCheck::Adjusted(range) => {
+                self.stats.ranges_out_of_bounds += 1;
+                if self.range_validation == RangeValidation::Clip {
+                    self.stats.ranges_clipped += 1;
+                }
+                Some(range)
+            }
+            RangeCheck::Dropped => {
+                self.stats.ranges_out_of_bounds += 1;
+                self.stats.ranges_dropped += 1;
+                None
+            }
+        }
+    }
+
     fn decode_local_occurrence(
         &mut self,
         local_symbol: String,
@@ -955,7 +1072,7 @@ fn scip_kind_to_symbol_kind(kind: symbol_information::Kind) -> Option<SymbolKind
 ///
 /// Centralized here so every site that needs to look up or insert a symbol
 /// fact uses the same key shape — divergence here silently breaks lookups.
-fn qualify_scip_symbol(symbol: &str, filepath: &str) -> Box<str> {
+pub fn qualify_scip_symbol(symbol: &str, filepath: &str) -> Box<str> {
     match parse_scip_symbol(symbol) {
         ScipSymbol::Local { .. } => format!("{}/{}", filepath, symbol).into_boxed_str(),
         ScipSymbol::Global { .. } => symbol.to_owned().into_boxed_str(),
@@ -981,7 +1098,7 @@ fn symbol_range_hint(symbol: &ScipSymbol) -> Option<String> {
 /// `decode_scip_range` understands. Prefers the typed range (`single_line_range`
 /// / `multi_line_range`) over the deprecated `range` field, per the SCIP schema;
 /// returns an empty vec when the occurrence has neither.
-fn occurrence_range(occ: &Occurrence) -> Vec<i32> {
+pub fn occurrence_range(occ: &Occurrence) -> Vec<i32> {
     if occ.has_single_line_range() {
         let r = occ.single_line_range();
         vec![r.line, r.start_character, r.end_character]
@@ -995,7 +1112,7 @@ fn occurrence_range(occ: &Occurrence) -> Vec<i32> {
 
 /// Like `occurrence_range`, but for the enclosing range: prefers the
 /// `typed_enclosing_range` oneof, else the deprecated `enclosing_range` field.
-fn occurrence_enclosing_range(occ: &Occurrence) -> Vec<i32> {
+pub fn occurrence_enclosing_range(occ: &Occurrence) -> Vec<i32> {
     if occ.has_single_line_enclosing_range() {
         let r = occ.single_line_enclosing_range();
         vec![r.line, r.start_character, r.end_character]
@@ -1011,6 +1128,45 @@ fn occurrence_enclosing_range(occ: &Occurrence) -> Vec<i32> {
 mod tests {
     use super::*;
 
+    /// Sources that count how often they are read.
+    struct CountingSources(std::cell::Cell<usize>);
+
+    impl SourceProvider for CountingSources {
+        fn read(&self, _relative_path: &str) -> std::io::Result<Vec<u8>> {
+            self.0.set(self.0.get() + 1);
+            Ok(b"@interface A\n@end\n".to_vec())
+        }
+    }
+
+    #[test]
+    fn test_source_read_once_per_file() {
+        let sources = CountingSources(std::cell::Cell::new(0));
+        let mut env = Env::new();
+        let paths = PathMapping::default();
+        let mut doc = Document::new();
+        doc.relative_path = "include/a.h".to_string();
+
+        // Sniffing the header reads it, decoding reuses what was read.
+        let (lang, source_read) = env.infer_lang_reading_source(None, true, &doc, Some(&sources));
+        assert_eq!(lang, LanguageId::ObjectiveC);
+        env.decode_scip_doc(lang, &paths, Some(&sources), doc.clone(), source_read)
+            .unwrap();
+        assert_eq!(sources.0.get(), 1);
+
+        // Later same-path documents do not read it again.
+        env.decode_scip_doc(lang, &paths, Some(&sources), doc, None)
+            .unwrap();
+        assert_eq!(sources.0.get(), 1);
+    }
+
+    #[test]
+    fn test_text_contents() {
+        assert_eq!(text_contents(b"fn main() {}\n"), Some("fn main() {}\n"));
+        assert_eq!(text_contents("caf\u{e9}".as_bytes()), Some("caf\u{e9}"));
+        assert_eq!(text_contents(b"\x7fELF\0\0"), None);
+        assert_eq!(text_contents(b"caf\xe9"), None);
+    }
+
     #[test]
     fn test_compute_file_lines_empty() {
         let (lengths, ends_in_newline, has_unicode_or_tabs) = compute_file_lines(b"");
@@ -1075,54 +1231,28 @@ mod tests {
         assert!(!ends_in_newline);
     }
 
-    #[test]
-    fn test_normalize_filepath_no_dots() {
-        assert_eq!(
-            normalize_filepath("foo/bar/baz.go"),
-            Some("foo/bar/baz.go".to_string())
-        );
-    }
-
-    #[test]
-    fn test_normalize_filepath_with_parent() {
-        assert_eq!(
-            normalize_filepath("foo/bar/../baz.go"),
-            Some("foo/baz.go".to_string())
-        );
-    }
-
-    #[test]
-    fn test_normalize_filepath_with_curdir() {
-        assert_eq!(
-            normalize_filepath("foo/./bar/baz.go"),
-            Some("foo/bar/baz.go".to_string())
-        );
-    }
-
-    #[test]
-    fn test_normalize_filepath_too_many_parents() {
-        assert_eq!(normalize_filepath("foo/../../baz.go"), None);
-    }
-
     #[test]
     fn test_file_language_of_perl() {
         let env = Env::new();
         assert!(
             matches!(
-                env.file_language_of("lib/Foo/Bar.pm"),
+                env.language_map.language_of("lib/Foo/Bar.pm"),
                 Some(LanguageId::Perl)
             ),
             "`.pm` is a Perl module"
         );
         assert!(
             matches!(
-                env.file_language_of("bin/deploy.pl"),
+                env.language_map.language_of("bin/deploy.pl"),
                 Some(LanguageId::Perl)
             ),
             "`.pl` is a Perl script"
         );
         assert!(
-            matches!(env.file_language_of("Makefile.PL"), Some(LanguageId::Perl)),
+            matches!(
+                env.language_map.language_of("Makefile.PL"),
+                Some(LanguageId::Perl)
+            ),
             "`.PL` is a Perl build script"
         );
     }
@@ -1131,15 +1261,15 @@ mod tests {
     fn test_file_language_of_perl_adjacent_extensions_unmapped() {
         let env = Env::new();
         assert!(
-            env.file_language_of("t/basic.t").is_none(),
+            env.language_map.language_of("t/basic.t").is_none(),
             "`.t` is not Perl-specific"
         );
         assert!(
-            env.file_language_of("lib/Foo.pod").is_none(),
+            env.language_map.language_of("lib/Foo.pod").is_none(),
             "`.pod` is documentation, not source"
         );
         assert!(
-            env.file_language_of("Foo.xs").is_none(),
+            env.language_map.language_of("Foo.xs").is_none(),
             "`.xs` bodies are C"
         );
     }
@@ -1148,13 +1278,18 @@ mod tests {
     fn test_file_language_of_unaffected_by_perl_arm() {
         let env = Env::new();
         assert!(matches!(
-            env.file_language_of("main.rs"),
+            env.language_map.language_of("main.rs"),
             Some(LanguageId::Rust)
         ));
         assert!(matches!(
-            env.file_language_of("main.py"),
+            env.language_map.language_of("main.py"),
             Some(LanguageId::Python)
         ));
-        assert!(env.file_language_of("README.md").is_none());
+        assert!(env.language_map.language_of("README").is_none());
+        // Markdown used to be unmapped, the built-in table now covers it.
+        assert_eq!(
+            env.language_map.language_of("README.md"),
+            Some(LanguageId::Markdown)
+        );
     }
 }
@@ -0,0 +1,401 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! TOML configuration for conversions, passed with `--config`.
+//!
+//! Top-level keys mirror the conversion flags (with `_` for `-`). Each
+//! `[[input]]` table names an index and may override the decoding settings
+//! (`language`, `infer_language`, `root_prefix`, `strip_prefix`,
+//! `source_root`, `rewrite`) for that input only:
+//!
+//! ```toml
+//! output = "out"
+//! shard = 100000
+//! root_prefix = "fbcode/"
+//!
+//! [[input]]
+//! path = "rust.scip"
+//!
+//! [[input]]
+//! path = "py.scip"
+//! root_prefix = "fbcode/py/"
+//! language = "python"
+//! rewrite = ["prefix:bazel-out/k8-fastbuild/bin/=gen/", "prefix:bazel-out/"]
+//! ```
+//!
+//! `input` may also be a list of paths. Relative paths are resolved against
+//! the directory of the config file. Flags given on the command line take
+//! precedence over the file, for every input; `--input` replaces the file's
+//! inputs.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use toml_edit::DocumentMut;
+use toml_edit::Item;
+use toml_edit::Table;
+
+use crate::BuildJsonArgs;
+use crate::DecodeOptions;
+use crate::lsif::LanguageId;
+use crate::paths::PathRewrites;
+
+/// Settings that apply to a whole conversion, or to a single input.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputSettings {
+    pub language: Option<String>,
+    pub infer_language: Option<bool>,
+    pub root_prefix: Option<String>,
+    pub strip_prefix: Option<String>,
+    pub source_root: Option<PathBuf>,
+    /// Path rewrite rules, see `crate::paths`. Replaces the rules of the
+    /// level below, rather than adding to them.
+    pub rewrite: Option<Vec<String>>,
+}
+
+impl InputSettings {
+    /// `self`, with unset settings taken from `fallback`.
+    fn or(&self, fallback: &InputSettings) -> InputSettings {
+        InputSettings {
+            language: self.language.clone().or_else(|| fallback.language.clone()),
+            infer_language: self.infer_language.or(fallback.infer_language),
+            root_prefix: self
+                .root_prefix
+                .clone()
+                .or_else(|| fallback.root_prefix.clone()),
+            strip_prefix: self
+                .strip_prefix
+                .clone()
+                .or_else(|| fallback.strip_prefix.clone()),
+            source_root: self
+                .source_root
+                .clone()
+                .or_else(|| fallback.source_root.clone()),
+            rewrite: self.rewrite.clone().or_else(|| fallback.rewrite.clone()),
+        }
+    }
+
+    /// The options to decode an input with, path normalization taken from
+    /// the conversion-wide `args`.
+    fn decode_options(self, args: &BuildJsonArgs) -> Result<DecodeOptions> {
+        Ok(DecodeOptions {
+            default_language: self
+                .language
+                .as_ref()
+                .and_then(|s| LanguageId::new(s).known()),
+            // Mapping languages is pointless without inferring them.
+            infer_language: self.infer_language.unwrap_or(!args.language_map.is_empty()),
+            path_prefix: self.root_prefix,
+            strip_prefix: self.strip_prefix,
+            source_root: self.source_root,
+            ignore_project_root_sources: false,
+            source_archive: args.source_archive.clone(),
+            source_tree: args.source_tree.clone(),
+            source_repo: args.source_repo.clone(),
+            path_rewrites: PathRewrites::parse(&self.rewrite.unwrap_or_default())?,
+            normalize_paths: args.normalize_paths,
+            escaping_paths: args.escaping_paths,
+            resolve_project_root: args.resolve_project_root,
+            repo_root: args.repo_root.clone(),
+            find_repo_root: args.find_repo_root,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputConfig {
+    pub path: PathBuf,
+    /// Overrides of the file's top-level settings for this input.
+    pub settings: InputSettings,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConversionConfig {
+    pub inputs: Vec<InputConfig>,
+    pub output: Option<PathBuf>,
+    pub settings: InputSettings,
+    pub shard: Option<usize>,
+    pub stats_out: Option<PathBuf>,
+    pub errors_out: Option<PathBuf>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub only_language: Vec<String>,
+}
+
+impl ConversionConfig {
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Error reading config file {}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&text, base).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    /// Parse a config, resolving relative paths against `base`.
+    pub fn parse(text: &str, base: &Path) -> Result<Self> {
+        let doc: DocumentMut = text.parse()?;
+        let mut config = ConversionConfig::default();
+        for (key, item) in doc.iter() {
+            if parse_setting(&mut config.settings, key, item, base)? {
+                continue;
+            }
+            match key {
+                "input" => config.inputs = parse_inputs(item, base)?,
+                "output" => config.output = Some(path(key, item, base)?),
+                "shard" => {
+                    let shard = integer(key, item)?;
+                    config.shard = Some(
+                        usize::try_from(shard)
+                            .map_err(|_| anyhow!("`shard` must be positive, got {}", shard))?,
+                    );
+                }
+                "stats_out" => config.stats_out = Some(path(key, item, base)?),
+                "errors_out" => config.errors_out = Some(path(key, item, base)?),
+                "include" => config.include = strings(key, item)?,
+                "exclude" => config.exclude = strings(key, item)?,
+                "only_language" => config.only_language = strings(key, item)?,
+                _ => bail!("Unknown key `{}`", key),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Parse `key` if it is a per-input setting. Returns whether it was one.
+fn parse_setting(
+    settings: &mut InputSettings,
+    key: &str,
+    item: &Item,
+    base: &Path,
+) -> Result<bool> {
+    match key {
+        "language" => settings.language = Some(string(key, item)?),
+        "infer_language" => {
+            settings.infer_language = Some(
+                item.as_bool()
+                    .ok_or_else(|| anyhow!("`{}` must be a boolean", key))?,
+            )
+        }
+        "root_prefix" => settings.root_prefix = Some(string(key, item)?),
+        "strip_prefix" => settings.strip_prefix = Some(string(key, item)?),
+        "source_root" => settings.source_root = Some(path(key, item, base)?),
+        "rewrite" => {
+            let rules = strings(key, item)?;
+            // Report bad rules with the config file's name.
+            PathRewrites::parse(&rules)?;
+            settings.rewrite = Some(rules);
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn parse_inputs(item: &Item, base: &Path) -> Result<Vec<InputConfig>> {
+    if let Some(tables) = item.as_array_of_tables() {
+        return tables
+            .iter()
+            .map(|table| parse_input_table(table, base))
+            .collect();
+    }
+    let paths = strings("input", item)
+        .map_err(|_| anyhow!("`input` must be a list of paths or [[input]] tables"))?;
+    Ok(paths
+        .into_iter()
+        .map(|path| InputConfig {
+            path: base.join(path),
+            settings: InputSettings::default(),
+        })
+        .collect())
+}
+
+fn parse_input_table(table: &Table, base: &Path) -> Result<InputConfig> {
+    let mut path = None;
+    let mut settings = InputSettings::default();
+    for (key, item) in table.iter() {
+        if key == "path" {
+            path = Some(self::path(key, item, base)?);
+        } else if !parse_setting(&mut settings, key, item, base)? {
+            bail!("Unknown key `{}` in [[input]]", key);
+        }
+    }
+    Ok(InputConfig {
+        path: path.ok_or_else(|| anyhow!("[[input]] without a `path`"))?,
+        settings,
+    })
+}
+
+fn string(key: &str, item: &Item) -> Result<String> {
+    item.as_str()
+        .map(str::to_owned)
+        .ok_or_else(|| anyhow!("`{}` must be a string", key))
+}
+
+fn path(key: &str, item: &Item, base: &Path) -> Result<PathBuf> {
+    Ok(base.join(string(key, item)?))
+}
+
+fn integer(key: &str, item: &Item) -> Result<i64> {
+    item.as_integer()
+        .ok_or_else(|| anyhow!("`{}` must be an integer", key))
+}
+
+fn strings(key: &str, item: &Item) -> Result<Vec<String>> {
+    let array = item
+        .as_array()
+        .ok_or_else(|| anyhow!("`{}` must be a list of strings", key))?;
+    array
+        .iter()
+        .map(|value| {
+            value
+                .as_str()
+                .map(str::to_owned)
+                .ok_or_else(|| anyhow!("`{}` must be a list of strings", key))
+        })
+        .collect()
+}
+
+/// Fill the unset fields of `args` from `config`, and return each input
+/// with the options to decode it with. Without a config, every input uses
+/// the flags' settings.
+pub fn resolve_inputs(
+    args: &mut BuildJsonArgs,
+    config: Option<ConversionConfig>,
+) -> Result<Vec<(PathBuf, DecodeOptions)>> {
+    let flags = InputSettings {
+        language: args.language.clone(),
+        infer_language: args.infer_language.then_some(true),
+        root_prefix: args.root_prefix.clone(),
+        strip_prefix: args.strip_prefix.clone(),
+        source_root: args.source_root.clone(),
+        rewrite: (!args.rewrite.is_empty()).then(|| args.rewrite.clone()),
+    };
+    let config = config.unwrap_or_default();
+    let settings = flags.or(&config.settings);
+
+    args.output = args.output.take().or(config.output);
+    args.shard = args.shard.or(config.shard);
+    args.stats_out = args.stats_out.take().or(config.stats_out);
+    args.errors_out = args.errors_out.take().or(config.errors_out);
+    let filter = &mut args.filter;
+    for (flag, configured) in [
+        (&mut filter.include, config.include),
+        (&mut filter.exclude, config.exclude),
+        (&mut filter.only_language, config.only_language),
+    ] {
+        if flag.is_empty() {
+            *flag = configured;
+        }
+    }
+    args.language = settings.language.clone();
+    args.infer_language = settings.infer_language.unwrap_or(false);
+    args.root_prefix = settings.root_prefix.clone();
+    args.strip_prefix = settings.strip_prefix.clone();
+    args.source_root = settings.source_root.clone();
+    args.rewrite = settings.rewrite.clone().unwrap_or_default();
+
+    if !args.input.is_empty() || config.inputs.is_empty() {
+        return args
+            .input
+            .iter()
+            .map(|path| Ok((path.clone(), settings.clone().decode_options(args)?)))
+            .collect();
+    }
+    args.input = config
+        .inputs
+        .iter()
+        .map(|input| input.path.clone())
+        .collect();
+    config
+        .inputs
+        .into_iter()
+        .map(|input| {
+            let options = flags
+                .or(&input.settings)
+                .or(&config.settings)
+                .decode_options(args)?;
+            Ok((input.path, options))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        output = "out"
+        shard = 1000
+        root_prefix = "fbcode/"
+        infer_language = true
+        exclude = ["gen/**"]
+
+        [[input]]
+        path = "rust.scip"
+
+        [[input]]
+        path = "/abs/py.scip"
+        root_prefix = "fbcode/py/"
+        language = "python"
+        source_root = "src"
+        rewrite = ["prefix:bazel-out/"]
+    "#;
+
+    #[test]
+    fn test_parse_config() {
+        let config = ConversionConfig::parse(CONFIG, Path::new("/conf")).unwrap();
+        assert_eq!(config.output, Some(PathBuf::from("/conf/out")));
+        assert_eq!(config.shard, Some(1000));
+        assert_eq!(config.exclude, vec!["gen/**"]);
+        assert_eq!(config.settings.root_prefix.as_deref(), Some("fbcode/"));
+        assert_eq!(config.settings.infer_language, Some(true));
+        assert_eq!(
+            config.inputs,
+            vec![
+                InputConfig {
+                    path: PathBuf::from("/conf/rust.scip"),
+                    settings: InputSettings::default(),
+                },
+                InputConfig {
+                    path: PathBuf::from("/abs/py.scip"),
+                    settings: InputSettings {
+                        language: Some("python".to_string()),
+                        root_prefix: Some("fbcode/py/".to_string()),
+                        source_root: Some(PathBuf::from("/conf/src")),
+                        rewrite: Some(vec!["prefix:bazel-out/".to_string()]),
+                        ..Default::default()
+                    },
+                },
+            ]
+        );
+
+        let config =
+            ConversionConfig::parse(r#"input = ["a.scip", "b.scip"]"#, Path::new("")).unwrap();
+        let paths: Vec<_> = config.inputs.iter().map(|i| i.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("a.scip"), PathBuf::from("b.scip")]
+        );
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        let err = |text: &str| {
+            ConversionConfig::parse(text, Path::new(""))
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(err("sharding = 3").contains("Unknown key `sharding`"));
+        assert!(err("shard = \"big\"").contains("`shard` must be an integer"));
+        assert!(err("[[input]]\nlanguage = \"go\"").contains("without a `path`"));
+        assert!(err("[[input]]\npath = \"a\"\nshard = 1").contains("in [[input]]"));
+        assert!(err("rewrite = [\"bazel-out/\"]").contains("must start with"));
+    }
+}
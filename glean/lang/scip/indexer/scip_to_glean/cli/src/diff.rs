@@ -0,0 +1,301 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! `scip-to-glean diff`: compare the output of two SCIP indexes, e.g. before
+//! and after an indexer version bump.
+//!
+//! Both indexes are decoded through `Env`, exactly like a conversion, and
+//! compared on the resolved facts. Kinds therefore reflect
+//! `SymbolInformation.kind` overrides and local symbols are qualified by
+//! file, so the diff shows what Glean would see rather than raw SCIP.
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::DecodeOptions;
+use crate::angle::Env;
+use crate::decode_scip_data;
+use crate::lsif::LanguageId;
+use crate::output::Location;
+use crate::output::ResolvedFacts;
+use crate::output::ResolvedFile;
+use crate::validate::ReportFormat;
+
+#[derive(clap::Args, Debug)]
+pub struct DiffArgs {
+    #[arg(help = "The baseline SCIP index")]
+    pub old: PathBuf,
+
+    #[arg(help = "The SCIP index to compare against the baseline")]
+    pub new: PathBuf,
+
+    #[arg(
+        long,
+        help = "Infer the language of documents that do not set one from their file name or extension"
+    )]
+    pub infer_language: bool,
+
+    #[arg(
+        long,
+        help = "The default language to use for files without a recognized extension."
+    )]
+    pub language: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+
+    #[arg(short, long, help = "Write the report to this file instead of stdout")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffSummary {
+    pub documents_added: usize,
+    pub documents_removed: usize,
+    /// Documents present in both indexes whose locations differ.
+    pub documents_changed: usize,
+    pub definitions_gained: usize,
+    pub definitions_lost: usize,
+    pub references_gained: usize,
+    pub references_lost: usize,
+    pub symbols_added: usize,
+    pub symbols_removed: usize,
+    pub symbols_changed: usize,
+}
+
+/// Locations gained or lost in one file. Files only present in one index
+/// gain or lose all of their locations.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiff {
+    pub path: Box<str>,
+    pub definitions_gained: Vec<Location>,
+    pub definitions_lost: Vec<Location>,
+    pub references_gained: Vec<Location>,
+    pub references_lost: Vec<Location>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Change<T> {
+    pub old: T,
+    pub new: T,
+}
+
+/// A symbol present in both indexes whose metadata differs. Unchanged
+/// properties are omitted.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolChange {
+    pub symbol: Box<str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kinds: Option<Change<BTreeSet<String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_names: Option<Change<BTreeSet<Box<str>>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub documentation: Option<Change<BTreeSet<Box<str>>>>,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexDiff {
+    pub summary: DiffSummary,
+    pub documents_added: Vec<Box<str>>,
+    pub documents_removed: Vec<Box<str>>,
+    pub files: Vec<FileDiff>,
+    pub symbols_added: Vec<Box<str>>,
+    pub symbols_removed: Vec<Box<str>>,
+    pub symbols_changed: Vec<SymbolChange>,
+}
+
+impl IndexDiff {
+    pub fn new(old: &ResolvedFacts, new: &ResolvedFacts) -> Self {
+        let mut diff = IndexDiff::default();
+        let empty = ResolvedFile::default();
+
+        let paths: BTreeSet<&Box<str>> = old.files.keys().chain(new.files.keys()).collect();
+        for path in paths {
+            let (old_file, new_file) = match (old.files.get(path), new.files.get(path)) {
+                (Some(old_file), Some(new_file)) => (old_file, new_file),
+                (None, Some(new_file)) => {
+                    diff.documents_added.push(path.clone());
+                    (&empty, new_file)
+                }
+                (Some(old_file), None) => {
+                    diff.documents_removed.push(path.clone());
+                    (old_file, &empty)
+                }
+                (None, None) => unreachable!(),
+            };
+            if old_file == new_file {
+                continue;
+            }
+            let difference = |a: &BTreeSet<Location>, b: &BTreeSet<Location>| {
+                a.difference(b).cloned().collect::<Vec<_>>()
+            };
+            let file = FileDiff {
+                path: path.clone(),
+                definitions_gained: difference(&new_file.definitions, &old_file.definitions),
+                definitions_lost: difference(&old_file.definitions, &new_file.definitions),
+                references_gained: difference(&new_file.references, &old_file.references),
+                references_lost: difference(&old_file.references, &new_file.references),
+            };
+            diff.summary.definitions_gained += file.definitions_gained.len();
+            diff.summary.definitions_lost += file.definitions_lost.len();
+            diff.summary.references_gained += file.references_gained.len();
+            diff.summary.references_lost += file.references_lost.len();
+            if old.files.contains_key(path) && new.files.contains_key(path) {
+                diff.summary.documents_changed += 1;
+            }
+            diff.files.push(file);
+        }
+
+        let symbols: BTreeSet<&Box<str>> = old.symbols.keys().chain(new.symbols.keys()).collect();
+        for symbol in symbols {
+            let (old_symbol, new_symbol) = match (old.symbols.get(symbol), new.symbols.get(symbol))
+            {
+                (Some(old_symbol), Some(new_symbol)) => (old_symbol, new_symbol),
+                (None, _) => {
+                    diff.symbols_added.push(symbol.clone());
+                    continue;
+                }
+                (_, None) => {
+                    diff.symbols_removed.push(symbol.clone());
+                    continue;
+                }
+            };
+            if old_symbol == new_symbol {
+                continue;
+            }
+            fn change<T: Clone + Eq>(old: &T, new: &T) -> Option<Change<T>> {
+                (old != new).then(|| Change {
+                    old: old.clone(),
+                    new: new.clone(),
+                })
+            }
+            diff.symbols_changed.push(SymbolChange {
+                symbol: symbol.clone(),
+                kinds: change(&old_symbol.kinds, &new_symbol.kinds),
+                display_names: change(&old_symbol.display_names, &new_symbol.display_names),
+                documentation: change(&old_symbol.documentation, &new_symbol.documentation),
+            });
+        }
+
+        diff.summary.documents_added = diff.documents_added.len();
+        diff.summary.documents_removed = diff.documents_removed.len();
+        diff.summary.symbols_added = diff.symbols_added.len();
+        diff.summary.symbols_removed = diff.symbols_removed.len();
+        diff.summary.symbols_changed = diff.symbols_changed.len();
+        diff
+    }
+
+    pub fn write(&self, format: ReportFormat, mut w: impl Write) -> Result<()> {
+        match format {
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut w, self)?;
+                writeln!(w)?;
+            }
+            ReportFormat::Text => {
+                let s = &self.summary;
+                writeln!(
+                    w,
+                    "documents: +{} -{} ~{}",
+                    s.documents_added, s.documents_removed, s.documents_changed
+                )?;
+                writeln!(
+                    w,
+                    "definitions: +{} -{}",
+                    s.definitions_gained, s.definitions_lost
+                )?;
+                writeln!(
+                    w,
+                    "references: +{} -{}",
+                    s.references_gained, s.references_lost
+                )?;
+                writeln!(
+                    w,
+                    "symbols: +{} -{} ~{}",
+                    s.symbols_added, s.symbols_removed, s.symbols_changed
+                )?;
+                for file in &self.files {
+                    writeln!(
+                        w,
+                        "{}: definitions +{} -{}, references +{} -{}",
+                        file.path,
+                        file.definitions_gained.len(),
+                        file.definitions_lost.len(),
+                        file.references_gained.len(),
+                        file.references_lost.len()
+                    )?;
+                }
+                for change in &self.symbols_changed {
+                    let mut changed = Vec::new();
+                    if let Some(kinds) = &change.kinds {
+                        changed.push(format!(
+                            "kind {:?} -> {:?}",
+                            Vec::from_iter(&kinds.old),
+                            Vec::from_iter(&kinds.new)
+                        ));
+                    }
+                    if change.display_names.is_some() {
+                        changed.push("display name".to_owned());
+                    }
+                    if change.documentation.is_some() {
+                        changed.push("documentation".to_owned());
+                    }
+                    writeln!(w, "`{}`: {}", change.symbol, changed.join(", "))?;
+                }
+            }
+        }
+        w.flush()?;
+        Ok(())
+    }
+}
+
+fn resolve_index(path: &Path, args: &DiffArgs) -> Result<ResolvedFacts> {
+    let default_language = args
+        .language
+        .as_ref()
+        .and_then(|s| LanguageId::new(s).known());
+    let options = DecodeOptions {
+        default_language,
+        infer_language: args.infer_language,
+        // Compare the indexes alone, whatever is checked out here.
+        ignore_project_root_sources: true,
+        ..Default::default()
+    };
+    let mut env = Env::new();
+    decode_scip_data(&mut env, path, &options)?;
+    Ok(env.output().resolve())
+}
+
+pub fn diff(args: &DiffArgs) -> Result<IndexDiff> {
+    let old = resolve_index(&args.old, args)?;
+    let new = resolve_index(&args.new, args)?;
+    Ok(IndexDiff::new(&old, &new))
+}
+
+/// Run `diff` and write the report to `--output`, or stdout.
+pub fn run_diff(args: &DiffArgs) -> Result<()> {
+    let diff = diff(args)?;
+    match &args.output {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Error creating report file {}", path.display()))?;
+            diff.write(args.format, std::io::BufWriter::new(file))
+        }
+        None => diff.write(args.format, std::io::stdout().lock()),
+    }
+}
@@ -0,0 +1,105 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Content digests of source files, emitted as `src.FileDigest`, and the
+//! digests a revision is expected to have, given with `--expected-digests`.
+//!
+//! The expected digests are in `sha256sum` format, one `<digest>  <path>`
+//! line per file, with paths as in the output (after rewriting and
+//! prefixing):
+//!
+//! ```text
+//! 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08  src/main.rs
+//! ```
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use sha2::Digest;
+use sha2::Sha256;
+
+/// The SHA-256 digest of `bytes`, in lowercase hex.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct ExpectedDigests {
+    digests: HashMap<String, String>,
+}
+
+impl ExpectedDigests {
+    pub fn read(path: &Path) -> Result<ExpectedDigests> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Error reading digests file {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid digests file {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<ExpectedDigests> {
+        let mut digests = HashMap::new();
+        for (n, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            // `sha256sum` separates binary-mode paths with ` *`.
+            let Some((digest, path)) = line
+                .split_once("  ")
+                .or_else(|| line.split_once(" *"))
+                .filter(|(digest, _)| {
+                    digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit())
+                })
+            else {
+                bail!("Line {}: expected `<sha256>  <path>`", n + 1);
+            };
+            digests.insert(path.to_owned(), digest.to_ascii_lowercase());
+        }
+        Ok(ExpectedDigests { digests })
+    }
+
+    /// Whether the file at `path` has the expected `digest`. `None` if no
+    /// digest is expected for it.
+    pub fn matches(&self, path: &str, digest: &str) -> Option<bool> {
+        self.digests.get(path).map(|expected| expected == digest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"test"),
+            "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+        );
+    }
+
+    #[test]
+    fn test_expected_digests() {
+        let digest = sha256_hex(b"test");
+        let expected = ExpectedDigests::parse(&format!(
+            "{}  src/a.rs\n\n{} *src/b c.rs\n",
+            digest,
+            digest.to_ascii_uppercase()
+        ))
+        .unwrap();
+        assert_eq!(expected.matches("src/a.rs", &digest), Some(true));
+        assert_eq!(expected.matches("src/b c.rs", &digest), Some(true));
+        assert_eq!(expected.matches("src/a.rs", &sha256_hex(b"")), Some(false));
+        assert_eq!(expected.matches("src/d.rs", &digest), None);
+        assert!(ExpectedDigests::parse("abc  src/a.rs\n").is_err());
+        assert!(ExpectedDigests::parse(&digest).is_err());
+    }
+}
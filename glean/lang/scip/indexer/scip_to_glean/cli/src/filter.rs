@@ -0,0 +1,146 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Selecting a subset of an index's documents by path or language.
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use globset::GlobBuilder;
+use globset::GlobSet;
+use globset::GlobSetBuilder;
+
+use crate::lsif::LanguageId;
+
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct DocumentFilterArgs {
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "Only keep documents whose relative path matches one of these globs (`*` does not match `/`, `**` does)"
+    )]
+    pub include: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "Drop documents whose relative path matches one of these globs, even if included"
+    )]
+    pub exclude: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "LANGUAGE",
+        help = "Only keep documents of these languages, e.g. `go` or `typescriptreact`"
+    )]
+    pub only_language: Vec<String>,
+}
+
+impl DocumentFilterArgs {
+    pub fn build(&self) -> Result<DocumentFilter> {
+        let languages = self
+            .only_language
+            .iter()
+            .map(|name| {
+                LanguageId::new(name)
+                    .known()
+                    .ok_or_else(|| anyhow!("Unknown language `{}` in --only-language", name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(DocumentFilter {
+            include: (!self.include.is_empty())
+                .then(|| glob_set(&self.include))
+                .transpose()?,
+            exclude: glob_set(&self.exclude)?,
+            languages,
+        })
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob `{}`", pattern))?;
+        builder.add(glob);
+    }
+    Ok(builder.build()?)
+}
+
+/// Decides which documents to keep. The default keeps everything.
+#[derive(Clone, Debug, Default)]
+pub struct DocumentFilter {
+    /// `None` includes every path.
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    /// Empty keeps every language.
+    languages: Vec<LanguageId>,
+}
+
+impl DocumentFilter {
+    /// Whether a document with this `relative_path` and resolved language
+    /// passes the filter.
+    pub fn matches(&self, path: &str, lang: LanguageId) -> bool {
+        self.include.as_ref().is_none_or(|set| set.is_match(path))
+            && !self.exclude.is_match(path)
+            && (self.languages.is_empty() || self.languages.contains(&lang))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str], only_language: &[&str]) -> DocumentFilter {
+        let strings = |s: &[&str]| s.iter().map(|s| s.to_string()).collect();
+        DocumentFilterArgs {
+            include: strings(include),
+            exclude: strings(exclude),
+            only_language: strings(only_language),
+        }
+        .build()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_default_keeps_everything() {
+        let filter = DocumentFilter::default();
+        assert!(filter.matches("a/b.go", LanguageId::Go));
+        assert!(filter.matches("", LanguageId::UnknownLanguage));
+    }
+
+    #[test]
+    fn test_include_and_exclude_globs() {
+        let filter = filter(&["src/**/*.rs", "build.rs"], &["src/gen/**"], &[]);
+        assert!(filter.matches("src/main.rs", LanguageId::Rust));
+        assert!(filter.matches("src/a/b/c.rs", LanguageId::Rust));
+        assert!(filter.matches("build.rs", LanguageId::Rust));
+        assert!(!filter.matches("src/gen/out.rs", LanguageId::Rust));
+        assert!(!filter.matches("tests/t.rs", LanguageId::Rust));
+        // `*` does not cross directories.
+        assert!(!filter.matches("x/build.rs", LanguageId::Rust));
+    }
+
+    #[test]
+    fn test_language_filter() {
+        let filter = filter(&[], &[], &["go", "TypeScript"]);
+        assert!(filter.matches("a.go", LanguageId::Go));
+        assert!(filter.matches("a.ts", LanguageId::TypeScript));
+        assert!(!filter.matches("a.tsx", LanguageId::TypeScriptReact));
+
+        let err = DocumentFilterArgs {
+            only_language: vec!["klingon".to_string()],
+            ..Default::default()
+        }
+        .build()
+        .unwrap_err();
+        assert!(err.to_string().contains("klingon"));
+    }
+}
@@ -0,0 +1,616 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Inferring a document's language from its file name, for documents that
+//! do not set `Document.language`.
+//!
+//! A built-in table maps extensions and whole file names to languages.
+//! Overrides are written `.EXT=LANGUAGE` or `NAME=LANGUAGE`, e.g. `.h=c` or
+//! `BUCK=python`, given with `--language-map` or one per line in a
+//! `--language-map-file` (blank lines and `#` comments are ignored).
+//! Overrides replace built-in entries for the same extension or name.
+//!
+//! When the name says nothing, or only that the file is a header shared
+//! between C and C++, the language is sniffed from the document's source if
+//! it is available: a shebang line, an Emacs (`-*- mode: c++ -*-`) or Vim
+//! (`vim: set ft=python:`) modeline, and for headers, C++ or Objective-C
+//! syntax.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+
+use crate::lsif::LanguageId;
+
+/// Whole file names, matched before extensions.
+const FILENAMES: &[(&str, LanguageId)] = {
+    use LanguageId::*;
+    &[
+        ("BUCK", Python),
+        ("TARGETS", Python),
+        ("BUILD", Python),
+        ("BUILD.bazel", Python),
+        ("WORKSPACE", Python),
+        ("COMMIT_EDITMSG", Git),
+        ("git-rebase-todo", Git),
+        ("Dockerfile", Dockerfile),
+        ("Containerfile", Dockerfile),
+        ("Gemfile", Ruby),
+        ("Rakefile", Ruby),
+        ("GNUmakefile", Makefile),
+        ("Makefile", Makefile),
+        ("makefile", Makefile),
+    ]
+};
+
+/// Extensions, with their leading `.`. Matched case-sensitively first, so
+/// `.PL` can differ from `.pl`, then in lower case.
+const EXTENSIONS: &[(&str, LanguageId)] = {
+    use LanguageId::*;
+    &[
+        (".abap", ABAP),
+        (".bat", WindowsBat),
+        (".cmd", WindowsBat),
+        (".bib", BibTeX),
+        (".clj", Clojure),
+        (".cljc", Clojure),
+        (".cljs", Clojure),
+        (".edn", Clojure),
+        (".coffee", Coffeescript),
+        (".c", C),
+        (".cpp", Cpp),
+        (".cc", Cpp),
+        (".cxx", Cpp),
+        (".c++", Cpp),
+        // Headers are shared with C; C++ is the more common owner.
+        (".h", Cpp),
+        (".hpp", Cpp),
+        (".hh", Cpp),
+        (".hxx", Cpp),
+        (".h++", Cpp),
+        (".ipp", Cpp),
+        (".tcc", Cpp),
+        (".inl", Cpp),
+        (".cs", CSharp),
+        (".css", CSS),
+        (".diff", Diff),
+        (".patch", Diff),
+        (".dart", Dart),
+        (".dockerfile", Dockerfile),
+        (".ex", Elixir),
+        (".exs", Elixir),
+        (".erl", Erlang),
+        (".hrl", Erlang),
+        (".fs", FSharp),
+        (".fsi", FSharp),
+        (".fsx", FSharp),
+        (".go", Go),
+        (".groovy", Groovy),
+        (".gradle", Groovy),
+        (".hbs", Handlebars),
+        (".handlebars", Handlebars),
+        (".hs", Haskell),
+        (".lhs", Haskell),
+        (".html", HTML),
+        (".htm", HTML),
+        (".ini", Ini),
+        (".java", Java),
+        (".js", JavaScript),
+        (".cjs", JavaScript),
+        (".mjs", JavaScript),
+        (".jsx", JavaScriptReact),
+        (".json", JSON),
+        (".kt", Kotlin),
+        (".kts", Kotlin),
+        (".ltx", LaTeX),
+        (".less", Less),
+        (".lua", Lua),
+        (".mk", Makefile),
+        (".mak", Makefile),
+        (".md", Markdown),
+        (".markdown", Markdown),
+        (".m", ObjectiveC),
+        (".mm", ObjectiveCpp),
+        (".ml", OCaml),
+        (".mli", OCaml),
+        // `.pl` is shared with Prolog, but `LanguageId` has no Prolog variant so no
+        // correct labelling is lost. `.t`, `.pod` and `.xs` are deliberately left
+        // unmapped: `.t` is widely used outside Perl, `.pod` is documentation rather
+        // than source, and `.xs` bodies are C.
+        (".pl", Perl),
+        (".pm", Perl),
+        (".PL", Perl),
+        (".p6", Perl6),
+        (".raku", Perl6),
+        (".rakumod", Perl6),
+        (".php", PHP),
+        (".ps1", Powershell),
+        (".psm1", Powershell),
+        (".pug", Pug),
+        (".jade", Pug),
+        (".py", Python),
+        (".pyi", Python),
+        (".pyw", Python),
+        (".bzl", Python),
+        (".r", R),
+        (".cshtml", Razor),
+        (".razor", Razor),
+        (".rb", Ruby),
+        (".rs", Rust),
+        (".scss", SCSS),
+        (".sass", SCSS),
+        (".scala", Scala),
+        (".sc", Scala),
+        (".shader", ShaderLab),
+        (".sh", Shell),
+        (".bash", Shell),
+        (".zsh", Shell),
+        (".sql", SQL),
+        (".swift", Swift),
+        (".ts", TypeScript),
+        (".cts", TypeScript),
+        (".mts", TypeScript),
+        (".tsx", TypeScriptReact),
+        (".tex", TeX),
+        (".vb", VisualBasic),
+        (".xml", XML),
+        (".xsl", XSL),
+        (".xslt", XSL),
+        (".yaml", YAML),
+        (".yml", YAML),
+    ]
+};
+
+/// Extensions whose built-in language is a guess worth refining from the
+/// file's contents.
+const AMBIGUOUS_EXTENSIONS: &[&str] = &[".h"];
+
+/// How many lines at either end of a file may hold a modeline.
+const MODELINE_LINES: usize = 5;
+
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct LanguageMapArgs {
+    #[arg(
+        long,
+        value_name = "PATTERN=LANGUAGE",
+        help = "Infer LANGUAGE for files with this extension (`.h=c`) or name (`BUCK=python`), replacing the built-in mapping. Implies --infer-language"
+    )]
+    pub language_map: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Read --language-map overrides from this file, one per line. Implies --infer-language"
+    )]
+    pub language_map_file: Option<PathBuf>,
+}
+
+impl LanguageMapArgs {
+    pub fn is_empty(&self) -> bool {
+        self.language_map.is_empty() && self.language_map_file.is_none()
+    }
+
+    /// The built-in map with the file's overrides, then the flags'.
+    pub fn build(&self) -> Result<LanguageMap> {
+        let mut map = LanguageMap::default();
+        if let Some(path) = &self.language_map_file {
+            map.read_overrides(path)?;
+        }
+        for spec in &self.language_map {
+            map.add_override(spec)?;
+        }
+        Ok(map)
+    }
+}
+
+/// Extension and file name to language mapping.
+#[derive(Clone, Debug)]
+pub struct LanguageMap {
+    filenames: HashMap<String, LanguageId>,
+    extensions: HashMap<String, LanguageId>,
+    /// Extensions whose language is refined by sniffing.
+    ambiguous: HashSet<String>,
+}
+
+impl Default for LanguageMap {
+    /// The built-in mapping.
+    fn default() -> Self {
+        LanguageMap {
+            filenames: FILENAMES
+                .iter()
+                .map(|(name, lang)| (name.to_string(), *lang))
+                .collect(),
+            extensions: EXTENSIONS
+                .iter()
+                .map(|(ext, lang)| (ext.to_string(), *lang))
+                .collect(),
+            ambiguous: AMBIGUOUS_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl LanguageMap {
+    /// Add an override written `.EXT=LANGUAGE` or `NAME=LANGUAGE`.
+    pub fn add_override(&mut self, spec: &str) -> Result<()> {
+        let (pattern, language) = spec
+            .split_once('=')
+            .filter(|(pattern, _)| !pattern.is_empty() && !pattern.contains('/'))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Invalid language mapping `{}`, expected `.EXT=LANGUAGE` or `NAME=LANGUAGE`",
+                    spec
+                )
+            })?;
+        let lang = LanguageId::new(language.trim())
+            .known()
+            .ok_or_else(|| anyhow!("Unknown language `{}` in mapping `{}`", language, spec))?;
+        let pattern = pattern.trim().to_owned();
+        if pattern.starts_with('.') {
+            self.ambiguous.remove(&pattern);
+            self.extensions.insert(pattern, lang);
+        } else {
+            self.filenames.insert(pattern, lang);
+        }
+        Ok(())
+    }
+
+    /// Add the overrides in the file at `path`, one per line.
+    pub fn read_overrides(&mut self, path: &Path) -> Result<()> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Error reading language map {}", path.display()))?;
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.add_override(line)
+                .with_context(|| format!("{}:{}", path.display(), idx + 1))?;
+        }
+        Ok(())
+    }
+
+    /// The language of the file at `filepath`, by its name, else its
+    /// longest known extension.
+    pub fn language_of(&self, filepath: &str) -> Option<LanguageId> {
+        self.lookup(filepath).map(|(lang, _)| lang)
+    }
+
+    /// The language of the file at `filepath` with the contents `source`
+    /// returns, read only if the name is unknown or ambiguous.
+    pub fn infer<'a>(
+        &self,
+        filepath: &str,
+        source: impl FnOnce() -> Option<&'a [u8]>,
+    ) -> Option<LanguageId> {
+        match self.lookup(filepath) {
+            Some((lang, false)) => Some(lang),
+            Some((lang, true)) => Some(
+                source()
+                    .and_then(|text| sniff_language(text).or_else(|| sniff_header(text)))
+                    .unwrap_or(lang),
+            ),
+            None => source().and_then(sniff_language),
+        }
+    }
+
+    /// The language of `filepath` by name, and whether it is ambiguous.
+    fn lookup(&self, filepath: &str) -> Option<(LanguageId, bool)> {
+        let name = filepath.rsplit('/').next().unwrap_or(filepath);
+        if let Some(lang) = self.filenames.get(name) {
+            return Some((*lang, false));
+        }
+        name.char_indices()
+            .filter(|(idx, c)| *c == '.' && *idx > 0)
+            .find_map(|(idx, _)| {
+                let extension = &name[idx..];
+                let lowercase = extension.to_ascii_lowercase();
+                let extension = if self.extensions.contains_key(extension) {
+                    extension
+                } else {
+                    &lowercase
+                };
+                let lang = self.extensions.get(extension)?;
+                Some((*lang, self.ambiguous.contains(extension)))
+            })
+    }
+}
+
+/// The language a shebang line or modeline in `text` names.
+pub fn sniff_language(text: &[u8]) -> Option<LanguageId> {
+    let text = String::from_utf8_lossy(&text[..text.len().min(64 * 1024)]);
+    let lines: Vec<&str> = text.lines().collect();
+    if let Some(lang) = lines
+        .first()
+        .and_then(|line| line.strip_prefix("#!"))
+        .and_then(shebang_language)
+    {
+        return Some(lang);
+    }
+    let head = lines.iter().take(MODELINE_LINES);
+    let tail = lines.iter().skip(MODELINE_LINES).rev().take(MODELINE_LINES);
+    // Emacs only reads the first line, or the second after a shebang.
+    lines
+        .iter()
+        .take(2)
+        .find_map(|line| emacs_mode(line))
+        .or_else(|| head.chain(tail).find_map(|line| vim_filetype(line)))
+        .and_then(mode_language)
+}
+
+/// C++ or Objective-C if `text` uses their syntax, else C.
+pub fn sniff_header(text: &[u8]) -> Option<LanguageId> {
+    let text = String::from_utf8_lossy(text);
+    const OBJECTIVE_C: &[&str] = &["@interface", "@protocol", "@end", "#import"];
+    const CPP: &[&str] = &[
+        "namespace ",
+        "class ",
+        "template <",
+        "template<",
+        "std::",
+        "public:",
+        "private:",
+        "protected:",
+        "constexpr ",
+        "nullptr",
+    ];
+    let has = |markers: &[&str]| {
+        text.lines().any(|line| {
+            let line = line.trim_start();
+            !line.starts_with("//") && markers.iter().any(|marker| line.contains(marker))
+        })
+    };
+    Some(if has(OBJECTIVE_C) {
+        LanguageId::ObjectiveC
+    } else if has(CPP) {
+        LanguageId::Cpp
+    } else {
+        LanguageId::C
+    })
+}
+
+/// The language of the interpreter in a shebang line, after the `#!`.
+fn shebang_language(line: &str) -> Option<LanguageId> {
+    let mut words = line.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        // Skip `env` options like `-S` and variable assignments.
+        interpreter = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+    }
+    use LanguageId::*;
+    Some(
+        match interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+            "python" | "pypy" => Python,
+            "sh" | "bash" | "zsh" | "dash" | "ksh" | "ash" | "mksh" => Shell,
+            "perl" => Perl,
+            "raku" => Perl6,
+            "ruby" | "jruby" => Ruby,
+            "node" | "nodejs" | "bun" => JavaScript,
+            "deno" | "ts-node" | "tsx" => TypeScript,
+            "php" => PHP,
+            "lua" | "luajit" => Lua,
+            "Rscript" => R,
+            "pwsh" | "powershell" => Powershell,
+            "elixir" => Elixir,
+            "escript" => Erlang,
+            "runghc" | "runhaskell" => Haskell,
+            "swift" => Swift,
+            "scala" => Scala,
+            "kotlin" => Kotlin,
+            "groovy" => Groovy,
+            "make" => Makefile,
+            "ocaml" => OCaml,
+            "dart" => Dart,
+            _ => return None,
+        },
+    )
+}
+
+/// The mode in an Emacs `-*- mode: NAME; ... -*-` or `-*- NAME -*-` line.
+fn emacs_mode(line: &str) -> Option<&str> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (variables, _) = rest.split_once("-*-")?;
+    if !variables.contains(':') {
+        return Some(variables.trim());
+    }
+    variables.split(';').find_map(|variable| {
+        let (name, value) = variable.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("mode")
+            .then(|| value.trim())
+    })
+}
+
+/// The file type in a Vim `vim: set ft=NAME:` or `vim: ft=NAME` line.
+fn vim_filetype(line: &str) -> Option<&str> {
+    let start = ["vim:", "vi:"].iter().find_map(|marker| {
+        line.match_indices(marker)
+            .find(|(idx, _)| *idx == 0 || line[..*idx].ends_with(char::is_whitespace))
+            .map(|(idx, marker)| idx + marker.len())
+    })?;
+    line[start..]
+        .split(|c: char| c.is_whitespace() || c == ':')
+        .find_map(|option| {
+            let (name, value) = option.split_once('=')?;
+            matches!(name, "ft" | "filetype" | "syntax" | "syn").then_some(value)
+        })
+}
+
+/// The language an Emacs mode or Vim file type names.
+fn mode_language(mode: &str) -> Option<LanguageId> {
+    let mode = mode.to_ascii_lowercase();
+    let mode = mode.strip_suffix("-mode").unwrap_or(&mode);
+    use LanguageId::*;
+    match mode {
+        "c++" => Some(Cpp),
+        "objc" => Some(ObjectiveC),
+        "objcpp" => Some(ObjectiveCpp),
+        "sh" | "bash" | "zsh" | "shell-script" => Some(Shell),
+        "js" | "js2" => Some(JavaScript),
+        "ts" => Some(TypeScript),
+        "py" => Some(Python),
+        "cperl" => Some(Perl),
+        "rb" => Some(Ruby),
+        "rs" => Some(Rust),
+        "hs" => Some(Haskell),
+        "make" => Some(Makefile),
+        "yml" => Some(YAML),
+        "md" => Some(Markdown),
+        "cs" => Some(CSharp),
+        "kt" => Some(Kotlin),
+        "plaintex" => Some(TeX),
+        "tuareg" => Some(OCaml),
+        mode => LanguageId::new(mode).known(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_table() {
+        let map = LanguageMap::default();
+        for (path, lang) in [
+            ("src/a.hpp", LanguageId::Cpp),
+            ("src/a.cc", LanguageId::Cpp),
+            ("build.gradle.kts", LanguageId::Kotlin),
+            ("App.swift", LanguageId::Swift),
+            ("Main.scala", LanguageId::Scala),
+            ("Lib.hs", LanguageId::Haskell),
+            ("lib.ml", LanguageId::OCaml),
+            ("index.mjs", LanguageId::JavaScript),
+            ("stubs/os.pyi", LanguageId::Python),
+            ("types.d.ts", LanguageId::TypeScript),
+            ("analysis.R", LanguageId::R),
+            ("proj/BUCK", LanguageId::Python),
+            ("Dockerfile", LanguageId::Dockerfile),
+            ("sub/Makefile", LanguageId::Makefile),
+        ] {
+            assert_eq!(map.language_of(path), Some(lang), "{}", path);
+        }
+        assert_eq!(map.language_of("README"), None);
+        assert_eq!(map.language_of(".bashrc"), None);
+        assert_eq!(map.language_of("dir.go/BUCKET"), None);
+    }
+
+    #[test]
+    fn test_builtin_table_covers_languages() {
+        let mapped: std::collections::HashSet<LanguageId> = EXTENSIONS
+            .iter()
+            .chain(FILENAMES)
+            .map(|(_, lang)| *lang)
+            .collect();
+        // Every variant but `UnknownLanguage`.
+        assert_eq!(mapped.len(), 58);
+        assert!(!mapped.contains(&LanguageId::UnknownLanguage));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut map = LanguageMap::default();
+        map.add_override(".h=c").unwrap();
+        map.add_override("TARGETS=python").unwrap();
+        map.add_override("BUILD.v2=python").unwrap();
+        assert_eq!(map.language_of("a.h"), Some(LanguageId::C));
+        assert_eq!(map.language_of("x/BUILD.v2"), Some(LanguageId::Python));
+        assert!(map.add_override("h").is_err());
+        assert!(map.add_override("=c").is_err());
+        assert!(map.add_override("a/b=c").is_err());
+        assert!(map.add_override(".h=klingon").is_err());
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            "# Overrides\n\n.inc = php\nJustfile=makefile\n",
+        )
+        .unwrap();
+        let args = LanguageMapArgs {
+            language_map: vec![".inc=perl".to_string()],
+            language_map_file: Some(file.path().to_path_buf()),
+        };
+        let map = args.build().unwrap();
+        assert_eq!(map.language_of("a.inc"), Some(LanguageId::Perl));
+        assert_eq!(map.language_of("Justfile"), Some(LanguageId::Makefile));
+
+        std::fs::write(file.path(), "ok.go=go\nbad\n").unwrap();
+        let err = LanguageMap::default()
+            .read_overrides(file.path())
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains(":2"));
+    }
+
+    #[test]
+    fn test_sniff_shebangs_and_modelines() {
+        for (text, lang) in [
+            ("#!/usr/bin/env python3\nprint(1)\n", LanguageId::Python),
+            ("#!/bin/bash\nset -e\n", LanguageId::Shell),
+            ("#!/usr/bin/env -S node --harmony\n", LanguageId::JavaScript),
+            ("#!/usr/bin/perl -w\n", LanguageId::Perl),
+            ("#!/bin/sh\n# -*- mode: ruby -*-\n", LanguageId::Shell),
+            (
+                "# -*- mode: python; coding: utf-8 -*-\n",
+                LanguageId::Python,
+            ),
+            ("/* -*- C++ -*- */\n", LanguageId::Cpp),
+            (
+                "x = 1\n\n\n\n\n\n\n# vim: set ts=4 ft=python :\n",
+                LanguageId::Python,
+            ),
+            ("-- vim: filetype=lua\n", LanguageId::Lua),
+        ] {
+            assert_eq!(sniff_language(text.as_bytes()), Some(lang), "{:?}", text);
+        }
+        assert_eq!(sniff_language(b"#!/usr/bin/unknown\n"), None);
+        assert_eq!(sniff_language(b"envi: ft=python\n"), None);
+        assert_eq!(sniff_language(b"plain text\n"), None);
+    }
+
+    #[test]
+    fn test_infer_sniffs_unknown_and_ambiguous_names() {
+        let map = LanguageMap::default();
+        let infer = |path, text: &'static str| map.infer(path, || Some(text.as_bytes()));
+        assert_eq!(
+            infer("bin/deploy", "#!/usr/bin/env bash\n"),
+            Some(LanguageId::Shell)
+        );
+        assert_eq!(infer("bin/tool", "no shebang\n"), None);
+        assert_eq!(infer("a.h", "int f(void);\n"), Some(LanguageId::C));
+        assert_eq!(
+            infer("a.h", "namespace a {\nint f();\n}\n"),
+            Some(LanguageId::Cpp)
+        );
+        assert_eq!(
+            infer("a.h", "@interface A : NSObject\n@end\n"),
+            Some(LanguageId::ObjectiveC)
+        );
+        assert_eq!(
+            infer("a.h", "// -*- C++ -*-\nint f(void);\n"),
+            Some(LanguageId::Cpp)
+        );
+        // Unambiguous names are not sniffed, and no source keeps the guess.
+        assert_eq!(
+            infer("a.go", "#!/usr/bin/env python\n"),
+            Some(LanguageId::Go)
+        );
+        assert_eq!(map.infer("a.h", || None), Some(LanguageId::Cpp));
+
+        let mut map = LanguageMap::default();
+        map.add_override(".h=cpp").unwrap();
+        assert_eq!(
+            map.infer("a.h", || Some(b"int f(void);\n")),
+            Some(LanguageId::Cpp)
+        );
+    }
+}
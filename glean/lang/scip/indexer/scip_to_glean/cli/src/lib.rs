@@ -0,0 +1,4144 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Convert SCIP indexes to Glean facts.
+//!
+//! The `scip-to-glean` binary is a thin wrapper around this library. To
+//! convert in-process, create an [`Env`], feed it SCIP indexes with
+//! [`decode_index`] (or [`decode_scip_reader`] / [`decode_scip_data`] for
+//! serialized indexes), then take the facts with [`Env::output`] and write
+//! them with [`GleanJSONOutput::write_as`]:
+//!
+//! ```
+//! use scip_to_glean::DecodeOptions;
+//! use scip_to_glean::Env;
+//! use scip_to_glean::OutputFormat;
+//! use scip_to_glean::decode_index;
+//! use scip_to_glean::proto::scip::Document;
+//! use scip_to_glean::proto::scip::Index;
+//! use scip_to_glean::proto::scip::Occurrence;
+//!
+//! let mut occ = Occurrence::new();
+//! occ.symbol = "scip-go gomod m v1 `m`/Foo().".to_string();
+//! occ.range = vec![0, 5, 8];
+//! occ.symbol_roles = 1; // Definition
+//! let mut doc = Document::new();
+//! doc.relative_path = "foo.go".to_string();
+//! doc.language = "go".to_string();
+//! doc.occurrences.push(occ);
+//! let mut index = Index::new();
+//! index.documents.push(doc);
+//!
+//! let mut env = Env::new();
+//! decode_index(&mut env, index, &DecodeOptions::default())?;
+//! let facts = env.output();
+//! assert_eq!(facts.source_files(), vec!["foo.go"]);
+//!
+//! let mut json = Vec::new();
+//! facts.write_as(OutputFormat::Json, &mut json)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! The subcommands of the binary are exposed as modules, each with a
+//! `clap::Args` struct and an entry point taking it.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use protobuf::Message;
+use rayon::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::debug;
+use tracing::info;
+use tracing::warn;
+
+/// The SCIP protobuf types, generated from `proto/scip.proto`.
+#[cfg(not(feature = "facebook"))]
+pub mod proto {
+    include!(concat!(env!("OUT_DIR"), "/proto_gen/mod.rs"));
+}
+#[cfg(not(feature = "facebook"))]
+use proto::scip::Index;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Index;
+
+pub use crate::angle::Env;
+pub use crate::angle::ScipId;
+use crate::config::ConversionConfig;
+use crate::config::resolve_inputs;
+use crate::digests::ExpectedDigests;
+pub use crate::filter::DocumentFilter;
+pub use crate::filter::DocumentFilterArgs;
+pub use crate::languages::LanguageMap;
+use crate::languages::LanguageMapArgs;
+pub use crate::lsif::LanguageId;
+pub use crate::lsif::SymbolKind;
+use crate::manifest::ShardManifest;
+use crate::manifest::write_output_file;
+pub use crate::output::GleanJSONOutput;
+pub use crate::output::OutputFormat;
+pub use crate::paths::EscapingPaths;
+use crate::paths::PathMapping;
+pub use crate::paths::PathNormalization;
+pub use crate::paths::PathRewrites;
+use crate::paths::directory_path;
+use crate::paths::find_vcs_root;
+pub use crate::policy::FailurePolicy;
+use crate::policy::write_skipped_items;
+pub use crate::ranges::RangeValidation;
+use crate::sources::MissingSourcesPolicy;
+use crate::sources::open_sources;
+pub use crate::stats::ConversionStats;
+
+mod angle;
+pub mod config;
+pub mod diff;
+pub mod digests;
+pub mod filter;
+pub mod languages;
+pub mod line_directives;
+pub mod lsif;
+pub mod manifest;
+pub mod merge;
+pub mod output;
+pub mod paths;
+pub mod policy;
+pub mod ranges;
+pub mod reverse;
+pub mod slice;
+pub mod snapshot;
+pub mod source_maps;
+pub mod sources;
+pub mod stats;
+pub mod validate;
+
+/// Arguments of a conversion, i.e. the binary without a subcommand.
+#[derive(clap::Args, Debug)]
+pub struct BuildJsonArgs {
+    #[arg(short, long)]
+    pub input: Vec<PathBuf>,
+    #[arg(short, long, required_unless_present = "config")]
+    pub output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Read settings, including per-input overrides, from this TOML file. Flags given on the command line take precedence"
+    )]
+    pub config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Infer the language of documents that do not set one from their file name or extension"
+    )]
+    pub infer_language: bool,
+
+    #[arg(
+        long,
+        help = "The default language to use for files without a recognized extension."
+    )]
+    pub language: Option<String>,
+
+    #[command(flatten)]
+    pub language_map: LanguageMapArgs,
+
+    #[arg(long, help = "Prefix to prepend to filepaths.")]
+    pub root_prefix: Option<String>,
+
+    #[arg(long, help = "Strip this prefix from the filepaths")]
+    pub strip_prefix: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "RULE",
+        help = "Rewrite document paths before --strip-prefix: `prefix:FROM=TO` or `regex:PATTERN=REPLACEMENT`, or without `=...` to drop matching documents. The first matching rule applies"
+    )]
+    pub rewrite: Vec<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = PathNormalization::All,
+        help = "Which documents get `.` and `..` path components resolved and repeated separators collapsed, after --root-prefix"
+    )]
+    pub normalize_paths: PathNormalization,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "What to do with normalized paths whose `..` components climb above the root: skip the document, keep the `..`, or strip them. Defaults to skip for TypeScript and keep for other languages, whose paths were not normalized before"
+    )]
+    pub escaping_paths: Option<EscapingPaths>,
+
+    #[arg(
+        long,
+        help = "Resolve document paths against the index's file:// project root, making absolute paths and paths like `../repo/a.go` relative to it"
+    )]
+    pub resolve_project_root: bool,
+
+    #[arg(
+        long,
+        help = "Make document paths relative to this repository root, after resolving them against the index's file:// project root"
+    )]
+    pub repo_root: Option<PathBuf>,
+
+    #[arg(
+        long,
+        conflicts_with = "repo_root",
+        help = "Like --repo-root, with the Git, Mercurial or Sapling checkout containing the index's project root"
+    )]
+    pub find_repo_root: bool,
+
+    #[arg(
+        long,
+        help = "Root directory for source files. When set, enables src.FileLines emission by reading files from disk at <source-root>/<relative-path>. JavaScript and TypeScript source maps are read from it too. Defaults to the index's project root if it exists on this machine"
+    )]
+    pub source_root: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "source_root",
+        help = "Read source files from this uncompressed tar archive instead of a directory, at <relative-path> in it"
+    )]
+    pub source_archive: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "TREE-ISH",
+        help = "Read source files from this commit or tree of --source-repo, or of the Git repository containing the source root rather than its working tree"
+    )]
+    pub source_tree: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "GIT-DIR",
+        requires = "source_tree",
+        conflicts_with = "source_archive",
+        help = "Read source files from --source-tree of this Git repository, which may be bare. Files are looked up at the index's project root relative to --repo-root, or at the top of the tree without it"
+    )]
+    pub source_repo: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub missing_sources: MissingSourcesPolicy,
+
+    #[arg(
+        long,
+        help = "Shards the JSON graph into subgraphs. Subgraphs will be approximately the size specified. Uses the --output argument as a directory, writes one file per shard, and a manifest.json describing each shard"
+    )]
+    pub shard: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Write a JSON report of conversion statistics (skipped occurrences, clamped ranges, facts per predicate/language/file) to this path"
+    )]
+    pub stats_out: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub failure_policy: FailurePolicy,
+
+    #[arg(
+        long,
+        help = "Write a JSON list of every skipped document or occurrence, with the reason, to this path"
+    )]
+    pub errors_out: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = RangeValidation::Off,
+        help = "Check ranges against file contents when src.FileLines data is available (document text or --source-root)"
+    )]
+    pub validate_ranges: RangeValidation,
+
+    #[arg(
+        long,
+        help = "Attribute occurrences after a line directive (`//line parse.y:10`, `#line 10 \"parse.y\"`) to the file it names, at the directive's line numbers, with a scip.GeneratedRange linking back to the generated file"
+    )]
+    pub attribute_to_directive_files: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Check the contents of source files against the digests expected at the indexed revision, listed in `sha256sum` format, and report the files that differ"
+    )]
+    pub expected_digests: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Emit the contents of source files as src.FileContent facts, when the document text or --source-root supplies them. Binary files are left out"
+    )]
+    pub emit_file_contents: bool,
+
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = 1024 * 1024,
+        help = "Leave out the contents of files larger than this with --emit-file-contents"
+    )]
+    pub max_file_content_bytes: usize,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Json,
+        help = "Output format. `ndjson` writes one fact per line, tagged with its predicate, in dependency order"
+    )]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub filter: DocumentFilterArgs,
+}
+
+/// How documents are interpreted while decoding, shared by every input of a
+/// conversion.
+#[derive(Clone, Debug, Default)]
+pub struct DecodeOptions {
+    /// The language of documents that neither set one nor have a recognized
+    /// extension.
+    pub default_language: Option<LanguageId>,
+    /// Infer the language of documents that do not set one from their path.
+    pub infer_language: bool,
+    /// Prepended to every document path.
+    pub path_prefix: Option<String>,
+    /// Stripped from document paths before `path_prefix` is prepended.
+    pub strip_prefix: Option<String>,
+    /// Applied to document paths before `strip_prefix`.
+    pub path_rewrites: PathRewrites,
+    /// Applied to document paths last, after `path_prefix`.
+    pub normalize_paths: PathNormalization,
+    pub escaping_paths: Option<EscapingPaths>,
+    /// Resolve document paths against `Metadata.project_root` first.
+    pub resolve_project_root: bool,
+    /// Make resolved document paths relative to this directory rather than
+    /// the project root. Implies resolving against the project root.
+    pub repo_root: Option<PathBuf>,
+    /// Use the VCS checkout containing the project root as `repo_root`.
+    pub find_repo_root: bool,
+    /// Where to read the source of documents without text, for
+    /// `src.FileLines` and range validation. Defaults to the index's project
+    /// root when that directory exists.
+    pub source_root: Option<PathBuf>,
+    /// Never default `source_root` to the project root, so that output does
+    /// not depend on what is on this machine.
+    pub ignore_project_root_sources: bool,
+    /// Read sources from this tar archive instead of `source_root`.
+    pub source_archive: Option<PathBuf>,
+    /// Read sources from this Git tree-ish of `source_repo`, or of the
+    /// repository containing `source_root`.
+    pub source_tree: Option<String>,
+    /// A Git repository, possibly bare, to read `source_tree` from. Files
+    /// are found at the project root's path relative to `repo_root`.
+    pub source_repo: Option<PathBuf>,
+}
+
+/// Decode the SCIP index in the file at `path` into `env`.
+pub fn decode_scip_data(env: &mut Env, path: &Path, options: &DecodeOptions) -> Result<()> {
+    info!("Loading documents from {}", path.display());
+    let scip_index = read_scip_file(path)
+        .with_context(|| format!("Error opening input file {}", path.display()))?;
+    env.start_input(path);
+    decode_index(env, scip_index, options)
+}
+
+/// Decode a serialized SCIP index read from `reader` into `env`.
+pub fn decode_scip_reader(
+    env: &mut Env,
+    mut reader: impl Read,
+    options: &DecodeOptions,
+) -> Result<()> {
+    let scip_index =
+        Index::parse_from_reader(&mut reader).context("Failed to deserialize scip file")?;
+    // There is no file to name skipped items after.
+    env.start_input(Path::new("<reader>"));
+    decode_index(env, scip_index, options)
+}
+
+/// Decode `scip_index` into `env`. Several indexes may be decoded into the
+/// same `Env`, their facts are merged.
+pub fn decode_index(env: &mut Env, scip_index: Index, options: &DecodeOptions) -> Result<()> {
+    let default_language = options.default_language;
+    let infer_language = options.infer_language;
+    env.start_index();
+    if let Some(metadata) = scip_index.metadata.into_option() {
+        env.decode_scip_metadata(metadata);
+    }
+    let project_root = env.project_root().map(str::to_owned);
+    let repo_root = match (&options.repo_root, &project_root) {
+        (Some(repo_root), _) => {
+            let repo_root = std::path::absolute(repo_root)?;
+            Some(
+                directory_path(&repo_root.to_string_lossy())
+                    .with_context(|| format!("Invalid repository root {}", repo_root.display()))?,
+            )
+        }
+        (None, Some(project_root)) if options.find_repo_root => {
+            let repo_root = find_vcs_root(Path::new(project_root));
+            if repo_root.is_none() {
+                warn!(
+                    "No VCS checkout contains project root {}, keeping paths relative to it",
+                    project_root
+                );
+            }
+            repo_root.and_then(|dir| directory_path(&dir.to_string_lossy()))
+        }
+        _ => None,
+    };
+    if project_root.is_none() && (repo_root.is_some() || options.resolve_project_root) {
+        warn!("Not resolving paths, the index has no file:// project root");
+    }
+    // Read sources from the project root when it is on this machine.
+    let source_root = options.source_root.clone().or_else(|| {
+        project_root
+            .as_deref()
+            .filter(|_| !options.ignore_project_root_sources)
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_dir())
+    });
+    // Where the project root is in the trees of `--source-repo`.
+    let project_dir_in_repo = match (&project_root, &repo_root) {
+        (Some(project_root), Some(repo_root)) => {
+            match project_root.strip_prefix(repo_root.as_str()) {
+                Some(dir) => dir.to_owned(),
+                None if options.source_repo.is_some() => bail!(
+                    "Project root {} is outside repository root {}, cannot find it in --source-repo",
+                    project_root,
+                    repo_root
+                ),
+                None => String::new(),
+            }
+        }
+        _ => String::new(),
+    };
+    let paths = PathMapping {
+        project_root: project_root.filter(|_| options.resolve_project_root || repo_root.is_some()),
+        repo_root,
+        rewrites: options.path_rewrites.clone(),
+        strip_prefix: options.strip_prefix.clone(),
+        path_prefix: options.path_prefix.clone(),
+        normalization: options.normalize_paths,
+        escaping: options.escaping_paths,
+    };
+    let sources = open_sources(
+        source_root.as_deref(),
+        options.source_archive.as_deref(),
+        options
+            .source_repo
+            .as_deref()
+            .map(|repo| (repo, project_dir_in_repo.as_str())),
+        options.source_tree.as_deref(),
+    )?;
+    let sources = sources.as_deref();
+    let num_docs = scip_index.documents.len();
+    env.stats_mut().inputs += 1;
+    env.stats_mut().documents += num_docs;
+    info!(
+        "Loaded {} {}",
+        num_docs,
+        if num_docs == 1 {
+            "document"
+        } else {
+            "documents"
+        }
+    );
+
+    // Pre-pass: register every document's `SymbolInformation.kind` overrides
+    // before any document is decoded. Without this, an occurrence in document
+    // B referencing a symbol whose kind lives in document A would fall back to
+    // the descriptor-derived kind, which conflicts with the authoritative kind
+    // emitted when document A is processed — yielding two contradictory
+    // `scip.SymbolKind` facts for the same symbol.
+    // Languages are inferred here once, as sniffing them may read sources,
+    // and the sources read are kept for decoding.
+    let documents = scip_index.documents;
+    let languages: Vec<_> = documents
+        .iter()
+        .map(|doc| {
+            let (lang, source_read) =
+                env.infer_lang_reading_source(default_language, infer_language, doc, sources);
+            env.register_kind_overrides_for_doc(lang, &paths, doc);
+            (lang, source_read)
+        })
+        .collect();
+    let policy = env.failure_policy();
+    let unmatched_before = env.stats_mut().paths_unmatched_by_rewrites.count;
+    let mut skipped_count: usize = 0;
+    for (doc, (lang, source_read)) in documents.into_iter().zip(languages) {
+        let doc_path = doc.relative_path.clone();
+        match env.decode_scip_doc(lang, &paths, sources, doc, source_read) {
+            Ok(()) => {}
+            Err(e) => {
+                env.record_skipped_document(&doc_path, format!("{:#}", e));
+                env.stats_mut().documents_skipped += 1;
+                if policy.strict {
+                    return Err(e.context(format!(
+                        "Aborting: failed to decode SCIP document `{}` in strict mode",
+                        doc_path
+                    )));
+                }
+                warn!("Skipping corrupted SCIP document `{}`: {:#}", doc_path, e);
+                skipped_count += 1;
+            }
+        }
+    }
+
+    let unmatched = &env.stats_mut().paths_unmatched_by_rewrites;
+    if unmatched.count > unmatched_before {
+        // The sample may be full with paths of earlier inputs.
+        let example = unmatched
+            .paths
+            .get(unmatched_before)
+            .map(|path| format!(", e.g. `{}`", path))
+            .unwrap_or_default();
+        warn!(
+            "{} document paths matched no rewrite rule and were kept unchanged{}",
+            unmatched.count - unmatched_before,
+            example
+        );
+    }
+
+    if skipped_count > 0 {
+        info!(
+            "Skipped {} of {} documents due to errors",
+            skipped_count, num_docs
+        );
+    }
+
+    if policy.exceeds_threshold(skipped_count, num_docs) {
+        return Err(anyhow!(
+            "Aborting: {} of {} documents failed (>{} %)",
+            skipped_count,
+            num_docs,
+            policy.max_failure_percent
+        ));
+    }
+
+    if !scip_index.external_symbols.is_empty() {
+        info!(
+            "Processing {} external symbols",
+            scip_index.external_symbols.len()
+        );
+        env.stats_mut().external_symbols += scip_index.external_symbols.len();
+        for ext_sym in scip_index.external_symbols {
+            env.decode_external_symbol(ext_sym)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn human_size(bytes: u64) -> String {
+    const KIB: f64 = 1024.0;
+    const MIB: f64 = 1024.0 * KIB;
+    const GIB: f64 = 1024.0 * MIB;
+    let bytes_f = bytes as f64;
+    if bytes_f >= GIB {
+        format!("{:.1} GiB", bytes_f / GIB)
+    } else if bytes_f >= MIB {
+        format!("{:.1} MiB", bytes_f / MIB)
+    } else if bytes_f >= KIB {
+        format!("{:.1} KiB", bytes_f / KIB)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+/// Convert the SCIP indexes in `args.input` and write the facts to
+/// `args.output`.
+pub fn build_json(mut args: BuildJsonArgs) -> Result<()> {
+    let config = args
+        .config
+        .as_deref()
+        .map(ConversionConfig::read)
+        .transpose()?;
+    let inputs = resolve_inputs(&mut args, config)?;
+    let Some(output) = args.output.clone() else {
+        bail!("No output given, pass --output or set `output` in the config file");
+    };
+    debug!("{:?}", args);
+
+    let mut env = Env::new();
+    env.set_failure_policy(args.failure_policy);
+    env.set_range_validation(args.validate_ranges);
+    env.set_directive_file_attribution(args.attribute_to_directive_files);
+    env.set_document_filter(args.filter.build()?);
+    env.set_language_map(args.language_map.build()?);
+    if args.emit_file_contents {
+        env.set_file_contents(args.max_file_content_bytes);
+    }
+    if let Some(expected_digests) = &args.expected_digests {
+        env.set_expected_digests(ExpectedDigests::read(expected_digests)?);
+    }
+    let decoded = inputs
+        .iter()
+        .try_for_each(|(input, options)| decode_scip_data(&mut env, input, options));
+    // Write the errors file even when aborting, it explains why.
+    if let Some(errors_out) = &args.errors_out {
+        write_skipped_items(errors_out, env.skipped_items())?;
+    }
+    decoded?;
+
+    let mut stats = std::mem::take(env.stats_mut());
+    let missing_sources = args.missing_sources.check(&stats);
+    if stats.files_with_unexpected_digests.count > 0 {
+        warn!(
+            "{} source files differ from --expected-digests, their facts may be stale",
+            stats.files_with_unexpected_digests.count
+        );
+    }
+    let output_facts = env.output();
+    let num_facts = output_facts.total_facts_count();
+    if let Some(stats_out) = &args.stats_out {
+        stats.record_output(&output_facts);
+        stats.write(stats_out)?;
+    }
+    missing_sources?;
+    info!(
+        "Found {} {} total",
+        num_facts,
+        if num_facts == 1 { "fact" } else { "facts" }
+    );
+    let shards = if let Some(shard_size) = args.shard {
+        let shards = output_facts.shard(shard_size);
+        // pad the output files for correct numerical sorting
+        let padding = shards.len().to_string().len();
+        shards
+            .into_iter()
+            .enumerate()
+            .map(|(i, shard)| {
+                let output = output.join(format!(
+                    "{:0width$}.{}",
+                    i,
+                    args.format.extension(),
+                    width = padding
+                ));
+                (output, shard)
+            })
+            .collect()
+    } else {
+        vec![(output.clone(), output_facts)]
+    };
+
+    // Shards are independent subgraphs, so they can be serialized in parallel.
+    let entries = shards
+        .into_par_iter()
+        .map(|(file, shard)| write_output_file(&file, shard, args.format))
+        .collect::<Result<Vec<_>>>()?;
+
+    let num_files = entries.len();
+    let total_bytes: u64 = entries.iter().map(|entry| entry.bytes).sum();
+    if args.shard.is_some() {
+        ShardManifest::new(entries).write(&output)?;
+    }
+    info!(
+        "Wrote {} {} ({})",
+        num_files,
+        if num_files == 1 { "file" } else { "files" },
+        human_size(total_bytes)
+    );
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct ToolInfo {
+    pub tool_args: Vec<String>,
+    pub tool_name: String,
+    pub version: String,
+}
+
+/// A `src.Range`: 1-based lines and columns, with an inclusive end column.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GleanRange {
+    pub column_begin: u64,
+    pub column_end: u64,
+    pub line_begin: u64,
+    pub line_end: u64,
+}
+
+/// Translate a SCIP `[line, column, (line,) column]` range into a
+/// `GleanRange`, fixing up negative and inverted positions. An empty range
+/// decodes to `None`.
+pub fn decode_scip_range(range: &[i32]) -> Result<Option<GleanRange>> {
+    // Some scip files have invalid ranges, the best we can do is to fix them up to 0 or 1
+    // If we don't this will cause integer overflow when we convert to glean facts
+    let mut range = match range {
+        [] => None,
+        [line_begin, column_begin, column_end] => Some(GleanRange {
+            line_begin: std::cmp::max(*line_begin + 1, 1) as u64,
+            column_begin: std::cmp::max(*column_begin + 1, 1) as u64,
+            line_end: std::cmp::max(*line_begin + 1, 1) as u64,
+            column_end: std::cmp::max(*column_end, 0) as u64,
+        }),
+        [line_begin, column_begin, line_end, column_end] => Some(GleanRange {
+            line_begin: std::cmp::max(*line_begin + 1, 1) as u64,
+            column_begin: std::cmp::max(*column_begin + 1, 1) as u64,
+            line_end: std::cmp::max(*line_end + 1, 1) as u64,
+            column_end: std::cmp::max(*column_end, 0) as u64,
+        }),
+        _ => {
+            return Err(anyhow!("bad range: {:#?}", range));
+        }
+    };
+    // Columns of multi-line ranges are on different lines and may decrease.
+    if let Some(ref mut r) = range {
+        if r.line_begin == r.line_end {
+            r.column_end = std::cmp::max(r.column_begin, r.column_end);
+        }
+    }
+    Ok(range)
+}
+
+/// Whether `decode_scip_range` had to fix up `range`: it has negative
+/// positions, or it is on one line and its end column is not after its
+/// begin column. Zero-width ranges are valid, and only end on their begin
+/// column in Glean.
+fn scip_range_was_clamped(range: &[i32]) -> bool {
+    let inverted = match *range {
+        [_, column_begin, column_end] => column_end < column_begin,
+        [line_begin, column_begin, line_end, column_end] => {
+            line_begin == line_end && column_end < column_begin
+        }
+        _ => return false,
+    };
+    range.iter().any(|position| *position < 0) || inverted
+}
+
+pub fn read_scip_file(file: &Path) -> Result<Index, Error> {
+    let scip_file = File::open(file)?;
+    let mut reader = std::io::BufReader::new(scip_file);
+    Index::parse_from_reader(&mut reader).context("Failed to deserialize scip file")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::collections::HashSet;
+
+    use proptest::prelude::*;
+    #[cfg(feature = "facebook")]
+    use proto_rust::scip::Document;
+    #[cfg(feature = "facebook")]
+    use proto_rust::scip::Metadata as ScipMetadata;
+    #[cfg(feature = "facebook")]
+    use proto_rust::scip::MultiLineRange;
+    #[cfg(feature = "facebook")]
+    use proto_rust::scip::Occurrence as ScipOccurrence;
+    #[cfg(feature = "facebook")]
+    use proto_rust::scip::Relationship;
+    #[cfg(feature = "facebook")]
+    use proto_rust::scip::SingleLineRange;
+    #[cfg(feature = "facebook")]
+    use proto_rust::scip::SymbolInformation as ScipSymbolInformation;
+    #[cfg(feature = "facebook")]
+    use proto_rust::scip::TextEncoding;
+    #[cfg(feature = "facebook")]
+    use proto_rust::scip::symbol_information;
+    use tempfile::NamedTempFile;
+
+    #[cfg(not(feature = "facebook"))]
+    use super::proto::scip::Document;
+    #[cfg(not(feature = "facebook"))]
+    use super::proto::scip::Metadata as ScipMetadata;
+    #[cfg(not(feature = "facebook"))]
+    use super::proto::scip::MultiLineRange;
+    #[cfg(not(feature = "facebook"))]
+    use super::proto::scip::Occurrence as ScipOccurrence;
+    #[cfg(not(feature = "facebook"))]
+    use super::proto::scip::Relationship;
+    #[cfg(not(feature = "facebook"))]
+    use super::proto::scip::SingleLineRange;
+    #[cfg(not(feature = "facebook"))]
+    use super::proto::scip::SymbolInformation as ScipSymbolInformation;
+    #[cfg(not(feature = "facebook"))]
+    use super::proto::scip::TextEncoding;
+    #[cfg(not(feature = "facebook"))]
+    use super::proto::scip::symbol_information;
+    use super::*;
+    use crate::angle::qualify_scip_symbol;
+    use crate::diff::DiffArgs;
+    use crate::diff::run_diff;
+    use crate::lsif::SymbolKind;
+    use crate::merge::MergeArgs;
+    use crate::merge::merge;
+    use crate::slice::SliceArgs;
+    use crate::slice::slice;
+    use crate::snapshot::SnapshotArgs;
+    use crate::snapshot::snapshot;
+    use crate::validate::ValidateArgs;
+    use crate::validate::run_validate;
+
+    fn build_args(scip_path: PathBuf, output_path: PathBuf) -> BuildJsonArgs {
+        BuildJsonArgs {
+            input: vec![scip_path],
+            output: Some(output_path),
+            config: None,
+            infer_language: false,
+            language: None,
+            root_prefix: None,
+            strip_prefix: None,
+            language_map: LanguageMapArgs::default(),
+            attribute_to_directive_files: false,
+            expected_digests: None,
+            source_archive: None,
+            source_tree: None,
+            source_repo: None,
+            missing_sources: MissingSourcesPolicy::default(),
+            emit_file_contents: false,
+            max_file_content_bytes: 1024 * 1024,
+            rewrite: vec![],
+            normalize_paths: PathNormalization::All,
+            escaping_paths: None,
+            resolve_project_root: false,
+            repo_root: None,
+            find_repo_root: false,
+            source_root: None,
+            shard: None,
+            stats_out: None,
+            failure_policy: FailurePolicy::default(),
+            errors_out: None,
+            validate_ranges: RangeValidation::Off,
+            format: OutputFormat::Json,
+            filter: DocumentFilterArgs::default(),
+        }
+    }
+
+    /// Find the kind value emitted for the symbol fact whose key string
+    /// contains `symbol_substr`.
+    fn find_kind_for_symbol(json: &str, symbol_substr: &str) -> Option<u64> {
+        let symbols = find_predicate_facts(json, "scip.Symbol.1")?;
+        let kinds = find_predicate_facts(json, "scip.SymbolKind.1")?;
+        let symbol_id = symbols.as_array()?.iter().find_map(|f| {
+            let key = f["key"].as_str()?;
+            if key.contains(symbol_substr) {
+                f["id"].as_u64()
+            } else {
+                None
+            }
+        })?;
+        kinds.as_array()?.iter().find_map(|f| {
+            if f["key"]["symbol"].as_u64() == Some(symbol_id) {
+                f["key"]["kind"].as_u64()
+            } else {
+                None
+            }
+        })
+    }
+
+    #[test]
+    fn test_write_blank_scip() {
+        let scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.infer_language = true;
+
+        build_json(args).expect("failure building JSON");
+
+        let output_json =
+            std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        assert_eq!(output_json, "[]\n");
+    }
+
+    #[test]
+    fn test_write_sharded_scip() {
+        let scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json_dir = tempfile::TempDir::new().expect("Unable to create temp dir");
+
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json_dir.path().to_path_buf(),
+        );
+        args.infer_language = true;
+        args.shard = Some(100);
+
+        build_json(args).expect("failure building JSON");
+
+        let files_in_dir: Vec<_> = std::fs::read_dir(output_json_dir.path())
+            .expect("unable to read output directory")
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.file_name().unwrap() != manifest::MANIFEST_FILE_NAME)
+            .collect();
+        let file_count = files_in_dir.len();
+        assert_eq!(
+            file_count, 1,
+            "Expected exactly one shard file in the output directory"
+        );
+
+        let output_json = std::fs::read_to_string(&files_in_dir[0]).expect("unable to read output");
+
+        assert_eq!(output_json, "[]\n");
+    }
+
+    #[test]
+    fn test_sharded_output_writes_manifest() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json_dir = tempfile::TempDir::new().expect("Unable to create temp dir");
+
+        let mut index = Index::new();
+        index
+            .documents
+            .push(make_valid_doc("a.go", "scip-go gomod pkg 1.0 `pkg`/Alpha."));
+        index
+            .documents
+            .push(make_valid_doc("b.go", "scip-go gomod pkg 1.0 `pkg`/Beta."));
+        write_scip_index_full(&mut scip_file, index);
+
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json_dir.path().to_path_buf(),
+        );
+        args.shard = Some(1);
+        build_json(args).expect("failure building JSON");
+
+        let manifest_json =
+            std::fs::read_to_string(output_json_dir.path().join(manifest::MANIFEST_FILE_NAME))
+                .expect("manifest.json should be written next to the shards");
+        let manifest: manifest::ShardManifest =
+            serde_json::from_str(&manifest_json).expect("manifest is not valid JSON");
+        assert!(manifest.shards.len() > 1, "expected several shards");
+
+        let mut covered_files = Vec::new();
+        let mut total_facts = 0;
+        for entry in &manifest.shards {
+            let contents = std::fs::read(output_json_dir.path().join(&entry.file))
+                .expect("manifest lists a shard that does not exist");
+            assert_eq!(entry.bytes, contents.len() as u64);
+            assert_eq!(entry.sha256.len(), 64);
+
+            // Per-predicate counts must match what the shard actually holds.
+            let parsed: serde_json::Value =
+                serde_json::from_slice(&contents).expect("shard is not valid JSON");
+            for batch in parsed.as_array().unwrap() {
+                let predicate = batch["predicate"].as_str().unwrap();
+                let count = batch["facts"].as_array().unwrap().len();
+                assert_eq!(entry.facts.get(predicate), Some(&count));
+            }
+            total_facts += entry.facts.values().sum::<usize>();
+            covered_files.extend(entry.source_files.iter().cloned());
+        }
+        assert_eq!(manifest.total_facts, total_facts);
+
+        covered_files.sort();
+        covered_files.dedup();
+        assert_eq!(covered_files, vec!["a.go", "b.go"]);
+    }
+
+    #[test]
+    fn test_ndjson_output_one_fact_per_line() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let json_output = NamedTempFile::new().expect("unable to create temp file");
+        let ndjson_output = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        index
+            .documents
+            .push(make_valid_doc("a.go", "scip-go gomod pkg 1.0 `pkg`/Alpha."));
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            json_output.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            ndjson_output.path().to_path_buf(),
+        );
+        args.format = OutputFormat::Ndjson;
+        build_json(args).expect("failure building NDJSON");
+
+        let ndjson = std::fs::read_to_string(ndjson_output.path()).expect("unable to read output");
+        let lines: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("each line should be a JSON fact"))
+            .collect();
+        let predicates: Vec<&str> = lines
+            .iter()
+            .map(|fact| fact["predicate"].as_str().expect("fact must be tagged"))
+            .collect();
+
+        // Facts are in dependency order: no fact precedes the facts it refers to.
+        let first = |name: &str| predicates.iter().position(|p| *p == name).unwrap();
+        assert!(first("src.File.1") < first("scip.FileRange.1"));
+        assert!(first("scip.FileRange.1") < first("scip.Definition.1"));
+        assert!(first("scip.Symbol.1") < first("scip.Definition.1"));
+
+        // Grouping the lines by predicate reproduces the JSON batch output.
+        let json = std::fs::read_to_string(json_output.path()).expect("unable to read output");
+        let batches: serde_json::Value = serde_json::from_str(&json).unwrap();
+        for batch in batches.as_array().unwrap() {
+            let predicate = batch["predicate"].as_str().unwrap();
+            let regrouped: Vec<serde_json::Value> = lines
+                .iter()
+                .filter(|fact| fact["predicate"] == predicate)
+                .map(|fact| {
+                    let mut fact = fact.clone();
+                    fact.as_object_mut().unwrap().remove("predicate");
+                    fact
+                })
+                .collect();
+            assert_eq!(
+                &serde_json::Value::Array(regrouped),
+                &batch["facts"],
+                "NDJSON facts for {predicate} should match the JSON batch"
+            );
+        }
+    }
+
+    #[test]
+    fn test_scip_range_was_clamped() {
+        assert!(!scip_range_was_clamped(&[1, 2, 5]));
+        assert!(!scip_range_was_clamped(&[1, 2, 2]));
+        assert!(!scip_range_was_clamped(&[1, 2, 1, 2]));
+        assert!(!scip_range_was_clamped(&[1, 2, 3, 5]));
+        assert!(scip_range_was_clamped(&[1, -1, 4]));
+        assert!(scip_range_was_clamped(&[-1, 0, 4]));
+        assert!(scip_range_was_clamped(&[1, 5, 2]));
+        assert!(!scip_range_was_clamped(&[1, 10, 3, 2]));
+        assert!(!scip_range_was_clamped(&[1, 2, 3, 2]));
+        assert!(scip_range_was_clamped(&[1, 5, 1, 2]));
+    }
+
+    #[test]
+    fn test_stats_out_reports_skips_and_fact_counts() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+        let stats_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+
+        let mut doc = make_valid_doc("a.go", "scip-go gomod pkg 1.0 `pkg`/Alpha.");
+        // scip-go style empty occurrence.
+        doc.occurrences.push(ScipOccurrence::new());
+        // Occurrence without any range.
+        let mut no_range = ScipOccurrence::new();
+        no_range.symbol = "scip-go gomod pkg 1.0 `pkg`/NoRange.".to_string();
+        doc.occurrences.push(no_range);
+        // Negative column, clamped to the first column.
+        let mut negative = ScipOccurrence::new();
+        negative.symbol = "scip-go gomod pkg 1.0 `pkg`/Negative.".to_string();
+        negative.range = vec![1, -1, 4];
+        doc.occurrences.push(negative);
+        // Zero-width ranges are valid, not clamped.
+        let mut zero_width = ScipOccurrence::new();
+        zero_width.symbol = "scip-go gomod pkg 1.0 `pkg`/ZeroWidth.".to_string();
+        zero_width.range = vec![2, 3, 3];
+        doc.occurrences.push(zero_width);
+        index.documents.push(doc);
+
+        // A TypeScript path escaping the root cannot be normalized.
+        let mut escaping = Document::new();
+        escaping.relative_path = "../../escape.ts".to_string();
+        escaping.language = "typescript".to_string();
+        index.documents.push(escaping);
+
+        write_scip_index_full(&mut scip_file, index);
+
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.stats_out = Some(stats_json.path().to_path_buf());
+        build_json(args).expect("failure building JSON");
+
+        let stats: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(stats_json.path()).expect("unable to read stats"),
+        )
+        .expect("stats is not valid JSON");
+        assert_eq!(stats["documents"], 2);
+        assert_eq!(stats["occurrences"], 5);
+        assert_eq!(stats["occurrencesSkippedEmptySymbol"], 1);
+        assert_eq!(stats["occurrencesSkippedMissingRange"], 1);
+        assert_eq!(stats["rangesClamped"], 1);
+        assert_eq!(stats["pathsFailedNormalization"], 1);
+
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+        let file_ranges = find_predicate_facts(&output, "scip.FileRange.1")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .len();
+        assert_eq!(stats["factsByPredicate"]["scip.FileRange.1"], file_ranges);
+        assert_eq!(stats["factsByLanguage"]["Go"], stats["factsByFile"]["a.go"]);
+        // The index has no metadata, so every fact is attributed to a.go.
+        assert_eq!(stats["factsByFile"]["a.go"], stats["totalFacts"]);
+    }
+
+    #[test]
+    fn test_validate_reports_each_check() {
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        let report_json = NamedTempFile::new().expect("Failed to create temp file");
+
+        let occ = |symbol: &str, range: Vec<i32>, roles: i32| {
+            let mut occ = ScipOccurrence::new();
+            occ.symbol = symbol.to_string();
+            occ.range = range;
+            occ.symbol_roles = roles;
+            occ
+        };
+        let mut doc = Document::new();
+        doc.relative_path = "a.go".to_string();
+        doc.occurrences
+            .push(occ("scip-go gomod m v1 `m`/Foo#", vec![0, 0, 10, 1], 1));
+        doc.occurrences
+            .push(occ("scip-go gomod m v1 `m`/Foo#", vec![5, 0, 3], 1));
+        doc.occurrences.push(occ("not a symbol", vec![1, 0, 3], 0));
+        doc.occurrences
+            .push(occ("scip-go gomod m v1 `m`/Bar#", vec![], 0));
+        doc.occurrences
+            .push(occ("scip-go gomod m v1 `m`/Bar#", vec![1, 2], 0));
+        let mut info = ScipSymbolInformation::new();
+        info.symbol = "scip-go gomod m v1 `m`/Unused#".to_string();
+        let mut rel = Relationship::new();
+        rel.symbol = "scip-go gomod m v1 `m`/Missing#".to_string();
+        info.relationships.push(rel);
+        doc.symbols.push(info);
+
+        let mut escaping = Document::new();
+        escaping.relative_path = "../escape.go".to_string();
+
+        let mut index = Index::new();
+        index.documents.push(doc.clone());
+        index.documents.push(escaping);
+        let mut split = Document::new();
+        split.relative_path = "a.go".to_string();
+        index.documents.push(split);
+        write_scip_index_full(&mut scip_file, index);
+
+        let args = ValidateArgs {
+            input: vec![scip_file.path().to_path_buf()],
+            format: validate::ReportFormat::Json,
+            output: Some(report_json.path().to_path_buf()),
+            deny_warnings: false,
+        };
+        let err = run_validate(&args).expect_err("errors should fail validation");
+        assert!(err.to_string().contains("4 errors, 4 warnings"), "{}", err);
+
+        let report: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(report_json.path()).expect("unable to read report"),
+        )
+        .expect("report is not valid JSON");
+        assert_eq!(report["documents"], 3);
+        assert_eq!(report["occurrences"], 5);
+        for check in [
+            "malformed-symbol",
+            "missing-range",
+            "invalid-range",
+            "unnormalizable-path",
+            "overlapping-definitions",
+            "unreferenced-symbol-information",
+            "unknown-relationship-target",
+            "duplicate-document",
+        ] {
+            assert_eq!(report["counts"][check], 1, "{}", check);
+        }
+        let issues = report["issues"].as_array().unwrap();
+        let overlap = issues
+            .iter()
+            .find(|issue| issue["check"] == "overlapping-definitions")
+            .unwrap();
+        assert_eq!(overlap["severity"], "warning");
+        assert_eq!(overlap["document"], "a.go");
+        assert_eq!(overlap["symbol"], "scip-go gomod m v1 `m`/Foo#");
+    }
+
+    #[test]
+    fn test_validate_clean_index_passes() {
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        let report_json = NamedTempFile::new().expect("Failed to create temp file");
+
+        let mut doc = make_valid_doc("a.go", "scip-go gomod m v1 `m`/Foo#");
+        let mut info = ScipSymbolInformation::new();
+        info.symbol = "scip-go gomod m v1 `m`/Foo#".to_string();
+        doc.symbols.push(info);
+        write_scip_index(&mut scip_file, doc);
+
+        let mut args = ValidateArgs {
+            input: vec![scip_file.path().to_path_buf()],
+            format: validate::ReportFormat::Text,
+            output: Some(report_json.path().to_path_buf()),
+            deny_warnings: true,
+        };
+        run_validate(&args).expect("clean index should pass");
+        let report = std::fs::read_to_string(report_json.path()).expect("unable to read report");
+        assert!(report.contains("errors: 0, warnings: 0"), "{}", report);
+
+        // A document that is declared twice is only a warning...
+        let mut index = Index::new();
+        index
+            .documents
+            .push(make_valid_doc("a.go", "scip-go gomod m v1 `m`/Foo#"));
+        index
+            .documents
+            .push(make_valid_doc("a.go", "scip-go gomod m v1 `m`/Bar#"));
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+        args.input = vec![scip_file.path().to_path_buf()];
+        // ...which fails only under --deny-warnings.
+        run_validate(&args).expect_err("warnings should fail with --deny-warnings");
+        args.deny_warnings = false;
+        run_validate(&args).expect("warnings alone should pass");
+    }
+
+    #[test]
+    fn test_diff_reports_documents_locations_and_symbol_changes() {
+        let mut old_scip = NamedTempFile::new().expect("Failed to create temp file");
+        let mut new_scip = NamedTempFile::new().expect("Failed to create temp file");
+        let report_json = NamedTempFile::new().expect("Failed to create temp file");
+
+        let foo = "scip-go gomod m v1 `m`/Foo.";
+        let occ = |symbol: &str, line: i32, roles: i32| {
+            let mut occ = ScipOccurrence::new();
+            occ.symbol = symbol.to_string();
+            occ.range = vec![line, 0, 3];
+            occ.symbol_roles = roles;
+            occ
+        };
+
+        let mut old_index = Index::new();
+        let mut a = Document::new();
+        a.relative_path = "a.go".to_string();
+        a.language = "go".to_string();
+        a.occurrences.push(occ(foo, 0, 1));
+        a.occurrences.push(occ(foo, 5, 0));
+        old_index.documents.push(a.clone());
+        old_index
+            .documents
+            .push(make_valid_doc("b.go", "scip-go gomod m v1 `m`/B."));
+        write_scip_index_full(&mut old_scip, old_index);
+
+        // The reference moves, Foo becomes a `const` with documentation, b.go
+        // is replaced by c.go.
+        let mut new_index = Index::new();
+        a.occurrences[1] = occ(foo, 7, 0);
+        let mut info = ScipSymbolInformation::new();
+        info.symbol = foo.to_string();
+        info.kind = symbol_information::Kind::Constant.into();
+        info.documentation.push("Foo docs".to_string());
+        a.symbols.push(info);
+        new_index.documents.push(a);
+        new_index
+            .documents
+            .push(make_valid_doc("c.go", "scip-go gomod m v1 `m`/C."));
+        write_scip_index_full(&mut new_scip, new_index);
+
+        let args = DiffArgs {
+            old: old_scip.path().to_path_buf(),
+            new: new_scip.path().to_path_buf(),
+            infer_language: false,
+            language: None,
+            format: validate::ReportFormat::Json,
+            output: Some(report_json.path().to_path_buf()),
+        };
+        run_diff(&args).expect("diff failed");
+
+        let report: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(report_json.path()).expect("unable to read report"),
+        )
+        .expect("report is not valid JSON");
+        let summary = &report["summary"];
+        assert_eq!(summary["documentsAdded"], 1);
+        assert_eq!(summary["documentsRemoved"], 1);
+        assert_eq!(summary["documentsChanged"], 1);
+        // c.go's definition, and b.go's.
+        assert_eq!(summary["definitionsGained"], 1);
+        assert_eq!(summary["definitionsLost"], 1);
+        assert_eq!(summary["referencesGained"], 1);
+        assert_eq!(summary["referencesLost"], 1);
+        assert_eq!(summary["symbolsAdded"], 1);
+        assert_eq!(summary["symbolsRemoved"], 1);
+        assert_eq!(summary["symbolsChanged"], 1);
+        assert_eq!(report["documentsAdded"], serde_json::json!(["c.go"]));
+        assert_eq!(report["documentsRemoved"], serde_json::json!(["b.go"]));
+
+        let a_diff = report["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|file| file["path"] == "a.go")
+            .unwrap();
+        assert_eq!(a_diff["referencesGained"][0]["range"]["lineBegin"], 8);
+        assert_eq!(a_diff["referencesLost"][0]["range"]["lineBegin"], 6);
+        assert_eq!(a_diff["definitionsGained"], serde_json::json!([]));
+
+        // The kind change comes from `Env`'s SymbolInformation.kind override.
+        let change = &report["symbolsChanged"][0];
+        assert_eq!(change["symbol"], foo);
+        assert_eq!(change["kinds"]["old"], serde_json::json!(["SkVariable"]));
+        assert_eq!(change["kinds"]["new"], serde_json::json!(["SkConstant"]));
+        assert_eq!(
+            change["documentation"]["new"],
+            serde_json::json!(["Foo docs"])
+        );
+        assert!(change.get("displayNames").is_none());
+    }
+
+    #[test]
+    fn test_merge_concatenates_documents_and_dedupes_external_symbols() {
+        let mut first_scip = NamedTempFile::new().expect("Failed to create temp file");
+        let mut second_scip = NamedTempFile::new().expect("Failed to create temp file");
+        let merged_scip = NamedTempFile::new().expect("Failed to create temp file");
+
+        let external = |symbol: &str, doc: &str| {
+            let mut info = ScipSymbolInformation::new();
+            info.symbol = symbol.to_string();
+            info.documentation.push(doc.to_string());
+            info
+        };
+        let x = "scip-go gomod dep v1 `dep`/X.";
+
+        let mut first = Index::new();
+        first
+            .documents
+            .push(make_valid_doc("a.go", "scip-go gomod m v1 `m`/Foo."));
+        first.external_symbols.push(external(x, "x docs"));
+        write_scip_index_full(&mut first_scip, first);
+
+        let mut second = Index::new();
+        let mut a = make_valid_doc("a.go", "scip-go gomod m v1 `m`/Bar.");
+        a.text = "package a\n".to_string();
+        second.documents.push(a);
+        second
+            .documents
+            .push(make_valid_doc("b.go", "scip-go gomod m v1 `m`/B."));
+        second.external_symbols.push(external(x, "more x docs"));
+        second
+            .external_symbols
+            .push(external("scip-go gomod dep v1 `dep`/Y.", "y docs"));
+        write_scip_index_full(&mut second_scip, second);
+
+        merge(&MergeArgs {
+            input: vec![
+                first_scip.path().to_path_buf(),
+                second_scip.path().to_path_buf(),
+            ],
+            output: merged_scip.path().to_path_buf(),
+        })
+        .expect("merge failed");
+
+        let merged = read_scip_file(merged_scip.path()).expect("unable to read merged index");
+        let paths: Vec<&str> = merged
+            .documents
+            .iter()
+            .map(|doc| doc.relative_path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["a.go", "b.go"]);
+        assert_eq!(merged.documents[0].occurrences.len(), 2);
+        assert_eq!(merged.documents[0].text, "package a\n");
+        assert_eq!(merged.external_symbols.len(), 2);
+        assert_eq!(
+            merged.external_symbols[0].documentation,
+            vec!["x docs", "more x docs"]
+        );
+
+        // Converting the merged index yields the same definitions and
+        // documentation as converting the inputs together.
+        let output_json = NamedTempFile::new().expect("Failed to create temp file");
+        build_json(build_args(
+            merged_scip.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let merged_output = std::fs::read_to_string(output_json.path()).unwrap();
+        let mut args = build_args(
+            first_scip.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.input.push(second_scip.path().to_path_buf());
+        build_json(args).expect("failure building JSON");
+        let inputs_output = std::fs::read_to_string(output_json.path()).unwrap();
+        for predicate in ["scip.Definition.1", "scip.Documentation.1"] {
+            assert_eq!(
+                find_predicate_facts(&merged_output, predicate)
+                    .unwrap()
+                    .as_array()
+                    .unwrap()
+                    .len(),
+                find_predicate_facts(&inputs_output, predicate)
+                    .unwrap()
+                    .as_array()
+                    .unwrap()
+                    .len(),
+                "{}",
+                predicate
+            );
+        }
+        // Unlike the separate conversion, which only looks at the first
+        // document for a path, the merged document carries the text.
+        assert!(find_predicate_facts(&merged_output, "src.FileLines.1").is_some());
+        assert!(find_predicate_facts(&inputs_output, "src.FileLines.1").is_none());
+    }
+
+    #[test]
+    fn test_merge_reconciles_metadata() {
+        let with_metadata = |root: &str, encoding: TextEncoding, path: &str| {
+            let mut index = Index::new();
+            let mut metadata = ScipMetadata::new();
+            metadata.project_root = root.to_string();
+            metadata.text_document_encoding = encoding.into();
+            index.metadata = protobuf::MessageField::some(metadata);
+            index
+                .documents
+                .push(make_valid_doc(path, "scip-go gomod m v1 `m`/Foo."));
+            index
+        };
+
+        let merged = merge::merge_indexes(vec![
+            with_metadata("file:///repo/a/", TextEncoding::UTF8, "x.go"),
+            with_metadata(
+                "file:///repo/b",
+                TextEncoding::UnspecifiedTextEncoding,
+                "x.go",
+            ),
+        ])
+        .expect("merge failed");
+        assert_eq!(merged.metadata.project_root, "file:///repo/");
+        assert_eq!(
+            merged.metadata.text_document_encoding.enum_value(),
+            Ok(TextEncoding::UTF8)
+        );
+        let paths: Vec<&str> = merged
+            .documents
+            .iter()
+            .map(|doc| doc.relative_path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["a/x.go", "b/x.go"]);
+
+        let err = merge::merge_indexes(vec![
+            with_metadata("file:///repo/", TextEncoding::UTF8, "x.go"),
+            with_metadata("file:///repo/", TextEncoding::UTF16, "y.go"),
+        ])
+        .expect_err("conflicting encodings should not merge");
+        assert!(
+            err.to_string().contains("different text_document_encoding"),
+            "{}",
+            err
+        );
+    }
+
+    /// An index where `src/a.go` refers to `B` (whose kind lives in
+    /// `src/gen/b.go`) and to external symbol `X`.
+    fn filter_test_index() -> Index {
+        let b = "scip-go gomod m v1 `m`/B.";
+        let mut index = Index::new();
+
+        let mut a = make_valid_doc("src/a.go", "scip-go gomod m v1 `m`/A.");
+        let mut reference = ScipOccurrence::new();
+        reference.symbol = b.to_string();
+        reference.range = vec![1, 0, 1];
+        a.occurrences.push(reference.clone());
+        reference.symbol = "scip-go gomod dep v1 `dep`/X.".to_string();
+        a.occurrences.push(reference);
+        index.documents.push(a);
+
+        let mut gen_b = make_valid_doc("src/gen/b.go", b);
+        let mut info = ScipSymbolInformation::new();
+        info.symbol = b.to_string();
+        info.kind = symbol_information::Kind::Constant.into();
+        info.enclosing_symbol = "scip-go gomod m v1 `m`/".to_string();
+        gen_b.symbols.push(info);
+        index.documents.push(gen_b);
+        index
+            .documents
+            .push(make_valid_doc("vendor/c.go", "scip-go gomod v v1 `v`/C."));
+
+        for symbol in [
+            "scip-go gomod dep v1 `dep`/X.",
+            "scip-go gomod dep v1 `dep`/Unused.",
+            "scip-go gomod m v1 `m`/",
+        ] {
+            let mut info = ScipSymbolInformation::new();
+            info.symbol = symbol.to_string();
+            index.external_symbols.push(info);
+        }
+        index
+    }
+
+    #[test]
+    fn test_slice_keeps_matching_documents_and_referenced_symbols() {
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        let sliced_scip = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, filter_test_index());
+
+        slice(&SliceArgs {
+            input: scip_file.path().to_path_buf(),
+            output: sliced_scip.path().to_path_buf(),
+            filter: DocumentFilterArgs {
+                include: vec!["src/**".to_string()],
+                exclude: vec!["src/gen/**".to_string()],
+                only_language: vec![],
+            },
+            symbol: vec![],
+            infer_language: false,
+            language: None,
+        })
+        .expect("slice failed");
+
+        let sliced = read_scip_file(sliced_scip.path()).expect("unable to read sliced index");
+        let paths: Vec<&str> = sliced
+            .documents
+            .iter()
+            .map(|doc| doc.relative_path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["src/a.go"]);
+        // B's SymbolInformation moves from the dropped document to the
+        // external symbols, and brings along its enclosing symbol.
+        let externals: Vec<&str> = sliced
+            .external_symbols
+            .iter()
+            .map(|info| info.symbol.as_str())
+            .collect();
+        assert_eq!(
+            externals,
+            vec![
+                "scip-go gomod dep v1 `dep`/X.",
+                "scip-go gomod m v1 `m`/",
+                "scip-go gomod m v1 `m`/B.",
+            ]
+        );
+
+        // Slicing by symbol keeps every document mentioning it.
+        slice(&SliceArgs {
+            input: scip_file.path().to_path_buf(),
+            output: sliced_scip.path().to_path_buf(),
+            filter: DocumentFilterArgs::default(),
+            symbol: vec!["`m`/B.".to_string()],
+            infer_language: false,
+            language: None,
+        })
+        .expect("slice failed");
+        let sliced = read_scip_file(sliced_scip.path()).expect("unable to read sliced index");
+        assert_eq!(sliced.documents.len(), 2);
+    }
+
+    #[test]
+    fn test_converter_document_filter_keeps_kind_overrides() {
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        let output_json = NamedTempFile::new().expect("Failed to create temp file");
+        let stats_json = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, filter_test_index());
+
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.filter.include = vec!["src/*.go".to_string()];
+        args.stats_out = Some(stats_json.path().to_path_buf());
+        build_json(args).expect("failure building JSON");
+
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+        let files = find_predicate_facts(&output, "src.File.1").unwrap();
+        assert_eq!(files.as_array().unwrap().len(), 1);
+        assert_eq!(files[0]["key"], "src/a.go");
+        // The kind of B comes from the filtered-out src/gen/b.go.
+        assert_eq!(
+            find_kind_for_symbol(&output, "`m`/B."),
+            Some(SymbolKind::SkConstant as u64)
+        );
+        let stats: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(stats_json.path()).expect("unable to read stats"),
+        )
+        .unwrap();
+        assert_eq!(stats["documentsFiltered"], 2);
+    }
+
+    #[test]
+    fn test_snapshot_reads_text_and_source_root() {
+        let source_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let output_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        std::fs::create_dir_all(source_dir.path().join("pkg")).unwrap();
+        std::fs::write(source_dir.path().join("pkg/disk.go"), "var diskVal = 1\n").unwrap();
+
+        let mut with_text = make_valid_doc("pkg/inline.go", "scip-go gomod m v1 `m`/Inline.");
+        with_text.occurrences[0].range = vec![0, 4, 10];
+        with_text.text = "func Inline() {}\n".to_string();
+        let mut on_disk = make_valid_doc("pkg/disk.go", "scip-go gomod m v1 `m`/diskVal.");
+        on_disk.occurrences[0].range = vec![0, 4, 11];
+        let missing = make_valid_doc("pkg/missing.go", "scip-go gomod m v1 `m`/Missing.");
+        let mut index = Index::new();
+        index.documents = vec![with_text, on_disk, missing];
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+
+        snapshot(&SnapshotArgs {
+            input: scip_file.path().to_path_buf(),
+            output: Some(output_dir.path().to_path_buf()),
+            source_root: Some(source_dir.path().to_path_buf()),
+            filter: DocumentFilterArgs::default(),
+            infer_language: false,
+            language: None,
+        })
+        .expect("snapshot failed");
+
+        let read = |path: &str| std::fs::read_to_string(output_dir.path().join(path)).unwrap();
+        assert_eq!(
+            read("pkg/inline.go"),
+            "  func Inline() {}\n//    ^^^^^^ definition scip-go gomod m v1 `m`/Inline.\n"
+        );
+        assert_eq!(
+            read("pkg/disk.go"),
+            "  var diskVal = 1\n//    ^^^^^^^ definition scip-go gomod m v1 `m`/diskVal.\n"
+        );
+        assert!(!output_dir.path().join("pkg/missing.go").exists());
+    }
+
+    #[test]
+    fn test_decode_scip_reader_matches_decode_scip_data() {
+        let mut index = Index::new();
+        index
+            .documents
+            .push(make_valid_doc("a.go", "scip-go gomod m v1 `m`/A."));
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index.clone());
+        let options = DecodeOptions {
+            path_prefix: Some("root/".to_string()),
+            ..Default::default()
+        };
+
+        let mut from_file = Env::new();
+        decode_scip_data(&mut from_file, scip_file.path(), &options).unwrap();
+        let mut from_bytes = Env::new();
+        let bytes = index.write_to_bytes().unwrap();
+        decode_scip_reader(&mut from_bytes, bytes.as_slice(), &options).unwrap();
+
+        let from_file = from_file.output();
+        assert_eq!(from_file.source_files(), vec!["root/a.go"]);
+        assert_eq!(from_file.resolve(), from_bytes.output().resolve());
+    }
+
+    #[test]
+    fn test_config_file_with_per_input_overrides() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        for name in ["first", "second"] {
+            let mut index = Index::new();
+            index.documents.push(make_valid_doc(
+                &format!("{}.go", name),
+                "scip-go gomod m v1 `m`/A.",
+            ));
+            let mut file = File::create(dir.path().join(format!("{}.scip", name))).unwrap();
+            write_scip_index_full(&mut file, index);
+        }
+        let config = dir.path().join("scip-to-glean.toml");
+        std::fs::write(
+            &config,
+            r#"
+                output = "out.json"
+                root_prefix = "repo/"
+
+                [[input]]
+                path = "first.scip"
+
+                [[input]]
+                path = "second.scip"
+                root_prefix = "other/"
+            "#,
+        )
+        .unwrap();
+        let source_files = |root_prefix: Option<&str>| {
+            let mut args = build_args(PathBuf::new(), PathBuf::new());
+            args.input.clear();
+            args.output = None;
+            args.config = Some(config.clone());
+            args.root_prefix = root_prefix.map(str::to_owned);
+            build_json(args).expect("failure building JSON");
+            let json = std::fs::read_to_string(dir.path().join("out.json")).unwrap();
+            let files = find_predicate_facts(&json, "src.File.1").unwrap();
+            let mut files: Vec<String> = files
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|f| f["key"].as_str().unwrap().to_owned())
+                .collect();
+            files.sort();
+            files
+        };
+
+        assert_eq!(source_files(None), vec!["other/second.go", "repo/first.go"]);
+        // Flags take precedence over the file, for every input.
+        assert_eq!(
+            source_files(Some("cli/")),
+            vec!["cli/first.go", "cli/second.go"]
+        );
+    }
+
+    #[test]
+    fn test_normalize_paths_for_all_languages() {
+        let mut index = Index::new();
+        let mut metadata = ScipMetadata::new();
+        metadata.project_root = "file:///work/repo".to_string();
+        index.metadata = Some(metadata).into();
+        for path in [
+            "./src//a/../b.go",
+            "/work/repo/src/c.go",
+            "../repo/d.go",
+            "../other/e.go",
+        ] {
+            index
+                .documents
+                .push(make_valid_doc(path, "scip-go gomod m v1 `m`/A."));
+        }
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+        let files_with = |escaping_paths, resolve_project_root| {
+            let output_json = NamedTempFile::new().expect("Failed to create temp file");
+            let mut args = build_args(
+                scip_file.path().to_path_buf(),
+                output_json.path().to_path_buf(),
+            );
+            args.normalize_paths = PathNormalization::All;
+            args.escaping_paths = Some(escaping_paths);
+            args.resolve_project_root = resolve_project_root;
+            build_json(args).expect("failure building JSON");
+            let json = std::fs::read_to_string(output_json.path()).unwrap();
+            let files = find_predicate_facts(&json, "src.File.1").unwrap();
+            let mut files: Vec<String> = files
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|f| f["key"].as_str().unwrap().to_string())
+                .collect();
+            files.sort();
+            files
+        };
+
+        assert_eq!(
+            files_with(EscapingPaths::Skip, false),
+            vec!["/work/repo/src/c.go", "src/b.go"]
+        );
+        assert_eq!(
+            files_with(EscapingPaths::Strip, false),
+            vec!["/work/repo/src/c.go", "other/e.go", "repo/d.go", "src/b.go"]
+        );
+        assert_eq!(
+            files_with(EscapingPaths::Skip, true),
+            vec!["d.go", "src/b.go", "src/c.go"]
+        );
+        assert_eq!(
+            files_with(EscapingPaths::Keep, true),
+            vec!["../other/e.go", "d.go", "src/b.go", "src/c.go"]
+        );
+    }
+
+    #[test]
+    fn test_project_root_relocates_paths_and_reads_sources() {
+        let repo = tempfile::tempdir().unwrap();
+        std::fs::create_dir(repo.path().join(".git")).unwrap();
+        let project = repo.path().join("svc");
+        std::fs::create_dir(&project).unwrap();
+        std::fs::write(project.join("a.go"), "package a\n").unwrap();
+        let mut index = Index::new();
+        let mut metadata = ScipMetadata::new();
+        metadata.project_root = format!("file://{}", project.display());
+        index.metadata = Some(metadata).into();
+        index
+            .documents
+            .push(make_valid_doc("a.go", "scip-go gomod m v1 `m`/A."));
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        let output_json = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.find_repo_root = true;
+        build_json(args).expect("failure building JSON");
+
+        let json = std::fs::read_to_string(output_json.path()).unwrap();
+        let files = find_predicate_facts(&json, "src.File.1").unwrap();
+        assert_eq!(files[0]["key"], "svc/a.go");
+        // Without --source-root, the source is read from the project root.
+        assert!(find_predicate_facts(&json, "src.FileLines.1").is_some());
+    }
+
+    #[test]
+    fn test_ignore_project_root_sources() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::write(project.path().join("a.go"), "package a\n").unwrap();
+        let mut index = Index::new();
+        let mut metadata = ScipMetadata::new();
+        metadata.project_root = format!("file://{}", project.path().display());
+        index.metadata = Some(metadata).into();
+        index
+            .documents
+            .push(make_valid_doc("a.go", "scip-go gomod m v1 `m`/A."));
+        let file_lines = |ignore_project_root_sources| {
+            let options = DecodeOptions {
+                ignore_project_root_sources,
+                ..Default::default()
+            };
+            let mut env = Env::new();
+            decode_index(&mut env, index.clone(), &options).unwrap();
+            let mut json = Vec::new();
+            env.output().write(&mut json).unwrap();
+            find_predicate_facts(&String::from_utf8(json).unwrap(), "src.FileLines.1").is_some()
+        };
+        assert!(file_lines(false));
+        assert!(!file_lines(true));
+    }
+
+    #[test]
+    fn test_sources_from_bare_repository_at_revision() {
+        let checkout = tempfile::tempdir().unwrap();
+        let bare = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(checkout.path())
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .status()
+                .expect("unable to run git");
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        std::fs::create_dir(checkout.path().join("svc")).unwrap();
+        std::fs::write(
+            checkout.path().join("svc/a.go"),
+            "package a\n\nfunc A() {}\n",
+        )
+        .unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "a"]);
+        git(&["clone", "-q", "--bare", ".", &bare.path().to_string_lossy()]);
+
+        // Indexed in a checkout that does not exist on this machine.
+        let mut index = Index::new();
+        let mut metadata = ScipMetadata::new();
+        metadata.project_root = "file:///ci/checkout/svc".to_string();
+        index.metadata = Some(metadata).into();
+        index
+            .documents
+            .push(make_valid_doc("a.go", "scip-go gomod m v1 `m`/A."));
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        let output_json = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.repo_root = Some(PathBuf::from("/ci/checkout"));
+        args.source_repo = Some(bare.path().to_path_buf());
+        args.source_tree = Some("HEAD".to_string());
+        build_json(args).expect("failure building JSON");
+
+        let json = std::fs::read_to_string(output_json.path()).unwrap();
+        let files = find_predicate_facts(&json, "src.File.1").unwrap();
+        assert_eq!(files[0]["key"], "svc/a.go");
+        let file_lines = find_predicate_facts(&json, "src.FileLines.1").unwrap();
+        assert_eq!(
+            file_lines[0]["key"]["lengths"],
+            serde_json::json!([10, 1, 12])
+        );
+
+        // A project root outside the repository root cannot be found in it.
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.repo_root = Some(PathBuf::from("/ci/other"));
+        args.source_repo = Some(bare.path().to_path_buf());
+        args.source_tree = Some("HEAD".to_string());
+        let err = build_json(args).expect_err("project root outside the repository");
+        assert!(
+            format!("{:#}", err).contains("is outside repository root"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_language_map_overrides_builtin_table() {
+        let mut index = Index::new();
+        for path in ["proj/BUCK", "include/a.hpp"] {
+            let mut doc = make_valid_doc(path, "scip-go gomod m v1 `m`/A.");
+            doc.language.clear();
+            index.documents.push(doc);
+        }
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+        let languages_with = |infer_language| {
+            let output_json = NamedTempFile::new().expect("Failed to create temp file");
+            let stats_json = NamedTempFile::new().expect("Failed to create temp file");
+            let mut args = build_args(
+                scip_file.path().to_path_buf(),
+                output_json.path().to_path_buf(),
+            );
+            args.infer_language = infer_language;
+            args.language_map.language_map = vec![".hpp=c".to_string()];
+            args.stats_out = Some(stats_json.path().to_path_buf());
+            build_json(args).expect("failure building JSON");
+
+            let stats: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(stats_json.path()).unwrap()).unwrap();
+            let languages: Vec<String> = stats["factsByLanguage"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect();
+            languages
+        };
+        assert_eq!(languages_with(true), vec!["C", "Python"]);
+        // --language-map implies --infer-language.
+        assert_eq!(languages_with(false), vec!["C", "Python"]);
+    }
+
+    #[test]
+    fn test_file_contents_sharded_with_their_ranges() {
+        let mut index = Index::new();
+        for (path, text) in [
+            ("a.go", "package a\n".to_string()),
+            ("b.go", "package b\n\0".to_string()),
+            ("c.go", format!("package c\n{}\n", "// padding ".repeat(10))),
+        ] {
+            let mut doc = make_valid_doc(path, &format!("scip-go gomod m v1 `m`/{}.", path));
+            doc.text = text;
+            index.documents.push(doc);
+        }
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        let output_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let stats_json = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_dir.path().to_path_buf(),
+        );
+        args.shard = Some(1);
+        args.emit_file_contents = true;
+        args.max_file_content_bytes = 64;
+        args.stats_out = Some(stats_json.path().to_path_buf());
+        build_json(args).expect("failure building JSON");
+
+        let mut contents = Vec::new();
+        for entry in std::fs::read_dir(output_dir.path()).unwrap() {
+            let path = entry.unwrap().path();
+            if path.file_name().unwrap() == manifest::MANIFEST_FILE_NAME {
+                continue;
+            }
+            let json = std::fs::read_to_string(path).unwrap();
+            let Some(shard_contents) = find_predicate_facts(&json, "src.FileContent.1") else {
+                continue;
+            };
+            let ranges = find_predicate_facts(&json, "scip.FileRange.1").unwrap();
+            for content in shard_contents.as_array().unwrap() {
+                assert!(
+                    ranges
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .any(|range| range["key"]["file"] == content["key"])
+                );
+                contents.push(content["value"].clone());
+            }
+        }
+        assert_eq!(contents, vec![serde_json::json!("package a\n")]);
+
+        let stats: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(stats_json.path()).unwrap()).unwrap();
+        assert_eq!(stats["fileContentsBinary"], 1);
+        assert_eq!(stats["fileContentsTooLarge"], 1);
+    }
+
+    #[test]
+    fn test_file_digests_checked_against_expected_revision() {
+        let mut index = Index::new();
+        for path in ["a.go", "b.go", "c.go"] {
+            let mut doc = make_valid_doc(path, "scip-go gomod m v1 `m`/A.");
+            doc.text = format!("package {}\n", &path[..1]);
+            index.documents.push(doc);
+        }
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        let output_json = NamedTempFile::new().expect("Failed to create temp file");
+        let stats_json = NamedTempFile::new().expect("Failed to create temp file");
+        let digests = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+        std::fs::write(
+            digests.path(),
+            format!(
+                "{}  a.go\n{}  b.go\n",
+                digests::sha256_hex(b"package a\n"),
+                digests::sha256_hex(b"package b // edited\n"),
+            ),
+        )
+        .unwrap();
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.expected_digests = Some(digests.path().to_path_buf());
+        args.stats_out = Some(stats_json.path().to_path_buf());
+        build_json(args).expect("failure building JSON");
+
+        let json = std::fs::read_to_string(output_json.path()).unwrap();
+        let files: HashMap<u64, String> = find_predicate_facts(&json, "src.File.1")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|fact| {
+                let path = fact["key"].as_str().unwrap().to_string();
+                (fact["id"].as_u64().unwrap(), path)
+            })
+            .collect();
+        let mut file_digests: Vec<(String, String)> =
+            find_predicate_facts(&json, "src.FileDigest.1")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|fact| {
+                    let file = files[&fact["key"].as_u64().unwrap()].clone();
+                    (file, fact["value"].as_str().unwrap().to_string())
+                })
+                .collect();
+        file_digests.sort();
+        assert_eq!(
+            file_digests,
+            ["a", "b", "c"]
+                .map(|name| (
+                    format!("{}.go", name),
+                    digests::sha256_hex(format!("package {}\n", name).as_bytes())
+                ))
+                .to_vec()
+        );
+
+        let stats: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(stats_json.path()).unwrap()).unwrap();
+        assert_eq!(
+            stats["filesWithUnexpectedDigests"],
+            serde_json::json!({"count": 1, "paths": ["b.go"]})
+        );
+        assert_eq!(stats["filesWithoutExpectedDigests"], 1);
+    }
+
+    #[test]
+    fn test_infer_language_sniffs_extensionless_sources() {
+        let source_root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(source_root.path().join("bin")).unwrap();
+        std::fs::write(
+            source_root.path().join("bin/build"),
+            "#!/usr/bin/env bash\n",
+        )
+        .unwrap();
+        let mut index = Index::new();
+        let mut from_text = make_valid_doc("bin/gen", "scip-go gomod m v1 `m`/A.");
+        from_text.language.clear();
+        from_text.text = "#!/usr/bin/env python3\nimport os\n".to_string();
+        let mut from_disk = make_valid_doc("bin/build", "scip-go gomod m v1 `m`/B.");
+        from_disk.language.clear();
+        index.documents = vec![from_text, from_disk];
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        let output_json = NamedTempFile::new().expect("Failed to create temp file");
+        let stats_json = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.infer_language = true;
+        args.source_root = Some(source_root.path().to_path_buf());
+        args.stats_out = Some(stats_json.path().to_path_buf());
+        build_json(args).expect("failure building JSON");
+
+        let stats: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(stats_json.path()).unwrap()).unwrap();
+        let languages: Vec<&String> = stats["factsByLanguage"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .collect();
+        assert_eq!(languages, vec!["Python", "Shell"]);
+    }
+
+    #[test]
+    fn test_rewrite_rules_map_drop_and_report_paths() {
+        let mut index = Index::new();
+        for path in ["bazel-out/bin/gen.go", "src/a.go", "bazel-out/tmp/x.go"] {
+            index
+                .documents
+                .push(make_valid_doc(path, "scip-go gomod m v1 `m`/A."));
+        }
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        let output_json = NamedTempFile::new().expect("Failed to create temp file");
+        let stats_json = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.rewrite = vec![
+            "prefix:bazel-out/bin/=gen/".to_string(),
+            "prefix:bazel-out/".to_string(),
+        ];
+        args.root_prefix = Some("repo/".to_string());
+        args.stats_out = Some(stats_json.path().to_path_buf());
+        build_json(args).expect("failure building JSON");
+
+        let json = std::fs::read_to_string(output_json.path()).unwrap();
+        let files = find_predicate_facts(&json, "src.File.1").unwrap();
+        let mut files: Vec<&str> = files
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["key"].as_str().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["repo/gen/gen.go", "repo/src/a.go"]);
+        let stats: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(stats_json.path()).unwrap()).unwrap();
+        assert_eq!(stats["documentsDroppedByRewrites"], 1);
+        assert_eq!(
+            stats["pathsUnmatchedByRewrites"],
+            serde_json::json!({"count": 1, "paths": ["src/a.go"]})
+        );
+    }
+
+    /// Convert `index` and return the JSON output.
+    fn convert_index(index: Index, format: OutputFormat) -> String {
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        let output_json = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.format = format;
+        build_json(args).expect("failure building JSON");
+        std::fs::read_to_string(output_json.path()).expect("unable to read output")
+    }
+
+    fn index_from_facts(json: &str) -> Index {
+        let mut facts = reverse::FactSet::default();
+        facts.read(json.as_bytes()).expect("unable to read facts");
+        facts.into_index().expect("unable to rebuild index")
+    }
+
+    #[test]
+    fn test_from_json_restores_file_contents() {
+        let mut doc = make_valid_doc("a.go", "scip-go gomod m v1 `m`/A.");
+        doc.text = "package a\n".to_string();
+        let mut index = Index::new();
+        index.documents.push(doc);
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        let output_json = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.emit_file_contents = true;
+        build_json(args).expect("failure building JSON");
+
+        let rebuilt = index_from_facts(&std::fs::read_to_string(output_json.path()).unwrap());
+        assert_eq!(rebuilt.documents[0].text, "package a\n");
+    }
+
+    #[test]
+    fn test_from_json_rebuilds_index() {
+        let foo = "scip-go gomod m v1 `m`/Foo#";
+        let iface = "scip-go gomod m v1 `m`/Iface#";
+        let mut doc = Document::new();
+        doc.relative_path = "a.go".to_string();
+        doc.language = "go".to_string();
+        for (symbol, range, roles) in [
+            (iface, vec![0, 5, 10], 1),
+            (foo, vec![2, 5, 8], 1),
+            ("local 0", vec![3, 1, 4, 2], 1),
+            (foo, vec![6, 0, 3], 0),
+        ] {
+            let mut occ = ScipOccurrence::new();
+            occ.symbol = symbol.to_string();
+            occ.range = range;
+            occ.symbol_roles = roles;
+            if symbol == foo && roles == 1 {
+                occ.enclosing_range = vec![2, 0, 4, 1];
+            }
+            doc.occurrences.push(occ);
+        }
+        let mut info = ScipSymbolInformation::new();
+        info.symbol = foo.to_string();
+        info.documentation.push("Foo docs".to_string());
+        info.display_name = "Foo".to_string();
+        info.kind = symbol_information::Kind::Function.into();
+        info.enclosing_symbol = iface.to_string();
+        let mut rel = Relationship::new();
+        rel.symbol = iface.to_string();
+        rel.is_implementation = true;
+        info.relationships.push(rel);
+        doc.symbols.push(info);
+
+        let mut index = Index::new();
+        let mut metadata = ScipMetadata::new();
+        metadata.text_document_encoding = TextEncoding::UTF8.into();
+        index.metadata = protobuf::MessageField::some(metadata);
+        index.documents.push(doc);
+        let mut external = ScipSymbolInformation::new();
+        external.symbol = "scip-go gomod dep v1 `dep`/Ext#".to_string();
+        external.documentation.push("Ext docs".to_string());
+        index.external_symbols.push(external.clone());
+
+        for format in [OutputFormat::Json, OutputFormat::Ndjson] {
+            let rebuilt = index_from_facts(&convert_index(index.clone(), format));
+            assert_eq!(
+                rebuilt.metadata.text_document_encoding.enum_value(),
+                Ok(TextEncoding::UTF8)
+            );
+            assert_eq!(rebuilt.documents.len(), 1);
+            let doc = &rebuilt.documents[0];
+            assert_eq!(doc.relative_path, "a.go");
+            assert_eq!(doc.language, "go");
+            let occurrences: Vec<_> = doc
+                .occurrences
+                .iter()
+                .map(|occ| {
+                    (
+                        occ.symbol.as_str(),
+                        occ.range.clone(),
+                        occ.symbol_roles,
+                        occ.enclosing_range.clone(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                occurrences,
+                vec![
+                    (iface, vec![0, 5, 10], 1, vec![]),
+                    (foo, vec![2, 5, 8], 1, vec![2, 0, 4, 1]),
+                    ("local 0", vec![3, 1, 4, 2], 1, vec![]),
+                    (foo, vec![6, 0, 3], 0, vec![]),
+                ]
+            );
+            assert_eq!(doc.symbols.len(), 1);
+            let info = &doc.symbols[0];
+            assert_eq!(info.symbol, foo);
+            assert_eq!(info.documentation, vec!["Foo docs"]);
+            assert_eq!(info.display_name, "Foo");
+            assert_eq!(
+                info.kind.enum_value(),
+                Ok(symbol_information::Kind::Function)
+            );
+            assert_eq!(info.enclosing_symbol, iface);
+            assert_eq!(info.relationships.len(), 1);
+            assert!(info.relationships[0].is_implementation);
+            assert_eq!(rebuilt.external_symbols, vec![external.clone()]);
+        }
+    }
+
+    fn arb_round_trip_index() -> impl Strategy<Value = Index> {
+        let symbol = prop_oneof![
+            (0..4u8).prop_map(|n| format!("scip-go gomod m v1 `m`/S{}.", n)),
+            (0..3u8).prop_map(|n| format!("scip-go gomod m v1 `m`/T{}#", n)),
+            (0..3u8).prop_map(|n| format!("scip-go gomod dep v1 `dep`/E{}.", n)),
+            (0..3u8).prop_map(|n| format!("local {}", n)),
+        ];
+        let range = prop_oneof![
+            (0..20i32, 0..10i32, 0..5i32).prop_map(|(l, c, len)| vec![l, c, c + len]),
+            (0..20i32, 0..10i32, 1..4i32, 0..10i32).prop_map(|(l, c, n, e)| vec![l, c, l + n, e]),
+        ];
+        let occurrence = (symbol, range, any::<bool>()).prop_map(|(symbol, range, def)| {
+            let mut occ = ScipOccurrence::new();
+            occ.symbol = symbol;
+            occ.range = range;
+            occ.symbol_roles = i32::from(def);
+            occ
+        });
+        let kind = prop_oneof![
+            Just(symbol_information::Kind::UnspecifiedKind),
+            Just(symbol_information::Kind::Constant),
+            Just(symbol_information::Kind::Class),
+            Just(symbol_information::Kind::Method),
+            Just(symbol_information::Kind::Variable),
+            Just(symbol_information::Kind::Macro),
+        ];
+        // (occurrence to describe, kind, documentation, display name)
+        let info = (
+            any::<prop::sample::Index>(),
+            kind,
+            proptest::option::of("[a-z]{1,8}"),
+            proptest::option::of("[A-Z][a-z]{0,5}"),
+        );
+        let document = (
+            prop::collection::vec(occurrence, 0..12),
+            prop::collection::vec(info, 0..4),
+        );
+        let external = (0..3u8, proptest::option::of("[a-z]{1,8}"), any::<bool>());
+        (
+            prop::collection::vec(document, 1..4),
+            prop::collection::vec(external, 0..3),
+        )
+            .prop_map(|(documents, externals)| {
+                let mut index = Index::new();
+                // Describe each symbol at most once: the facts cannot tell
+                // apart two display names given by two SymbolInformation.
+                let mut described = HashSet::new();
+                for (i, (occurrences, infos)) in documents.into_iter().enumerate() {
+                    let mut doc = Document::new();
+                    doc.relative_path = format!("d{}.go", i);
+                    doc.language = "go".to_string();
+                    for (which, kind, docs, display_name) in infos {
+                        // Only describe symbols occurring in the document, so
+                        // no documentation is orphaned.
+                        if occurrences.is_empty() {
+                            break;
+                        }
+                        let symbol = &which.get(&occurrences).symbol;
+                        if !described.insert(qualify_scip_symbol(symbol, &doc.relative_path)) {
+                            continue;
+                        }
+                        let mut info = ScipSymbolInformation::new();
+                        info.symbol = symbol.clone();
+                        info.kind = kind.into();
+                        info.documentation.extend(docs);
+                        info.display_name = display_name.unwrap_or_default();
+                        doc.symbols.push(info);
+                    }
+                    doc.occurrences = occurrences;
+                    index.documents.push(doc);
+                }
+                for (n, docs, constant) in externals {
+                    let symbol = format!("scip-go gomod dep v1 `dep`/E{}.", n);
+                    if !described.insert(symbol.clone().into_boxed_str()) {
+                        continue;
+                    }
+                    let mut info = ScipSymbolInformation::new();
+                    info.symbol = symbol;
+                    info.documentation.extend(docs);
+                    if constant {
+                        info.kind = symbol_information::Kind::Constant.into();
+                    }
+                    index.external_symbols.push(info);
+                }
+                index
+            })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        /// Converting the index rebuilt from a conversion's facts yields the
+        /// same facts again.
+        #[test]
+        fn test_from_json_round_trip(index in arb_round_trip_index()) {
+            let facts = convert_index(index, OutputFormat::Json);
+            let rebuilt = index_from_facts(&facts);
+            let facts_again = convert_index(rebuilt, OutputFormat::Json);
+
+            // Fact ids depend on the order facts are emitted in, so compare
+            // what the facts describe rather than the JSON.
+            prop_assert_eq!(index_from_facts(&facts), index_from_facts(&facts_again));
+        }
+    }
+
+    /// Helper to create a SCIP index file with a single document
+    fn write_scip_index(scip_file: &mut impl std::io::Write, doc: Document) {
+        let mut index = Index::new();
+        index.documents.push(doc);
+        index
+            .write_to_writer(scip_file)
+            .expect("failed to write SCIP index");
+    }
+
+    /// Helper to write a full Index (with documents and external_symbols)
+    fn write_scip_index_full(scip_file: &mut impl std::io::Write, index: Index) {
+        index
+            .write_to_writer(scip_file)
+            .expect("failed to write SCIP index");
+    }
+
+    /// Helper to find a predicate's facts array in the output JSON
+    fn find_predicate_facts(json: &str, predicate: &str) -> Option<serde_json::Value> {
+        let parsed: serde_json::Value =
+            serde_json::from_str(json).expect("output is not valid JSON");
+        let entries = parsed.as_array().expect("top-level should be array");
+        entries
+            .iter()
+            .find(|entry| entry["predicate"].as_str() == Some(predicate))
+            .map(|entry| entry["facts"].clone())
+    }
+
+    fn sorted_file_range_line_begins(json: &str) -> Vec<u64> {
+        let mut line_begins: Vec<_> = find_predicate_facts(json, "scip.FileRange.1")
+            .expect("scip.FileRange.1 not found")
+            .as_array()
+            .expect("scip.FileRange facts should be array")
+            .iter()
+            .map(|fact| {
+                fact["key"]["range"]["lineBegin"]
+                    .as_u64()
+                    .expect("lineBegin should be an integer")
+            })
+            .collect();
+        line_begins.sort_unstable();
+        line_begins
+    }
+
+    #[test]
+    fn test_file_lines_from_document_text() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut doc = Document::new();
+        doc.relative_path = "test.go".to_string();
+        doc.language = "go".to_string();
+        doc.text = "package main\n\nfunc main() {\n}\n".to_string();
+        write_scip_index(&mut scip_file, doc);
+
+        let args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        build_json(args).expect("failure building JSON");
+
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        // Verify src.FileLines predicate is present
+        let file_lines_facts = find_predicate_facts(&output, "src.FileLines.1")
+            .expect("src.FileLines.1 predicate not found in output");
+        let facts = file_lines_facts.as_array().expect("facts should be array");
+        assert_eq!(facts.len(), 1, "expected exactly one FileLines fact");
+
+        let fact = &facts[0]["key"];
+        // "package main\n" = 13, "\n" = 1, "func main() {\n" = 14, "}\n" = 2
+        let lengths: Vec<u64> = fact["lengths"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_u64().unwrap())
+            .collect();
+        assert_eq!(lengths, vec![13, 1, 14, 2]);
+        assert_eq!(fact["endsInNewline"], true);
+        assert_eq!(fact["hasUnicodeOrTabs"], false);
+    }
+
+    #[test]
+    fn test_file_lines_from_source_root() {
+        let source_dir = tempfile::TempDir::new().expect("unable to create temp dir");
+        let source_file_path = source_dir.path().join("hello.go");
+        std::fs::write(&source_file_path, "package hello\n\tfmt.Println()\n")
+            .expect("failed to write source file");
+
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        // Document with no text — FileLines should come from disk via source_root
+        let mut doc = Document::new();
+        doc.relative_path = "hello.go".to_string();
+        doc.language = "go".to_string();
+        write_scip_index(&mut scip_file, doc);
+
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.source_root = Some(source_dir.path().to_path_buf());
+        build_json(args).expect("failure building JSON");
+
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        let file_lines_facts = find_predicate_facts(&output, "src.FileLines.1")
+            .expect("src.FileLines.1 predicate not found in output");
+        let facts = file_lines_facts.as_array().expect("facts should be array");
+        assert_eq!(facts.len(), 1, "expected exactly one FileLines fact");
+
+        let fact = &facts[0]["key"];
+        // "package hello\n" = 14, "\tfmt.Println()\n" = 15
+        let lengths: Vec<u64> = fact["lengths"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_u64().unwrap())
+            .collect();
+        assert_eq!(lengths, vec![14, 15]);
+        assert_eq!(fact["endsInNewline"], true);
+        assert_eq!(fact["hasUnicodeOrTabs"], true); // has tab character
+    }
+
+    #[test]
+    fn test_missing_sources_reported_and_fail_above_threshold() {
+        let source_dir = tempfile::tempdir().unwrap();
+        std::fs::write(source_dir.path().join("a.go"), "package a\n").unwrap();
+        let mut index = Index::new();
+        for path in ["a.go", "b.go"] {
+            index
+                .documents
+                .push(make_valid_doc(path, "scip-go gomod m v1 `m`/A."));
+        }
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index_full(&mut scip_file, index);
+        let output_json = NamedTempFile::new().expect("Failed to create temp file");
+        let stats_json = NamedTempFile::new().expect("Failed to create temp file");
+        let args = |max_missing_sources_percent| {
+            let mut args = build_args(
+                scip_file.path().to_path_buf(),
+                output_json.path().to_path_buf(),
+            );
+            args.source_root = Some(source_dir.path().to_path_buf());
+            args.stats_out = Some(stats_json.path().to_path_buf());
+            args.missing_sources = MissingSourcesPolicy {
+                on_missing_sources: sources::MissingSources::Fail,
+                max_missing_sources_percent,
+            };
+            args
+        };
+
+        let err = build_json(args(10)).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("1 of 2 source files could not be read"),
+            "{:#}",
+            err
+        );
+        let stats: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(stats_json.path()).unwrap()).unwrap();
+        assert_eq!(stats["sourceFilesRequested"], 2);
+        assert_eq!(
+            stats["sourceFilesMissing"],
+            serde_json::json!({"count": 1, "paths": ["b.go"]})
+        );
+
+        build_json(args(50)).expect("failure building JSON");
+        let json = std::fs::read_to_string(output_json.path()).unwrap();
+        let file_lines = find_predicate_facts(&json, "src.FileLines.1").unwrap();
+        assert_eq!(file_lines.as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_file_lines_from_source_archive() {
+        let source_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(source_dir.path().join("pkg")).unwrap();
+        std::fs::write(
+            source_dir.path().join("pkg/a.go"),
+            "package a\n\nfunc A() {}\n",
+        )
+        .unwrap();
+        let archive = source_dir.path().join("sources.tar");
+        let status = std::process::Command::new("tar")
+            .arg("-cf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(source_dir.path())
+            .arg("./pkg")
+            .status()
+            .expect("unable to run tar");
+        assert!(status.success());
+
+        let mut scip_file = NamedTempFile::new().expect("Failed to create temp file");
+        write_scip_index(
+            &mut scip_file,
+            make_valid_doc("pkg/a.go", "scip-go gomod m v1 `m`/A."),
+        );
+        let output_json = NamedTempFile::new().expect("Failed to create temp file");
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.source_archive = Some(archive);
+        build_json(args).expect("failure building JSON");
+
+        let json = std::fs::read_to_string(output_json.path()).unwrap();
+        let file_lines = find_predicate_facts(&json, "src.FileLines.1").unwrap();
+        assert_eq!(
+            file_lines[0]["key"]["lengths"],
+            serde_json::json!([10, 1, 12])
+        );
+    }
+
+    #[test]
+    fn test_no_file_lines_without_source_root_or_text() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        // Document with no text and no source_root — no FileLines should be emitted
+        let mut doc = Document::new();
+        doc.relative_path = "test.go".to_string();
+        doc.language = "go".to_string();
+        write_scip_index(&mut scip_file, doc);
+
+        let args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        build_json(args).expect("failure building JSON");
+
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        // src.FileLines should NOT be present
+        assert!(
+            find_predicate_facts(&output, "src.FileLines.1").is_none(),
+            "src.FileLines should not be emitted without source_root or document text"
+        );
+    }
+
+    #[test]
+    fn test_go_line_directives_remap_virtual_ranges_to_physical_lines() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut doc = Document::new();
+        doc.relative_path = "generated.go".to_string();
+        doc.language = "go".to_string();
+        doc.text = [
+            "// Code generated by a parser generator. DO NOT EDIT.\n",
+            "\n",
+            "//line original.y:3\n",
+            "func fromOriginal() {}\n",
+            "\n",
+            "//line parser:1\n",
+            "type parserHelper int\n",
+            "\n",
+            "func fromParser() {}\n",
+        ]
+        .concat();
+
+        let mut original_occ = ScipOccurrence::new();
+        original_occ.symbol = "scip-go gomod . . `generated`/fromOriginal().".to_string();
+        original_occ.range = vec![2, 0, 0];
+        original_occ.enclosing_range = vec![2, 0, 2, 22];
+        original_occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(original_occ);
+
+        let mut parser_occ = ScipOccurrence::new();
+        parser_occ.symbol = "scip-go gomod . . `generated`/fromParser().".to_string();
+        parser_occ.range = vec![2, 0, 0];
+        parser_occ.enclosing_range = vec![2, 0, 2, 20];
+        parser_occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(parser_occ);
+
+        write_scip_index(&mut scip_file, doc);
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        assert_eq!(
+            sorted_file_range_line_begins(&output),
+            vec![4, 4, 9, 9],
+            "both symbols reported virtual line 3, but FileRange facts should use their physical generated.go lines"
+        );
+    }
+
+    #[test]
+    fn test_attribute_ranges_to_directive_files() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut doc = Document::new();
+        doc.relative_path = "parser/parse.tab.c".to_string();
+        doc.language = "c".to_string();
+        doc.text = [
+            "#line 12 \"../grammar/parse.y\"\n",
+            "int from_grammar(void) {}\n",
+            "#line 4 \"parse.tab.c\"\n",
+            "int from_skeleton(void) {}\n",
+        ]
+        .concat();
+        for (symbol, line) in [("from_grammar", 11), ("from_skeleton", 3)] {
+            let mut occ = ScipOccurrence::new();
+            occ.symbol = format!("cxx . . $ {}(49f6e7a06ebc5aa8).", symbol);
+            occ.range = vec![line, 4, 16];
+            occ.symbol_roles = 1; // Definition
+            doc.occurrences.push(occ);
+        }
+
+        write_scip_index(&mut scip_file, doc);
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.attribute_to_directive_files = true;
+        build_json(args).expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        let by_id = |predicate| -> HashMap<u64, serde_json::Value> {
+            find_predicate_facts(&output, predicate)
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|fact| (fact["id"].as_u64().unwrap(), fact["key"].clone()))
+                .collect()
+        };
+        let files = by_id("src.File.1");
+        let file_ranges = by_id("scip.FileRange.1");
+        let location = |id: &serde_json::Value| {
+            let file_range = &file_ranges[&id.as_u64().unwrap()];
+            (
+                files[&file_range["file"].as_u64().unwrap()].clone(),
+                file_range["range"]["lineBegin"].as_u64().unwrap(),
+            )
+        };
+        let mut definitions: Vec<_> = find_predicate_facts(&output, "scip.Definition.1")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|fact| location(&fact["key"]["location"]))
+            .collect();
+        definitions.sort_by_key(|(_, line)| *line);
+        assert_eq!(
+            definitions,
+            vec![
+                (serde_json::json!("parser/parse.tab.c"), 4),
+                (serde_json::json!("grammar/parse.y"), 12),
+            ]
+        );
+        let generated = find_predicate_facts(&output, "scip.GeneratedRange.1").unwrap();
+        assert_eq!(generated.as_array().unwrap().len(), 1);
+        assert_eq!(
+            location(&generated[0]["key"]["range"]),
+            (serde_json::json!("grammar/parse.y"), 12)
+        );
+        assert_eq!(
+            location(&generated[0]["key"]["generated"]),
+            (serde_json::json!("parser/parse.tab.c"), 2)
+        );
+    }
+
+    #[test]
+    fn test_source_maps_attribute_ranges_to_original_sources() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+        let source_root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(source_root.path().join("dist")).unwrap();
+        std::fs::write(
+            source_root.path().join("dist/greet.js"),
+            "\"use strict\";\nfunction greet() {}\n//# sourceMappingURL=greet.js.map\n",
+        )
+        .unwrap();
+        // `greet` is on line 2 of the generated file and line 4 of the
+        // original, at column 9 in both.
+        std::fs::write(
+            source_root.path().join("dist/greet.js.map"),
+            r#"{"version":3,"sources":["../src/greet.ts"],"names":[],"mappings":";AAGA,SAAS,KAAK"}"#,
+        )
+        .unwrap();
+
+        let mut doc = Document::new();
+        doc.relative_path = "dist/greet.js".to_string();
+        doc.language = "javascript".to_string();
+        let mut occ = ScipOccurrence::new();
+        occ.symbol = "scip-typescript npm pkg 1.0.0 src/`greet.ts`/greet().".to_string();
+        occ.range = vec![1, 9, 14];
+        occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(occ);
+
+        write_scip_index(&mut scip_file, doc);
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.source_root = Some(source_root.path().to_path_buf());
+        build_json(args).expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        let files: HashMap<u64, String> = find_predicate_facts(&output, "src.File.1")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|fact| {
+                let path = fact["key"].as_str().unwrap().to_string();
+                (fact["id"].as_u64().unwrap(), path)
+            })
+            .collect();
+        let file_ranges = find_predicate_facts(&output, "scip.FileRange.1").unwrap();
+        let file_range = |id: &serde_json::Value| {
+            let fact = file_ranges
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|fact| fact["id"] == *id)
+                .unwrap();
+            let key = &fact["key"];
+            (
+                files[&key["file"].as_u64().unwrap()].as_str(),
+                key["range"]["lineBegin"].as_u64().unwrap(),
+                key["range"]["columnBegin"].as_u64().unwrap(),
+                key["range"]["columnEnd"].as_u64().unwrap(),
+            )
+        };
+        let definitions = find_predicate_facts(&output, "scip.Definition.1").unwrap();
+        assert_eq!(definitions.as_array().unwrap().len(), 1);
+        assert_eq!(
+            file_range(&definitions[0]["key"]["location"]),
+            ("src/greet.ts", 4, 10, 14)
+        );
+        let generated = find_predicate_facts(&output, "scip.GeneratedRange.1").unwrap();
+        assert_eq!(
+            file_range(&generated[0]["key"]["generated"]),
+            ("dist/greet.js", 2, 10, 14)
+        );
+    }
+
+    #[test]
+    fn test_c_line_directives_remap_virtual_ranges_to_physical_lines() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut doc = Document::new();
+        doc.relative_path = "parse.tab.c".to_string();
+        doc.language = "c".to_string();
+        doc.text = [
+            "/* A Bison parser, made by GNU Bison. */\n",
+            "\n",
+            "#line 3 \"parse.y\"\n",
+            "int from_grammar(void) {}\n",
+            "\n",
+            "#line 3 \"parse.tab.c\"\n",
+            "int from_skeleton(void) {}\n",
+        ]
+        .concat();
+
+        let mut grammar_occ = ScipOccurrence::new();
+        grammar_occ.symbol = "cxx . . $ from_grammar(49f6e7a06ebc5aa8).".to_string();
+        grammar_occ.range = vec![2, 4, 16];
+        grammar_occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(grammar_occ);
+
+        let mut skeleton_occ = ScipOccurrence::new();
+        skeleton_occ.symbol = "cxx . . $ from_skeleton(49f6e7a06ebc5aa8).".to_string();
+        skeleton_occ.range = vec![2, 4, 17];
+        skeleton_occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(skeleton_occ);
+
+        write_scip_index(&mut scip_file, doc);
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        assert_eq!(
+            sorted_file_range_line_begins(&output),
+            vec![4, 7],
+            "both symbols reported virtual line 3, of parse.y and parse.tab.c, but FileRange facts should use their physical lines"
+        );
+    }
+
+    #[test]
+    fn test_haskell_line_pragmas_remap_virtual_ranges_to_physical_lines() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut doc = Document::new();
+        doc.relative_path = "Lexer.hs".to_string();
+        doc.language = "haskell".to_string();
+        doc.text = [
+            "module Lexer where\n",
+            "{-# LINE 20 \"Lexer.x\" #-}\n",
+            "alexScan = undefined\n",
+        ]
+        .concat();
+
+        let mut occ = ScipOccurrence::new();
+        occ.symbol = "scip-haskell hackage lexer 0.1 Lexer/alexScan.".to_string();
+        occ.range = vec![19, 0, 8];
+        occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(occ);
+
+        write_scip_index(&mut scip_file, doc);
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        assert_eq!(sorted_file_range_line_begins(&output), vec![3]);
+    }
+
+    #[test]
+    fn test_typed_range_decoded_when_flat_range_empty() {
+        // A newer indexer sets only the typed range (single_line_range /
+        // multi_line_range) and leaves the deprecated flat `range` empty. The
+        // encoder must decode the typed form instead of panicking.
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut doc = Document::new();
+        doc.relative_path = "typed.go".to_string();
+        doc.language = "go".to_string();
+        doc.text = [
+            "package p\n",          // line 0
+            "\n",                   // line 1
+            "var singleLine = 0\n", // line 2
+            "var multiLine = 0\n",  // line 3
+        ]
+        .concat();
+
+        // Occurrence carrying ONLY a single-line typed range (empty `range`).
+        let mut single = ScipOccurrence::new();
+        single.symbol = "scip-go gomod . . `p`/singleLine.".to_string();
+        single.symbol_roles = 1; // Definition
+        let mut slr = SingleLineRange::new();
+        slr.line = 2;
+        slr.start_character = 4;
+        slr.end_character = 14;
+        single.set_single_line_range(slr);
+        doc.occurrences.push(single);
+
+        // Occurrence carrying ONLY a multi-line typed range (empty `range`).
+        let mut multi = ScipOccurrence::new();
+        multi.symbol = "scip-go gomod . . `p`/multiLine.".to_string();
+        multi.symbol_roles = 1; // Definition
+        let mut mlr = MultiLineRange::new();
+        mlr.start_line = 3;
+        mlr.start_character = 4;
+        mlr.end_line = 3;
+        mlr.end_character = 13;
+        multi.set_multi_line_range(mlr);
+        doc.occurrences.push(multi);
+
+        write_scip_index(&mut scip_file, doc);
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        // decode_scip_range converts 0-based SCIP lines to 1-based, so the typed
+        // ranges on lines 2 and 3 must yield FileRange facts at lineBegin 3 and 4.
+        assert_eq!(
+            sorted_file_range_line_begins(&output),
+            vec![3, 4],
+            "typed single_line_range/multi_line_range must decode to FileRange facts even with an empty deprecated `range`"
+        );
+    }
+
+    #[test]
+    fn test_external_symbols_create_symbol_and_documentation() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+
+        // Add a document with a reference to an external symbol
+        let mut doc = Document::new();
+        doc.relative_path = "test.java".to_string();
+        doc.language = "java".to_string();
+        index.documents.push(doc);
+
+        // Add an external symbol with documentation
+        let mut ext_sym = ScipSymbolInformation::new();
+        ext_sym.symbol = "semanticdb maven . . android/os/PowerManager#".to_string();
+        ext_sym.documentation = vec!["Controls the power state of the device.".to_string()];
+        ext_sym.display_name = "PowerManager".to_string();
+        index.external_symbols.push(ext_sym);
+
+        write_scip_index_full(&mut scip_file, index);
+
+        let args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        build_json(args).expect("failure building JSON");
+
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        // Verify the external symbol was created
+        let symbol_facts = find_predicate_facts(&output, "scip.Symbol.1")
+            .expect("scip.Symbol.1 predicate not found");
+        let symbols = symbol_facts.as_array().expect("facts should be array");
+        let has_pm = symbols.iter().any(|f| {
+            f["key"]
+                .as_str()
+                .is_some_and(|s| s.contains("PowerManager"))
+        });
+        assert!(has_pm, "Expected PowerManager symbol fact");
+
+        // Verify documentation was attached
+        let doc_facts = find_predicate_facts(&output, "scip.Documentation.1")
+            .expect("scip.Documentation.1 predicate not found");
+        let docs = doc_facts.as_array().expect("facts should be array");
+        let has_doc = docs
+            .iter()
+            .any(|f| f["key"].as_str().is_some_and(|s| s.contains("power state")));
+        assert!(has_doc, "Expected documentation for external symbol");
+
+        // Verify display name was created
+        let dn_facts = find_predicate_facts(&output, "scip.DisplayName.1")
+            .expect("scip.DisplayName.1 predicate not found");
+        let dns = dn_facts.as_array().expect("facts should be array");
+        let has_dn = dns
+            .iter()
+            .any(|f| f["key"].as_str().is_some_and(|s| s == "PowerManager"));
+        assert!(has_dn, "Expected display name for external symbol");
+    }
+
+    #[test]
+    fn test_external_symbols_empty_symbol_skipped() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+
+        // Add an external symbol with an empty symbol string (should be skipped)
+        let ext_sym = ScipSymbolInformation::new();
+        index.external_symbols.push(ext_sym);
+
+        write_scip_index_full(&mut scip_file, index);
+
+        let args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        build_json(args).expect("failure building JSON");
+
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        // No symbols should have been created
+        assert!(
+            find_predicate_facts(&output, "scip.Symbol.1").is_none(),
+            "No symbols should be created for empty external symbol"
+        );
+    }
+
+    #[test]
+    fn test_external_symbols_dedup_with_occurrence() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+
+        // Add a document with an occurrence referencing an external symbol
+        let mut doc = Document::new();
+        doc.relative_path = "test.java".to_string();
+        doc.language = "java".to_string();
+
+        let mut occ = ScipOccurrence::new();
+        occ.symbol = "semanticdb maven . . android/os/PowerManager#".to_string();
+        occ.range = vec![10, 5, 17]; // line 10, col 5-17
+        occ.symbol_roles = 0; // reference, not definition
+        doc.occurrences.push(occ);
+
+        index.documents.push(doc);
+
+        // Also add the same symbol as an external symbol with documentation
+        let mut ext_sym = ScipSymbolInformation::new();
+        ext_sym.symbol = "semanticdb maven . . android/os/PowerManager#".to_string();
+        ext_sym.documentation = vec!["Controls the power state.".to_string()];
+        index.external_symbols.push(ext_sym);
+
+        write_scip_index_full(&mut scip_file, index);
+
+        let args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        build_json(args).expect("failure building JSON");
+
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        // The symbol should exist exactly once (deduped)
+        let symbol_facts = find_predicate_facts(&output, "scip.Symbol.1")
+            .expect("scip.Symbol.1 predicate not found");
+        let symbols = symbol_facts.as_array().expect("facts should be array");
+        let pm_count = symbols
+            .iter()
+            .filter(|f| {
+                f["key"]
+                    .as_str()
+                    .is_some_and(|s| s.contains("PowerManager"))
+            })
+            .count();
+        assert_eq!(
+            pm_count, 1,
+            "PowerManager symbol should appear exactly once"
+        );
+
+        // There should be a reference
+        let ref_facts = find_predicate_facts(&output, "scip.Reference.1")
+            .expect("scip.Reference.1 predicate not found");
+        assert!(
+            !ref_facts.as_array().unwrap().is_empty(),
+            "Expected at least one reference"
+        );
+
+        // Documentation from external_symbols should still be attached
+        let doc_facts = find_predicate_facts(&output, "scip.Documentation.1")
+            .expect("scip.Documentation.1 predicate not found");
+        let docs = doc_facts.as_array().expect("facts should be array");
+        let has_doc = docs
+            .iter()
+            .any(|f| f["key"].as_str().is_some_and(|s| s.contains("power state")));
+        assert!(has_doc, "Expected documentation from external_symbols");
+    }
+
+    /// A Go-style global symbol whose `info.kind = Constant` is emitted as
+    /// `SkConstant`, overriding the descriptor-derived `SkVariable` that the
+    /// Term suffix `.` would otherwise produce. This is the bug that
+    /// previously caused all Go consts to be misclassified as variables.
+    #[test]
+    fn test_symbol_kind_global_const_overrides_variable() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        let mut doc = Document::new();
+        doc.relative_path = "initial/toplevel_decls.go".to_string();
+        doc.language = "go".to_string();
+
+        let global_const = "scip-go gomod sg/initial 0.1.test `sg/initial`/MY_THING.";
+
+        let mut occ = ScipOccurrence::new();
+        occ.symbol = global_const.to_string();
+        occ.range = vec![3, 6, 14];
+        occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(occ);
+
+        let mut info = ScipSymbolInformation::new();
+        info.symbol = global_const.to_string();
+        info.kind = symbol_information::Kind::Constant.into();
+        doc.symbols.push(info);
+
+        index.documents.push(doc);
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        let kind = find_kind_for_symbol(&output, "MY_THING")
+            .expect("expected scip.SymbolKind for MY_THING");
+        assert_eq!(
+            kind,
+            SymbolKind::SkConstant as u64,
+            "Go const should be SkConstant, got {kind}"
+        );
+    }
+
+    /// Without `info.kind`, a global Term-suffix symbol falls back to the
+    /// descriptor-derived `SkVariable`. Pins the existing behavior so a
+    /// future change to the override map can't silently change it.
+    #[test]
+    fn test_symbol_kind_global_term_defaults_to_variable() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        let mut doc = Document::new();
+        doc.relative_path = "initial/toplevel_decls.go".to_string();
+        doc.language = "go".to_string();
+
+        let global_var = "scip-go gomod sg/initial 0.1.test `sg/initial`/initFunctions.";
+
+        let mut occ = ScipOccurrence::new();
+        occ.symbol = global_var.to_string();
+        occ.range = vec![10, 4, 17];
+        occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(occ);
+
+        index.documents.push(doc);
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        let kind = find_kind_for_symbol(&output, "initFunctions")
+            .expect("expected scip.SymbolKind for initFunctions");
+        assert_eq!(
+            kind,
+            SymbolKind::SkVariable as u64,
+            "Term-suffix global without info.kind should remain SkVariable, got {kind}"
+        );
+    }
+
+    /// Local symbols have no descriptor-derived kind. The kind override map
+    /// must still apply, so a local with `info.kind = Constant` becomes
+    /// SkConstant rather than the SkVariable default.
+    #[test]
+    fn test_symbol_kind_local_const_override() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        let mut doc = Document::new();
+        doc.relative_path = "initial/toplevel_decls.go".to_string();
+        doc.language = "go".to_string();
+
+        let local_sym = "local 0";
+
+        let mut occ = ScipOccurrence::new();
+        occ.symbol = local_sym.to_string();
+        occ.range = vec![5, 8, 16];
+        occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(occ);
+
+        let mut info = ScipSymbolInformation::new();
+        info.symbol = local_sym.to_string();
+        info.kind = symbol_information::Kind::Constant.into();
+        doc.symbols.push(info);
+
+        index.documents.push(doc);
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        // Local symbols are namespaced by file path in the symbol fact key.
+        let kind = find_kind_for_symbol(&output, "local 0")
+            .expect("expected scip.SymbolKind for local symbol");
+        assert_eq!(
+            kind,
+            SymbolKind::SkConstant as u64,
+            "Local with info.kind=Constant should be SkConstant, got {kind}"
+        );
+    }
+
+    /// Pin the prior behavior for locals: with no `info.kind`, the local
+    /// path falls back to its `SkVariable` default. Pairs with
+    /// `test_symbol_kind_local_const_override` so the diff between the two
+    /// shows exactly what the override mechanism changes.
+    #[test]
+    fn test_symbol_kind_local_no_info_defaults_to_variable() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        let mut doc = Document::new();
+        doc.relative_path = "initial/toplevel_decls.go".to_string();
+        doc.language = "go".to_string();
+
+        let local_sym = "local 0";
+
+        let mut occ = ScipOccurrence::new();
+        occ.symbol = local_sym.to_string();
+        occ.range = vec![5, 8, 16];
+        occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(occ);
+
+        // No SymbolInformation entry — exercises the fallback path.
+
+        index.documents.push(doc);
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        let kind = find_kind_for_symbol(&output, "local 0")
+            .expect("expected scip.SymbolKind for local symbol");
+        assert_eq!(
+            kind,
+            SymbolKind::SkVariable as u64,
+            "Local without info.kind should default to SkVariable, got {kind}"
+        );
+    }
+
+    /// Symmetric "before" test for globals: when `info.kind = Variable` is set
+    /// explicitly, the result still maps to `SkVariable` (same as the
+    /// descriptor would have produced). Proves the override mechanism honors
+    /// upstream agreement, not just upstream disagreement — i.e., we are
+    /// reading `info.kind` rather than only triggering when it differs from
+    /// the descriptor.
+    #[test]
+    fn test_symbol_kind_global_explicit_variable_kind() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        let mut doc = Document::new();
+        doc.relative_path = "initial/toplevel_decls.go".to_string();
+        doc.language = "go".to_string();
+
+        let global_var = "scip-go gomod sg/initial 0.1.test `sg/initial`/initFunctions.";
+
+        let mut occ = ScipOccurrence::new();
+        occ.symbol = global_var.to_string();
+        occ.range = vec![10, 4, 17];
+        occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(occ);
+
+        let mut info = ScipSymbolInformation::new();
+        info.symbol = global_var.to_string();
+        info.kind = symbol_information::Kind::Variable.into();
+        doc.symbols.push(info);
+
+        index.documents.push(doc);
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        let kind = find_kind_for_symbol(&output, "initFunctions")
+            .expect("expected scip.SymbolKind for initFunctions");
+        assert_eq!(
+            kind,
+            SymbolKind::SkVariable as u64,
+            "Term symbol with info.kind=Variable should be SkVariable, got {kind}"
+        );
+    }
+
+    /// Narrowed-mapping boundary: an `info.kind` value outside the narrowed
+    /// override set (e.g. `Macro`) on a Term-suffix symbol must fall back to
+    /// the descriptor-derived kind (`SkVariable`) rather than producing
+    /// `SkUnknown` or refusing to emit a kind. Pins the design choice that
+    /// the override is *additive*, not *replacing*.
+    #[test]
+    fn test_symbol_kind_unrecognized_kind_falls_back_to_descriptor() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        let mut doc = Document::new();
+        doc.relative_path = "initial/toplevel_decls.go".to_string();
+        doc.language = "go".to_string();
+
+        let global_sym = "scip-go gomod sg/initial 0.1.test `sg/initial`/notMappedKind.";
+
+        let mut occ = ScipOccurrence::new();
+        occ.symbol = global_sym.to_string();
+        occ.range = vec![20, 4, 17];
+        occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(occ);
+
+        let mut info = ScipSymbolInformation::new();
+        info.symbol = global_sym.to_string();
+        // `Macro` is outside the narrowed override set, so it is not mapped
+        // and falls through to the descriptor-derived kind.
+        info.kind = symbol_information::Kind::Macro.into();
+        doc.symbols.push(info);
+
+        index.documents.push(doc);
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        let kind = find_kind_for_symbol(&output, "notMappedKind")
+            .expect("expected scip.SymbolKind for notMappedKind");
+        assert_eq!(
+            kind,
+            SymbolKind::SkVariable as u64,
+            "Unmapped info.kind should fall back to descriptor SkVariable, got {kind}"
+        );
+    }
+
+    /// External symbols (no occurrence) with `info.kind = Constant` should
+    /// be emitted as SkConstant. Covers the `decode_external_symbol` path,
+    /// which has its own kind-resolution branch separate from occurrences.
+    #[test]
+    fn test_symbol_kind_external_const() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+
+        // Need at least one document so processing runs.
+        let mut doc = Document::new();
+        doc.relative_path = "test.go".to_string();
+        doc.language = "go".to_string();
+        index.documents.push(doc);
+
+        let external_const =
+            "scip-go gomod golang.org/x/example 1.0.0 `golang.org/x/example`/SomeConst.";
+        let mut ext = ScipSymbolInformation::new();
+        ext.symbol = external_const.to_string();
+        ext.kind = symbol_information::Kind::Constant.into();
+        index.external_symbols.push(ext);
+
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        let kind = find_kind_for_symbol(&output, "SomeConst")
+            .expect("expected scip.SymbolKind for external SomeConst");
+        assert_eq!(
+            kind,
+            SymbolKind::SkConstant as u64,
+            "External const should be SkConstant, got {kind}"
+        );
+    }
+
+    /// Pin the prior behavior for externals: with no `info.kind`, the
+    /// external symbol path falls back to the descriptor-derived kind. Pairs
+    /// with `test_symbol_kind_external_const` so the diff between the two
+    /// shows exactly what the override mechanism changes for externals.
+    #[test]
+    fn test_symbol_kind_external_no_info_uses_descriptor() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+
+        // Need at least one document so processing runs.
+        let mut doc = Document::new();
+        doc.relative_path = "test.go".to_string();
+        doc.language = "go".to_string();
+        index.documents.push(doc);
+
+        let external_term =
+            "scip-go gomod golang.org/x/example 1.0.0 `golang.org/x/example`/SomeTerm.";
+        let mut ext = ScipSymbolInformation::new();
+        ext.symbol = external_term.to_string();
+        // No `kind` set — exercise the fallback path.
+        index.external_symbols.push(ext);
+
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        let kind = find_kind_for_symbol(&output, "SomeTerm")
+            .expect("expected scip.SymbolKind for external SomeTerm");
+        assert_eq!(
+            kind,
+            SymbolKind::SkVariable as u64,
+            "External Term symbol without info.kind should fall back to SkVariable, got {kind}"
+        );
+    }
+
+    /// Collect every distinct kind value emitted for the symbol fact whose key
+    /// string contains `symbol_substr`. Mirrors `find_kind_for_symbol` but
+    /// returns all kinds, sorted and deduplicated, so tests can pin cases
+    /// where the same symbol receives more than one kind value across the
+    /// pipeline.
+    fn find_all_kinds_for_symbol(json: &str, symbol_substr: &str) -> Vec<u64> {
+        let symbols =
+            find_predicate_facts(json, "scip.Symbol.1").expect("scip.Symbol.1 not found in output");
+        let kinds = find_predicate_facts(json, "scip.SymbolKind.1")
+            .expect("scip.SymbolKind.1 not found in output");
+        let symbol_id = symbols
+            .as_array()
+            .expect("symbol facts should be array")
+            .iter()
+            .find_map(|f| {
+                let key = f["key"].as_str()?;
+                if key.contains(symbol_substr) {
+                    f["id"].as_u64()
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_else(|| panic!("no Symbol fact whose key contains {symbol_substr:?}"));
+        let mut out: Vec<u64> = kinds
+            .as_array()
+            .expect("symbolkind facts should be array")
+            .iter()
+            .filter_map(|f| {
+                if f["key"]["symbol"].as_u64() == Some(symbol_id) {
+                    f["key"]["kind"].as_u64()
+                } else {
+                    None
+                }
+            })
+            .collect();
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+
+    /// Cross-document `SymbolInformation.kind` overrides must apply uniformly:
+    /// a global symbol defined in document A with `info.kind = Constant` and
+    /// referenced from a second document B should produce a *single*
+    /// `SymbolKind` fact (`SkConstant`) for that symbol, not one fact per
+    /// document. Pre-`Env::kind_overrides`, doc B's reference fell back to the
+    /// descriptor-derived `SkVariable` and the symbol ended up with two
+    /// contradictory kind facts — see the matching pinning test that landed
+    /// in D102345695 immediately below this commit, which expects the
+    /// pre-override `vec![SkVariable]` and is flipped here to `vec![SkConstant]`
+    /// in lockstep with the implementation change.
+    #[test]
+    fn test_symbol_kind_cross_doc_constant_uses_global_override() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let global_const = "scip-rust cargo crate_a 0.0.0 `crate_a`/SHARED.";
+
+        let mut index = Index::new();
+
+        // Doc A defines SHARED and registers info.kind = Constant.
+        let mut doc_a = Document::new();
+        doc_a.relative_path = "crate_a.rs".to_string();
+        doc_a.language = "rust".to_string();
+        let mut def_occ = ScipOccurrence::new();
+        def_occ.symbol = global_const.to_string();
+        def_occ.range = vec![1, 0, 12];
+        def_occ.symbol_roles = 1; // Definition
+        doc_a.occurrences.push(def_occ);
+        let mut info = ScipSymbolInformation::new();
+        info.symbol = global_const.to_string();
+        info.kind = symbol_information::Kind::Constant.into();
+        doc_a.symbols.push(info);
+        index.documents.push(doc_a);
+
+        // Doc B only references SHARED — no SymbolInformation entry. The
+        // override must still apply to its reference because the pre-pass
+        // populated `Env::kind_overrides` from every document up front.
+        let mut doc_b = Document::new();
+        doc_b.relative_path = "crate_b.rs".to_string();
+        doc_b.language = "rust".to_string();
+        let mut ref_occ = ScipOccurrence::new();
+        ref_occ.symbol = global_const.to_string();
+        ref_occ.range = vec![3, 4, 16];
+        ref_occ.symbol_roles = 0; // Reference
+        doc_b.occurrences.push(ref_occ);
+        index.documents.push(doc_b);
+
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        let kinds = find_all_kinds_for_symbol(&output, "SHARED");
+        assert_eq!(
+            kinds,
+            vec![SymbolKind::SkConstant as u64],
+            "Cross-doc constant should emit a single SkConstant fact: doc_b's \
+             reference must see the kind override registered from doc_a, not \
+             fall back to the descriptor-derived SkVariable."
+        );
+    }
+
+    #[test]
+    fn test_same_path_documents_merge_occurrences() {
+        // SCIP allows multiple Documents to share the same `relative_path`;
+        // indexers may split a single file's data across multiple Documents
+        // to stay under protobuf's 2 GB serialization limit (in particular,
+        // writeScipIndexChunks in fbcode/swift_devx/glean-indexer/scip/SCIPExporter.cpp).
+        // This test pins down that the consumer merges them: src.File and
+        // src.FileLines are emitted once, but every Document's occurrences
+        // contribute scip.FileRange facts.
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+
+        // First Document: carries the file text + first occurrence.
+        let mut doc_first = Document::new();
+        doc_first.relative_path = "shared.swift".to_string();
+        doc_first.language = "swift".to_string();
+        doc_first.text = "let a = 1\nlet b = 2\n".to_string();
+        let mut occ_first = ScipOccurrence::new();
+        occ_first.symbol = "scip-swift . . . `shared`/a.".to_string();
+        occ_first.range = vec![0, 4, 5];
+        occ_first.symbol_roles = 1; // Definition
+        doc_first.occurrences.push(occ_first);
+        index.documents.push(doc_first);
+
+        // Second Document: same path, second occurrence (no text -- the
+        // file metadata was already emitted from the first Document).
+        let mut doc_second = Document::new();
+        doc_second.relative_path = "shared.swift".to_string();
+        doc_second.language = "swift".to_string();
+        let mut occ_second = ScipOccurrence::new();
+        occ_second.symbol = "scip-swift . . . `shared`/b.".to_string();
+        occ_second.range = vec![1, 4, 5];
+        occ_second.symbol_roles = 1; // Definition
+        doc_second.occurrences.push(occ_second);
+        index.documents.push(doc_second);
+
+        write_scip_index_full(&mut scip_file, index);
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        // src.File should be emitted exactly once -- the second same-path
+        // Document must NOT register a new src.File fact.
+        let src_files = find_predicate_facts(&output, "src.File.1")
+            .expect("src.File.1 not found")
+            .as_array()
+            .expect("src.File facts should be array")
+            .len();
+        assert_eq!(
+            src_files, 1,
+            "expected exactly one src.File fact, got {}",
+            src_files
+        );
+
+        // src.FileLines is per-file metadata -- also exactly once.
+        let file_lines = find_predicate_facts(&output, "src.FileLines.1")
+            .expect("src.FileLines.1 not found")
+            .as_array()
+            .expect("src.FileLines facts should be array")
+            .len();
+        assert_eq!(
+            file_lines, 1,
+            "expected exactly one src.FileLines fact, got {}",
+            file_lines
+        );
+
+        // Both occurrences must contribute to scip.FileRange. Without merge
+        // semantics, the second Document's occurrence would be silently
+        // dropped and we would see only one FileRange.
+        let file_ranges = find_predicate_facts(&output, "scip.FileRange.1")
+            .expect("scip.FileRange.1 not found")
+            .as_array()
+            .expect("scip.FileRange facts should be array")
+            .len();
+        assert_eq!(
+            file_ranges, 2,
+            "expected one scip.FileRange per occurrence across both Documents, got {}",
+            file_ranges
+        );
+
+        // Both symbols should be present -- one from each Document.
+        let symbols = find_predicate_facts(&output, "scip.Symbol.1")
+            .expect("scip.Symbol.1 not found")
+            .as_array()
+            .expect("scip.Symbol facts should be array")
+            .len();
+        assert_eq!(
+            symbols, 2,
+            "expected one scip.Symbol per Document, got {}",
+            symbols
+        );
+    }
+
+    #[test]
+    fn test_same_path_documents_merge_n_way() {
+        // Merge must be n-way -- when an indexer splits a single file across
+        // four sibling Documents (rare but legal: writeScipIndexChunks may
+        // chain multiple continuation pieces for very large files), the
+        // consumer must still emit exactly one src.File and contribute every
+        // occurrence.
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+
+        // First doc carries text + first occurrence.
+        let mut first = Document::new();
+        first.relative_path = "huge.swift".to_string();
+        first.language = "swift".to_string();
+        first.text = "let a = 1\nlet b = 2\nlet c = 3\nlet d = 4\n".to_string();
+        let mut occ0 = ScipOccurrence::new();
+        occ0.symbol = "scip-swift . . . `huge`/a.".to_string();
+        occ0.range = vec![0, 4, 5];
+        occ0.symbol_roles = 1;
+        first.occurrences.push(occ0);
+        index.documents.push(first);
+
+        // Three continuation docs, one occurrence each, no metadata.
+        for (i, name) in [(1, "b"), (2, "c"), (3, "d")] {
+            let mut cont = Document::new();
+            cont.relative_path = "huge.swift".to_string();
+            cont.language = "swift".to_string();
+            let mut occ = ScipOccurrence::new();
+            occ.symbol = format!("scip-swift . . . `huge`/{}.", name);
+            occ.range = vec![i, 4, 5];
+            occ.symbol_roles = 1;
+            cont.occurrences.push(occ);
+            index.documents.push(cont);
+        }
+
+        write_scip_index_full(&mut scip_file, index);
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("failure building JSON");
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        // Exactly one src.File despite four same-path Documents.
+        let src_files = find_predicate_facts(&output, "src.File.1")
+            .expect("src.File.1 not found")
+            .as_array()
+            .expect("src.File facts should be array")
+            .len();
+        assert_eq!(src_files, 1, "n-way merge must dedupe src.File");
+
+        // Exactly one src.FileLines (per-file metadata, only first doc has text).
+        let file_lines = find_predicate_facts(&output, "src.FileLines.1")
+            .expect("src.FileLines.1 not found")
+            .as_array()
+            .expect("src.FileLines facts should be array")
+            .len();
+        assert_eq!(
+            file_lines, 1,
+            "src.FileLines comes from the first doc only and must not be duplicated"
+        );
+
+        // Every occurrence across all four Documents must contribute.
+        let file_ranges = find_predicate_facts(&output, "scip.FileRange.1")
+            .expect("scip.FileRange.1 not found")
+            .as_array()
+            .expect("scip.FileRange facts should be array")
+            .len();
+        assert_eq!(
+            file_ranges, 4,
+            "expected one scip.FileRange per occurrence across 4 Documents, got {}",
+            file_ranges
+        );
+
+        let symbols = find_predicate_facts(&output, "scip.Symbol.1")
+            .expect("scip.Symbol.1 not found")
+            .as_array()
+            .expect("scip.Symbol facts should be array")
+            .len();
+        assert_eq!(
+            symbols, 4,
+            "expected one scip.Symbol per Document, got {}",
+            symbols
+        );
+    }
+
+    /// Helper to create a valid SCIP document with a single definition occurrence.
+    fn make_valid_doc(path: &str, symbol: &str) -> Document {
+        let mut doc = Document::new();
+        doc.relative_path = path.to_string();
+        doc.language = "go".to_string();
+        let mut occ = ScipOccurrence::new();
+        occ.symbol = symbol.to_string();
+        occ.range = vec![0, 0, 10]; // valid 3-element range
+        occ.symbol_roles = 1; // Definition
+        doc.occurrences.push(occ);
+        doc
+    }
+
+    /// Helper to create a corrupted SCIP document whose occurrence has an
+    /// invalid range (1 element), which triggers `Err` from `decode_scip_doc`.
+    fn make_corrupted_doc(path: &str) -> Document {
+        let mut doc = Document::new();
+        doc.relative_path = path.to_string();
+        doc.language = "go".to_string();
+        let mut occ = ScipOccurrence::new();
+        occ.symbol = "scip-go gomod pkg 1.0 `pkg`/Bad.".to_string();
+        occ.range = vec![42]; // 1 element — invalid range
+        doc.occurrences.push(occ);
+        doc
+    }
+
+    /// A single corrupted document must not abort the entire conversion.
+    /// Facts from the valid document should still be emitted.
+    #[test]
+    fn test_error_isolation_skips_corrupted_document() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        index.documents.push(make_valid_doc(
+            "good.go",
+            "scip-go gomod pkg 1.0 `pkg`/Good.",
+        ));
+        index.documents.push(make_corrupted_doc("bad.go"));
+
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("build_json should succeed despite one corrupted document");
+
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        // The valid document's symbol should still be present.
+        let symbol_facts = find_predicate_facts(&output, "scip.Symbol.1")
+            .expect("scip.Symbol.1 not found — valid document facts were lost");
+        let symbols = symbol_facts.as_array().expect("facts should be array");
+        let has_good = symbols
+            .iter()
+            .any(|f| f["key"].as_str().is_some_and(|s| s.contains("Good")));
+        assert!(
+            has_good,
+            "Valid document's symbol should be emitted despite corrupted sibling"
+        );
+    }
+
+    /// When more than 50% of documents are corrupted, the converter should
+    /// return an error — something is fundamentally wrong with the input.
+    #[test]
+    fn test_error_isolation_fails_above_50_percent() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        // 1 valid, 2 corrupted → 66% failure rate
+        index
+            .documents
+            .push(make_valid_doc("good.go", "scip-go gomod pkg 1.0 `pkg`/Ok."));
+        index.documents.push(make_corrupted_doc("bad1.go"));
+        index.documents.push(make_corrupted_doc("bad2.go"));
+
+        write_scip_index_full(&mut scip_file, index);
+
+        let result = build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ));
+        assert!(
+            result.is_err(),
+            "Should fail when >50% of documents are corrupted"
+        );
+        let err_msg = format!("{}", result.unwrap_err());
+        assert!(
+            err_msg.contains("2 of 3"),
+            "Error message should report skip count: {err_msg}"
+        );
+    }
+
+    /// At exactly 50% failure rate (1 of 2), the converter should still
+    /// succeed — the threshold is strictly greater than 50%.
+    #[test]
+    fn test_error_isolation_succeeds_at_50_percent() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        // 1 valid, 1 corrupted → exactly 50%
+        index.documents.push(make_valid_doc(
+            "good.go",
+            "scip-go gomod pkg 1.0 `pkg`/Half.",
+        ));
+        index.documents.push(make_corrupted_doc("bad.go"));
+
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("Should succeed at exactly 50% failure rate");
+    }
+
+    /// All documents valid — zero skips, no error, all facts emitted.
+    #[test]
+    fn test_error_isolation_no_errors() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        index
+            .documents
+            .push(make_valid_doc("a.go", "scip-go gomod pkg 1.0 `pkg`/Alpha."));
+        index
+            .documents
+            .push(make_valid_doc("b.go", "scip-go gomod pkg 1.0 `pkg`/Beta."));
+
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("Should succeed with no corrupted documents");
+
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+
+        let symbol_facts =
+            find_predicate_facts(&output, "scip.Symbol.1").expect("scip.Symbol.1 not found");
+        let symbols = symbol_facts.as_array().expect("facts should be array");
+        assert_eq!(
+            symbols.len(),
+            2,
+            "Both documents' symbols should be emitted"
+        );
+    }
+
+    /// The abort threshold is configurable: 1 of 3 documents failing is
+    /// fatal at 20% but tolerated at the default 50%.
+    #[test]
+    fn test_failure_policy_custom_threshold() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        index
+            .documents
+            .push(make_valid_doc("a.go", "scip-go gomod pkg 1.0 `pkg`/Alpha."));
+        index
+            .documents
+            .push(make_valid_doc("b.go", "scip-go gomod pkg 1.0 `pkg`/Beta."));
+        index.documents.push(make_corrupted_doc("bad.go"));
+        write_scip_index_full(&mut scip_file, index);
+
+        build_json(build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        ))
+        .expect("1 of 3 failures is within the default threshold");
+
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.failure_policy.max_failure_percent = 20;
+        let err = build_json(args).expect_err("1 of 3 failures exceeds 20%");
+        assert!(
+            format!("{err}").contains("1 of 3 documents failed (>20 %)"),
+            "unexpected error: {err}"
+        );
+    }
+
+    /// Strict mode fails on the first bad document, regardless of threshold,
+    /// and the errors file still records it.
+    #[test]
+    fn test_failure_policy_strict_fails_on_first_error() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+        let errors_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        index
+            .documents
+            .push(make_valid_doc("a.go", "scip-go gomod pkg 1.0 `pkg`/Alpha."));
+        index
+            .documents
+            .push(make_valid_doc("b.go", "scip-go gomod pkg 1.0 `pkg`/Beta."));
+        index.documents.push(make_corrupted_doc("bad.go"));
+        write_scip_index_full(&mut scip_file, index);
+
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.failure_policy.strict = true;
+        args.errors_out = Some(errors_json.path().to_path_buf());
+        let err = build_json(args).expect_err("strict mode should fail on bad.go");
+        assert!(
+            format!("{err:#}").contains("bad.go"),
+            "error should name the document: {err:#}"
+        );
+
+        let errors: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(errors_json.path()).expect("unable to read errors"),
+        )
+        .expect("errors file is not valid JSON");
+        let errors = errors.as_array().expect("errors should be an array");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0]["document"], "bad.go");
+        assert!(errors[0].get("symbol").is_none());
+    }
+
+    /// Documents left out by the filter, a rewrite rule or an escaping path
+    /// are listed in the errors file too.
+    #[test]
+    fn test_errors_out_lists_documents_left_out() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+        let errors_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut index = Index::new();
+        for path in ["a.go", "gen/b.go", "vendor/c.go"] {
+            index
+                .documents
+                .push(make_valid_doc(path, "scip-go gomod pkg 1.0 `pkg`/A."));
+        }
+        let mut escaping = make_valid_doc("../../d.ts", "scip-typescript npm pkg 1.0 d/A.");
+        escaping.language = "typescript".to_string();
+        index.documents.push(escaping);
+        write_scip_index_full(&mut scip_file, index);
+
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.rewrite = vec!["prefix:gen/".to_string()];
+        args.filter.exclude = vec!["vendor/**".to_string()];
+        args.errors_out = Some(errors_json.path().to_path_buf());
+        build_json(args).expect("failure building JSON");
+
+        let errors: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(errors_json.path()).expect("unable to read errors"),
+        )
+        .expect("errors file is not valid JSON");
+        let mut errors: Vec<(&str, &str)> = errors
+            .as_array()
+            .expect("errors should be an array")
+            .iter()
+            .map(|e| {
+                (
+                    e["document"].as_str().unwrap(),
+                    e["reason"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        errors.sort();
+        assert_eq!(
+            errors,
+            vec![
+                ("../../d.ts", "path escapes its root"),
+                ("gen/b.go", "dropped by a --rewrite rule"),
+                ("vendor/c.go", "excluded by the document filter"),
+            ]
+        );
+    }
+
+    /// Per-occurrence skipping keeps the rest of a document that contains a
+    /// bad range, and reports the dropped occurrence.
+    #[test]
+    fn test_failure_policy_skip_bad_occurrences_keeps_document() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+        let output_json = NamedTempFile::new().expect("unable to create temp file");
+        let errors_json = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut doc = make_valid_doc("mixed.go", "scip-go gomod pkg 1.0 `pkg`/Good.");
+        let mut bad = ScipOccurrence::new();
+        bad.symbol = "scip-go gomod pkg 1.0 `pkg`/Bad.".to_string();
+        bad.range = vec![42]; // 1 element — invalid range
+        doc.occurrences.push(bad);
+        let mut after = ScipOccurrence::new();
+        after.symbol = "scip-go gomod pkg 1.0 `pkg`/After.".to_string();
+        after.range = vec![1, 0, 5];
+        doc.occurrences.push(after);
+
+        let mut index = Index::new();
+        index.documents.push(doc);
+        write_scip_index_full(&mut scip_file, index);
+
+        let mut args = build_args(
+            scip_file.path().to_path_buf(),
+            output_json.path().to_path_buf(),
+        );
+        args.failure_policy.skip_bad_occurrences = true;
+        args.errors_out = Some(errors_json.path().to_path_buf());
+        build_json(args).expect("a bad occurrence should not fail the document");
+
+        let output = std::fs::read_to_string(output_json.path()).expect("unable to read output");
+        let symbols = find_predicate_facts(&output, "scip.Symbol.1").expect("no symbols");
+        let names: Vec<&str> = symbols
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|f| f["key"].as_str())
+            .collect();
+        assert!(names.iter().any(|s| s.contains("Good")));
+        assert!(names.iter().any(|s| s.contains("After")));
+        assert!(!names.iter().any(|s| s.contains("Bad")));
+        assert_eq!(sorted_file_range_line_begins(&output), vec![1, 2]);
+
+        let errors: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(errors_json.path()).expect("unable to read errors"),
+        )
+        .expect("errors file is not valid JSON");
+        let errors = errors.as_array().expect("errors should be an array");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0]["document"], "mixed.go");
+        assert_eq!(errors[0]["symbol"], "scip-go gomod pkg 1.0 `pkg`/Bad.");
+        assert!(
+            errors[0]["reason"]
+                .as_str()
+                .is_some_and(|r| r.contains("bad range"))
+        );
+    }
+
+    /// With document text available, out-of-bounds ranges are clipped or
+    /// dropped according to `--validate-ranges`, and counted.
+    #[test]
+    fn test_validate_ranges_against_document_text() {
+        let mut scip_file = NamedTempFile::new().expect("unable to create temp file");
+
+        let mut doc = Document::new();
+        doc.relative_path = "short.go".to_string();
+        doc.language = "go".to_string();
+        doc.text = "package p\n\nvar ok = 1\n".to_string();
+        for (name, range) in [
+            ("Ok", vec![2, 4, 6]),
+            ("PastEol", vec![2, 4, 40]),
+            ("PastEof", vec![30, 0, 3]),
+        ] {
+            let mut occ = ScipOccurrence::new();
+            occ.symbol = format!("scip-go gomod pkg 1.0 `pkg`/{}.", name);
+            occ.range = range;
+            occ.symbol_roles = 1; // Definition
+            doc.occurrences.push(occ);
+        }
+        write_scip_index(&mut scip_file, doc);
+
+        let run = |validation: RangeValidation| {
+            let output_json = NamedTempFile::new().expect("unable to create temp file");
+            let stats_json = NamedTempFile::new().expect("unable to create temp file");
+            let mut args = build_args(
+                scip_file.path().to_path_buf(),
+                output_json.path().to_path_buf(),
+            );
+            args.validate_ranges = validation;
+            args.stats_out = Some(stats_json.path().to_path_buf());
+            build_json(args).expect("failure building JSON");
+            let output =
+                std::fs::read_to_string(output_json.path()).expect("unable to read output");
+            let stats: serde_json::Value = serde_json::from_str(
+                &std::fs::read_to_string(stats_json.path()).expect("unable to read stats"),
+            )
+            .unwrap();
+            (output, stats)
+        };
+
+        let (output, stats) = run(RangeValidation::Off);
+        assert_eq!(sorted_file_range_line_begins(&output), vec![3, 3, 31]);
+        assert_eq!(stats["rangesOutOfBounds"], 0);
+
+        let (output, stats) = run(RangeValidation::Drop);
+        assert_eq!(sorted_file_range_line_begins(&output), vec![3]);
+        assert_eq!(stats["rangesOutOfBounds"], 2);
+        assert_eq!(stats["rangesDropped"], 2);
+
+        let (output, stats) = run(RangeValidation::Clip);
+        assert_eq!(sorted_file_range_line_begins(&output), vec![3, 3]);
+        assert_eq!(stats["rangesClipped"], 1);
+        assert_eq!(stats["rangesDropped"], 1);
+        let column_ends: Vec<u64> = find_predicate_facts(&output, "scip.FileRange.1")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|fact| fact["key"]["range"]["columnEnd"].as_u64().unwrap())
+            .collect();
+        assert!(
+            column_ends.iter().all(|end| *end <= 10),
+            "clipped ranges must end within `var ok = 1`: {column_ends:?}"
+        );
+    }
+}
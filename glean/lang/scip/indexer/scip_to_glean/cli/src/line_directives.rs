@@ -0,0 +1,495 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Remapping ranges in generated code from virtual to physical lines.
+//!
+//! Generators mark where their output came from with line directives: Go's
+//! `//line file.y:3`, the C preprocessor's `#line 3 "file.y"` (and GCC's
+//! `# 3 "file.y"` linemarkers), Haskell's `{-# LINE 3 "file.y" #-}`. After a
+//! directive, the next line is line 3 of `file.y`, and indexers report the
+//! positions of what follows in those virtual lines. Each language's
+//! directive syntax implements `LineDirectiveSyntax`; `line_remapper` picks
+//! the one for a document and builds a `LineRemapper` from its source.
+
+use crate::GleanRange;
+use crate::lsif::LanguageId;
+
+/// Maps ranges of a document as an indexer reports them to the document's
+/// physical lines.
+pub trait LineRemapper {
+    fn remap_range(&self, range: GleanRange, symbol_hint: Option<&str>) -> GleanRange;
+
+    /// Where the generated code at the (unmapped) `range` came from: the
+    /// file, relative to the document's directory, and the range in it.
+    fn original_location(
+        &self,
+        range: &GleanRange,
+        symbol_hint: Option<&str>,
+    ) -> Option<(&str, GleanRange)>;
+
+    /// Whether occurrences are always attributed to their original location,
+    /// rather than only under `--attribute-to-directive-files`.
+    fn always_attribute(&self) -> bool {
+        false
+    }
+}
+
+/// One language's line directive syntax.
+pub trait LineDirectiveSyntax {
+    /// The directive on `line`, if it is one.
+    fn parse(&self, line: &[u8]) -> Option<LineDirective>;
+}
+
+/// The next line is line `line` of `file`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineDirective {
+    pub line: u64,
+    /// `None` keeps the previous directive's file.
+    pub file: Option<String>,
+}
+
+/// The line directive syntax of `lang`, if it has one.
+pub fn line_directive_syntax(lang: LanguageId) -> Option<&'static dyn LineDirectiveSyntax> {
+    match lang {
+        LanguageId::Go => Some(&GoLineDirectives),
+        LanguageId::C | LanguageId::Cpp | LanguageId::ObjectiveC | LanguageId::ObjectiveCpp => {
+            Some(&CLineDirectives)
+        }
+        LanguageId::Haskell => Some(&HaskellLineDirectives),
+        _ => None,
+    }
+}
+
+/// The remapper for a document of language `lang` with contents `source`.
+/// `None` if the document has no line directives.
+pub fn line_remapper(lang: LanguageId, source: &[u8]) -> Option<Box<dyn LineRemapper>> {
+    let syntax = line_directive_syntax(lang)?;
+    LineDirectiveMap::from_source(source, syntax).map(|map| Box::new(map) as Box<dyn LineRemapper>)
+}
+
+/// `//line file:line`, `//line file:line:col` and `/*line file:line */`.
+pub struct GoLineDirectives;
+
+impl LineDirectiveSyntax for GoLineDirectives {
+    fn parse(&self, line: &[u8]) -> Option<LineDirective> {
+        let line = trim_ascii(line);
+        let directive = if let Some(rest) = line.strip_prefix(b"//line ") {
+            rest
+        } else if let Some(rest) = line.strip_prefix(b"/*line ") {
+            trim_ascii(rest).strip_suffix(b"*/")?
+        } else {
+            return None;
+        };
+
+        let directive = std::str::from_utf8(trim_ascii(directive)).ok()?;
+        let (head, last) = directive.rsplit_once(':')?;
+        let (file, line_number) = match head.rsplit_once(':') {
+            Some((file, line_number))
+                if last.parse::<u64>().is_ok() && line_number.parse::<u64>().is_ok() =>
+            {
+                (file, line_number)
+            }
+            _ => (head, last),
+        };
+
+        let line_number = line_number.parse::<u64>().ok()?;
+        (line_number > 0).then(|| LineDirective {
+            line: line_number,
+            file: (!file.is_empty()).then(|| file.to_owned()),
+        })
+    }
+}
+
+/// `#line 3`, `#line 3 "file"` and GCC's `# 3 "file" flags`.
+pub struct CLineDirectives;
+
+impl LineDirectiveSyntax for CLineDirectives {
+    fn parse(&self, line: &[u8]) -> Option<LineDirective> {
+        let line = std::str::from_utf8(trim_ascii(line)).ok()?;
+        let rest = line.strip_prefix('#')?.trim_start();
+        let rest = match rest.strip_prefix("line") {
+            Some(rest) if rest.starts_with([' ', '\t']) => rest.trim_start(),
+            _ => rest,
+        };
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let line_number = rest[..digits].parse::<u64>().ok()?;
+        let rest = &rest[digits..];
+        if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+            return None;
+        }
+        let file = match rest.trim_start() {
+            "" => None,
+            rest => Some(parse_c_string(rest)?),
+        };
+        (line_number > 0).then_some(LineDirective {
+            line: line_number,
+            file,
+        })
+    }
+}
+
+/// `{-# LINE 3 "file" #-}`, and `#line` for sources run through CPP.
+pub struct HaskellLineDirectives;
+
+impl LineDirectiveSyntax for HaskellLineDirectives {
+    fn parse(&self, line: &[u8]) -> Option<LineDirective> {
+        let text = std::str::from_utf8(trim_ascii(line)).ok()?;
+        let Some(pragma) = text
+            .strip_prefix("{-#")
+            .and_then(|rest| rest.strip_suffix("#-}"))
+        else {
+            return CLineDirectives.parse(line);
+        };
+        let pragma = pragma.trim();
+        let keyword = pragma.get(..4)?;
+        if !keyword.eq_ignore_ascii_case("line") {
+            return None;
+        }
+        let rest = pragma[4..].trim_start();
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let line_number = rest[..digits].parse::<u64>().ok()?;
+        let file = parse_c_string(rest[digits..].trim_start())?;
+        (line_number > 0).then_some(LineDirective {
+            line: line_number,
+            file: Some(file),
+        })
+    }
+}
+
+/// The contents of the double-quoted string `text` starts with, with `\"`
+/// and `\\` unescaped.
+fn parse_c_string(text: &str) -> Option<String> {
+    let mut chars = text.strip_prefix('"')?.chars();
+    let mut string = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(string),
+            '\\' => string.push(chars.next()?),
+            c => string.push(c),
+        }
+    }
+}
+
+/// The physical lines between two directives, and the virtual lines they
+/// stand for.
+#[derive(Clone, Debug)]
+struct LineDirectiveSegment {
+    physical_start_line: u64,
+    physical_end_line: u64,
+    virtual_start_line: u64,
+    /// The file the virtual lines are in, `None` before any directive names
+    /// one.
+    file: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct LineDirectiveMap {
+    source: Vec<u8>,
+    line_starts: Vec<usize>,
+    segments: Vec<LineDirectiveSegment>,
+}
+
+impl LineDirectiveMap {
+    pub fn from_source(bytes: &[u8], syntax: &dyn LineDirectiveSyntax) -> Option<Self> {
+        let line_starts = Self::compute_line_starts(bytes);
+        let line_count = line_starts.len().saturating_sub(1) as u64;
+        let mut segments = Vec::new();
+        let mut open_segment: Option<(u64, LineDirective)> = None;
+        let mut file = None;
+
+        for physical_line in 1..=line_count {
+            let line = Self::line_text_from(bytes, &line_starts, physical_line);
+            if let Some(mut directive) = syntax.parse(line) {
+                if directive.file.is_none() {
+                    directive.file = file.clone();
+                }
+                file = directive.file.clone();
+                let prior_segment = open_segment.take();
+                if let Some((physical_start_line, prior)) = prior_segment {
+                    if physical_start_line < physical_line {
+                        segments.push(LineDirectiveSegment {
+                            physical_start_line,
+                            physical_end_line: physical_line,
+                            virtual_start_line: prior.line,
+                            file: prior.file,
+                        });
+                    }
+                }
+                open_segment = Some((physical_line + 1, directive));
+            }
+        }
+
+        if let Some((physical_start_line, directive)) = open_segment {
+            if physical_start_line <= line_count {
+                segments.push(LineDirectiveSegment {
+                    physical_start_line,
+                    physical_end_line: line_count + 1,
+                    virtual_start_line: directive.line,
+                    file: directive.file,
+                });
+            }
+        }
+
+        if segments.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            source: bytes.to_vec(),
+            line_starts,
+            segments,
+        })
+    }
+
+    fn compute_line_starts(bytes: &[u8]) -> Vec<usize> {
+        if bytes.is_empty() {
+            return vec![0];
+        }
+
+        let mut line_starts = vec![0];
+        line_starts.extend(bytes.iter().enumerate().filter_map(|(idx, byte)| {
+            if *byte == b'\n' && idx + 1 < bytes.len() {
+                Some(idx + 1)
+            } else {
+                None
+            }
+        }));
+        line_starts.push(bytes.len());
+        line_starts
+    }
+
+    fn choose_segment(
+        &self,
+        virtual_line: u64,
+        symbol_hint: Option<&str>,
+    ) -> Option<&LineDirectiveSegment> {
+        let candidates: Vec<_> = self
+            .segments
+            .iter()
+            .filter(|segment| segment.contains_virtual_line(virtual_line))
+            .collect();
+
+        match candidates.as_slice() {
+            [] => None,
+            [segment] => Some(*segment),
+            _ => symbol_hint.and_then(|hint| {
+                candidates.into_iter().find(|segment| {
+                    segment
+                        .physical_line_for(virtual_line)
+                        .is_some_and(|line| self.physical_line_contains(line, hint))
+                })
+            }),
+        }
+    }
+
+    fn physical_line_contains(&self, physical_line: u64, needle: &str) -> bool {
+        if needle.is_empty() {
+            return false;
+        }
+
+        std::str::from_utf8(Self::line_text_from(
+            &self.source,
+            &self.line_starts,
+            physical_line,
+        ))
+        .is_ok_and(|line| line.contains(needle))
+    }
+
+    fn line_text_from<'a>(bytes: &'a [u8], line_starts: &[usize], line: u64) -> &'a [u8] {
+        if line == 0 {
+            return &[];
+        }
+
+        let line_idx = (line - 1) as usize;
+        if line_idx + 1 >= line_starts.len() {
+            return &[];
+        }
+
+        let start = line_starts[line_idx];
+        let mut end = line_starts[line_idx + 1];
+        while end > start && matches!(bytes[end - 1], b'\n' | b'\r') {
+            end -= 1;
+        }
+        &bytes[start..end]
+    }
+}
+
+impl LineRemapper for LineDirectiveMap {
+    fn remap_range(&self, range: GleanRange, symbol_hint: Option<&str>) -> GleanRange {
+        let Some(segment) = self.choose_segment(range.line_begin, symbol_hint) else {
+            return range;
+        };
+        let Some(line_begin) = segment.physical_line_for(range.line_begin) else {
+            return range;
+        };
+        let Some(line_end) = segment.physical_line_for(range.line_end) else {
+            return range;
+        };
+
+        GleanRange {
+            line_begin,
+            line_end,
+            ..range
+        }
+    }
+
+    /// Directives name lines as the indexer reports them, so the range is
+    /// kept as is.
+    fn original_location(
+        &self,
+        range: &GleanRange,
+        symbol_hint: Option<&str>,
+    ) -> Option<(&str, GleanRange)> {
+        let file = self
+            .choose_segment(range.line_begin, symbol_hint)?
+            .file
+            .as_deref()?;
+        Some((file, range.clone()))
+    }
+}
+
+impl LineDirectiveSegment {
+    fn contains_virtual_line(&self, virtual_line: u64) -> bool {
+        virtual_line >= self.virtual_start_line
+            && virtual_line < self.virtual_start_line + self.physical_line_count()
+    }
+
+    fn physical_line_for(&self, virtual_line: u64) -> Option<u64> {
+        if !self.contains_virtual_line(virtual_line) {
+            return None;
+        }
+        Some(self.physical_start_line + virtual_line - self.virtual_start_line)
+    }
+
+    fn physical_line_count(&self) -> u64 {
+        self.physical_end_line - self.physical_start_line
+    }
+}
+
+fn trim_ascii(bytes: &[u8]) -> &[u8] {
+    let mut start = 0;
+    let mut end = bytes.len();
+
+    while start < end && matches!(bytes[start], b' ' | b'\t' | b'\n' | b'\r') {
+        start += 1;
+    }
+    while end > start && matches!(bytes[end - 1], b' ' | b'\t' | b'\n' | b'\r') {
+        end -= 1;
+    }
+
+    &bytes[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directive(line: u64, file: Option<&str>) -> Option<LineDirective> {
+        Some(LineDirective {
+            line,
+            file: file.map(str::to_owned),
+        })
+    }
+
+    #[test]
+    fn test_parse_go_directives() {
+        let parse = |line: &str| GoLineDirectives.parse(line.as_bytes());
+        assert_eq!(
+            parse("//line original.y:3"),
+            directive(3, Some("original.y"))
+        );
+        assert_eq!(parse("//line a.y:3:7"), directive(3, Some("a.y")));
+        assert_eq!(parse("/*line c:\\a.y:9 */"), directive(9, Some("c:\\a.y")));
+        assert_eq!(parse("//line :4"), directive(4, None));
+        assert_eq!(parse("//line a.y:0"), None);
+        assert_eq!(parse("// line a.y:3"), None);
+    }
+
+    #[test]
+    fn test_parse_c_directives() {
+        let parse = |line: &str| CLineDirectives.parse(line.as_bytes());
+        assert_eq!(parse("#line 42"), directive(42, None));
+        assert_eq!(
+            parse("#line 42 \"parser.y\""),
+            directive(42, Some("parser.y"))
+        );
+        assert_eq!(
+            parse("  #  line 7 \"dir/\\\"q\\\".y\""),
+            directive(7, Some("dir/\"q\".y"))
+        );
+        assert_eq!(parse("# 12 \"foo.c\" 1 3"), directive(12, Some("foo.c")));
+        assert_eq!(parse("#line 42 parser.y"), None);
+        assert_eq!(parse("#line 4x"), None);
+        assert_eq!(parse("#include \"line.h\""), None);
+        assert_eq!(parse("#linefeed 3"), None);
+    }
+
+    #[test]
+    fn test_parse_haskell_directives() {
+        let parse = |line: &str| HaskellLineDirectives.parse(line.as_bytes());
+        assert_eq!(
+            parse("{-# LINE 10 \"Lexer.x\" #-}"),
+            directive(10, Some("Lexer.x"))
+        );
+        assert_eq!(parse("{-# line 3 \"A.hs\" #-}"), directive(3, Some("A.hs")));
+        assert_eq!(parse("#line 5 \"B.hs\""), directive(5, Some("B.hs")));
+        assert_eq!(parse("{-# LANGUAGE CPP #-}"), None);
+    }
+
+    #[test]
+    fn test_c_directives_inherit_file() {
+        let source = "#line 10 \"a.y\"\nx\n#line 20\ny\n";
+        let map = LineDirectiveMap::from_source(source.as_bytes(), &CLineDirectives).unwrap();
+        let files: Vec<_> = map.segments.iter().map(|s| s.file.as_deref()).collect();
+        assert_eq!(files, vec![Some("a.y"), Some("a.y")]);
+        assert_eq!(map.segments[1].virtual_start_line, 20);
+        assert_eq!(map.segments[1].physical_start_line, 4);
+    }
+
+    #[test]
+    fn test_remap_c_directives_with_files() {
+        let source = [
+            "/* A Bison parser. */\n",
+            "#line 10 \"parse.y\"\n",
+            "int rule(void) {}\n",
+            "#line 5 \"parse.tab.c\"\n",
+            "static int helper;\n",
+        ]
+        .concat();
+        let map = line_remapper(LanguageId::C, source.as_bytes()).unwrap();
+        let range = |line| GleanRange {
+            line_begin: line,
+            column_begin: 1,
+            line_end: line,
+            column_end: 4,
+        };
+        assert_eq!(map.remap_range(range(10), None), range(3));
+        assert_eq!(
+            map.original_location(&range(10), None),
+            Some(("parse.y", range(10)))
+        );
+        assert_eq!(map.remap_range(range(5), None), range(5));
+        assert_eq!(
+            map.original_location(&range(5), None),
+            Some(("parse.tab.c", range(5)))
+        );
+        assert_eq!(map.remap_range(range(1), None), range(1));
+        assert_eq!(map.original_location(&range(1), None), None);
+    }
+
+    #[test]
+    fn test_no_remapper_without_directives() {
+        assert!(line_remapper(LanguageId::C, b"int x;\n").is_none());
+        assert!(line_remapper(LanguageId::Python, b"#line 3 \"a.y\"\nx\n").is_none());
+        assert!(line_remapper(LanguageId::Cpp, b"#line 3 \"a.y\"\nx\n").is_some());
+    }
+}
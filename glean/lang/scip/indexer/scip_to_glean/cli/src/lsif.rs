@@ -10,7 +10,7 @@
 
 use scip_symbol::DescriptorKind;
 
-#[allow(unused)]
+#[allow(unused, clippy::enum_variant_names)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum SymbolKind {
     SkFile,
@@ -42,6 +42,45 @@ pub enum SymbolKind {
     SkUnknown,
 }
 impl SymbolKind {
+    /// Every variant, in discriminant order.
+    pub const ALL: [SymbolKind; 27] = {
+        use SymbolKind::*;
+        [
+            SkFile,
+            SkModule,
+            SkNamespace,
+            SkPackage,
+            SkClass,
+            SkMethod,
+            SkProperty,
+            SkField,
+            SkConstructor,
+            SkEnum,
+            SkInterface,
+            SkFunction,
+            SkVariable,
+            SkConstant,
+            SkString,
+            SkNumber,
+            SkBoolean,
+            SkArray,
+            SkObject,
+            SkKey,
+            SkNull,
+            SkEnumMember,
+            SkStruct,
+            SkEvent,
+            SkOperator,
+            SkTypeParameter,
+            SkUnknown,
+        ]
+    };
+
+    /// Inverse of `kind as u8`, as stored in `scip.SymbolKind` facts.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Self::ALL.get(value as usize).copied()
+    }
+
     pub fn new(value: DescriptorKind) -> Self {
         use DescriptorKind::*;
         use SymbolKind::*;
@@ -59,7 +98,7 @@ impl SymbolKind {
 }
 
 // https://www.internalfb.com/code/fbsource/[8d8905791b99]/fbcode/glean/lang/lsif/Data/LSIF/Gen.hs?lines=318
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub enum LanguageId {
     ABAP,            // "abap"
     WindowsBat,      // "bat"
@@ -189,6 +228,144 @@ impl LanguageId {
         }
     }
 
+    /// Every variant, in discriminant order.
+    pub const ALL: [LanguageId; 59] = {
+        use LanguageId::*;
+        [
+            ABAP,
+            WindowsBat,
+            BibTeX,
+            Clojure,
+            Coffeescript,
+            C,
+            Cpp,
+            CSharp,
+            CSS,
+            Diff,
+            Dart,
+            Dockerfile,
+            Elixir,
+            Erlang,
+            FSharp,
+            Git,
+            Go,
+            Groovy,
+            Handlebars,
+            Haskell,
+            HTML,
+            Ini,
+            Java,
+            JavaScript,
+            JavaScriptReact,
+            JSON,
+            LaTeX,
+            Less,
+            Lua,
+            Makefile,
+            Markdown,
+            ObjectiveC,
+            ObjectiveCpp,
+            Perl,
+            Perl6,
+            PHP,
+            Powershell,
+            Pug,
+            Python,
+            R,
+            Razor,
+            Ruby,
+            Rust,
+            SCSS,
+            Scala,
+            ShaderLab,
+            Shell,
+            SQL,
+            Swift,
+            TypeScript,
+            TypeScriptReact,
+            TeX,
+            VisualBasic,
+            XML,
+            XSL,
+            YAML,
+            UnknownLanguage,
+            Kotlin,
+            OCaml,
+        ]
+    };
+
+    /// Inverse of `lang as u8`, as stored in `scip.FileLanguage` facts.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Self::ALL.get(value as usize).copied()
+    }
+
+    /// The SCIP `Document.language` name that `new` maps to this variant,
+    /// or `""` for `UnknownLanguage`.
+    pub fn name(self) -> &'static str {
+        use LanguageId::*;
+        match self {
+            ABAP => "abap",
+            WindowsBat => "bat",
+            BibTeX => "bibtex",
+            Clojure => "clojure",
+            Coffeescript => "coffeescript",
+            C => "c",
+            Cpp => "cpp",
+            CSharp => "csharp",
+            CSS => "css",
+            Diff => "diff",
+            Dart => "dart",
+            Dockerfile => "dockerfile",
+            Elixir => "elixir",
+            Erlang => "erlang",
+            FSharp => "fsharp",
+            Git => "git-commit",
+            Go => "go",
+            Groovy => "groovy",
+            Handlebars => "handlebars",
+            Haskell => "haskell",
+            HTML => "html",
+            Ini => "ini",
+            Java => "java",
+            JavaScript => "javascript",
+            JavaScriptReact => "javascriptreact",
+            JSON => "json",
+            LaTeX => "latex",
+            Less => "less",
+            Lua => "lua",
+            Makefile => "makefile",
+            Markdown => "markdown",
+            ObjectiveC => "objective-c",
+            ObjectiveCpp => "objective-cpp",
+            Perl => "perl",
+            Perl6 => "perl6",
+            PHP => "php",
+            Powershell => "powershell",
+            Pug => "jade",
+            Python => "python",
+            R => "r",
+            Razor => "razor",
+            Ruby => "ruby",
+            Rust => "rust",
+            SCSS => "scss",
+            Scala => "scala",
+            ShaderLab => "shaderlab",
+            Shell => "shellscript",
+            SQL => "sql",
+            Swift => "swift",
+            TypeScript => "typescript",
+            TypeScriptReact => "typescriptreact",
+            TeX => "tex",
+            VisualBasic => "vb",
+            XML => "xml",
+            XSL => "xsl",
+            YAML => "yaml",
+            Kotlin => "kotlin",
+            OCaml => "ocaml",
+            UnknownLanguage => "",
+        }
+    }
+
     pub fn known(self) -> Option<Self> {
         match self {
             LanguageId::UnknownLanguage => None,
@@ -0,0 +1,148 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Shard manifest written next to sharded JSON output.
+//!
+//! The manifest records what each shard file contains so upload tooling can
+//! verify that every shard arrived intact and retry individual shards.
+
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::output::GleanJSONOutput;
+use crate::output::OutputFormat;
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct ShardManifest {
+    pub total_facts: usize,
+    pub total_bytes: u64,
+    pub shards: Vec<ShardEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct ShardEntry {
+    /// File name of the shard, relative to the manifest's directory.
+    pub file: String,
+    /// Fact count per versioned predicate name, e.g. `scip.Symbol.1`.
+    pub facts: BTreeMap<String, usize>,
+    pub bytes: u64,
+    /// Lowercase hex SHA-256 of the shard file contents.
+    pub sha256: String,
+    /// Source files (`src.File` keys) whose facts appear in this shard.
+    pub source_files: Vec<String>,
+}
+
+impl ShardManifest {
+    pub fn new(mut shards: Vec<ShardEntry>) -> Self {
+        shards.sort_by(|a, b| a.file.cmp(&b.file));
+        Self {
+            total_facts: shards.iter().flat_map(|s| s.facts.values()).sum(),
+            total_bytes: shards.iter().map(|s| s.bytes).sum(),
+            shards,
+        }
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(MANIFEST_FILE_NAME);
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .with_context(|| format!("Error creating manifest file {}", path.display()))?;
+        let mut w = std::io::BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut w, self)?;
+        w.write_all(b"\n")?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+/// Forwards writes to `inner` while hashing and counting the bytes, so the
+/// shard checksum is computed without reading the file back.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Write `output` to `file` in `format` and describe what was written.
+pub fn write_output_file(
+    file: &Path,
+    output: GleanJSONOutput,
+    format: OutputFormat,
+) -> Result<ShardEntry> {
+    let facts = output
+        .predicate_counts()
+        .into_iter()
+        .map(|(predicate, count)| (predicate.to_owned(), count))
+        .collect();
+    let source_files = output
+        .source_files()
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+
+    let write = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(file)
+        .with_context(|| format!("Error creating output file {}", file.display()))?;
+    let mut writer = HashingWriter {
+        inner: std::io::BufWriter::new(write),
+        hasher: Sha256::new(),
+        bytes: 0,
+    };
+    output
+        .write_as(format, &mut writer)
+        .with_context(|| format!("Error writing output file {}", file.display()))?;
+
+    let sha256 = writer
+        .hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Ok(ShardEntry {
+        file: file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        facts,
+        bytes: writer.bytes,
+        sha256,
+        source_files,
+    })
+}
@@ -0,0 +1,389 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! `scip-to-glean merge`: combine several SCIP indexes into one SCIP file,
+//! for consumers other than Glean.
+//!
+//! Same-path documents are concatenated: their language and text come from
+//! the first document that sets them, occurrences are additive and symbol
+//! information is merged per symbol. Documents with different position
+//! encodings cannot be merged, their columns would not agree. Like the
+//! converter, local symbols of same-path documents share one namespace.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Document;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Index;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Metadata;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::SymbolInformation;
+use protobuf::Message;
+use protobuf::MessageField;
+use tracing::info;
+use tracing::warn;
+
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Document;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Index;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Metadata;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::SymbolInformation;
+use crate::read_scip_file;
+
+#[derive(clap::Args, Debug)]
+pub struct MergeArgs {
+    #[arg(short, long, required = true)]
+    pub input: Vec<PathBuf>,
+
+    #[arg(short, long, help = "Path of the merged SCIP index")]
+    pub output: PathBuf,
+}
+
+pub fn merge(args: &MergeArgs) -> Result<()> {
+    let indexes = args
+        .input
+        .iter()
+        .map(|input| {
+            read_scip_file(input)
+                .with_context(|| format!("Error opening input file {}", input.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let merged = merge_indexes(indexes)?;
+    info!(
+        "Merged {} inputs into {} documents and {} external symbols",
+        args.input.len(),
+        merged.documents.len(),
+        merged.external_symbols.len()
+    );
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&args.output)
+        .with_context(|| format!("Error creating output file {}", args.output.display()))?;
+    let mut w = std::io::BufWriter::new(file);
+    merged
+        .write_to_writer(&mut w)
+        .with_context(|| format!("Error writing output file {}", args.output.display()))?;
+    w.flush()?;
+    Ok(())
+}
+
+pub fn merge_indexes(indexes: Vec<Index>) -> Result<Index> {
+    let metadata: Vec<Option<&Metadata>> = indexes.iter().map(|i| i.metadata.as_ref()).collect();
+    let (metadata, path_prefixes) = reconcile_metadata(&metadata)?;
+
+    let mut merged = Index::new();
+    merged.metadata = metadata.map_or_else(MessageField::none, MessageField::some);
+    let mut documents: HashMap<String, usize> = HashMap::new();
+    let mut external_symbols: HashMap<String, usize> = HashMap::new();
+    for (index, prefix) in indexes.into_iter().zip(path_prefixes) {
+        for mut doc in index.documents {
+            doc.relative_path.insert_str(0, &prefix);
+            match documents.entry(doc.relative_path.clone()) {
+                Entry::Occupied(entry) => merge_document(&mut merged.documents[*entry.get()], doc)?,
+                Entry::Vacant(entry) => {
+                    entry.insert(merged.documents.len());
+                    merged.documents.push(doc);
+                }
+            }
+        }
+        for info in index.external_symbols {
+            match external_symbols.entry(info.symbol.clone()) {
+                Entry::Occupied(entry) => {
+                    merge_symbol_information(&mut merged.external_symbols[*entry.get()], info)
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(merged.external_symbols.len());
+                    merged.external_symbols.push(info);
+                }
+            }
+        }
+    }
+    Ok(merged)
+}
+
+/// Combine the inputs' metadata. Returns the merged metadata, if any input
+/// had some, and the prefix to prepend to each input's document paths so
+/// they are relative to the merged `project_root`.
+fn reconcile_metadata(inputs: &[Option<&Metadata>]) -> Result<(Option<Metadata>, Vec<String>)> {
+    let mut prefixes = vec![String::new(); inputs.len()];
+    let present: Vec<&Metadata> = inputs.iter().flatten().copied().collect();
+    let Some(first) = present.first() else {
+        return Ok((None, prefixes));
+    };
+    let mut merged = (*first).clone();
+
+    for metadata in &present[1..] {
+        if metadata.version.value() > merged.version.value() {
+            merged.version = metadata.version;
+        }
+        if merged.tool_info.is_none() {
+            merged.tool_info = metadata.tool_info.clone();
+        } else if metadata.tool_info.is_some() && metadata.tool_info != merged.tool_info {
+            warn!(
+                "Inputs were produced by different tools, keeping tool_info of {} {}",
+                merged.tool_info.name, merged.tool_info.version
+            );
+        }
+    }
+
+    // Ranges are only meaningful in the encoding they were produced in, so
+    // conflicting encodings cannot be reconciled.
+    let mut encodings = present
+        .iter()
+        .map(|m| m.text_document_encoding)
+        .filter(|encoding| encoding.value() != 0);
+    if let Some(encoding) = encodings.next() {
+        if let Some(other) = encodings.find(|other| *other != encoding) {
+            bail!(
+                "Cannot merge indexes with different text_document_encoding ({:?} and {:?})",
+                encoding,
+                other
+            );
+        }
+        merged.text_document_encoding = encoding;
+    }
+
+    let roots: Vec<&str> = inputs
+        .iter()
+        .map(|m| m.map_or("", |m| m.project_root.as_str()))
+        .collect();
+    if roots.iter().any(|root| *root != roots[0]) {
+        if roots.iter().any(|root| root.is_empty()) {
+            warn!(
+                "Some inputs have no project_root, keeping document paths unchanged and project_root {}",
+                merged.project_root
+            );
+        } else {
+            let (root, root_prefixes) = common_project_root(&roots);
+            info!(
+                "Inputs have different project roots, merging under {}",
+                root
+            );
+            merged.project_root = root;
+            prefixes = root_prefixes;
+        }
+    }
+    Ok((Some(merged), prefixes))
+}
+
+/// The deepest URI that contains every root, and each root's path below it
+/// (with a trailing `/`, or empty).
+fn common_project_root(roots: &[&str]) -> (String, Vec<String>) {
+    let components: Vec<Vec<&str>> = roots
+        .iter()
+        .map(|root| root.trim_end_matches('/').split('/').collect())
+        .collect();
+    let mut common = components[0].len();
+    for other in &components[1..] {
+        common = common.min(
+            components[0]
+                .iter()
+                .zip(other)
+                .take_while(|(a, b)| a == b)
+                .count(),
+        );
+    }
+    let root = format!("{}/", components[0][..common].join("/"));
+    let prefixes = components
+        .iter()
+        .map(|c| {
+            c[common..]
+                .iter()
+                .map(|component| format!("{}/", component))
+                .collect()
+        })
+        .collect();
+    (root, prefixes)
+}
+
+/// Add a later same-path `doc` to `into`. Its language, text and position
+/// encoding only fill in those `into` leaves unset, and its symbol
+/// information is merged into that of the same symbols.
+fn merge_document(into: &mut Document, doc: Document) -> Result<()> {
+    match (
+        into.position_encoding.value(),
+        doc.position_encoding.value(),
+    ) {
+        (_, 0) => {}
+        (0, _) => into.position_encoding = doc.position_encoding,
+        (encoding, other) if encoding != other => bail!(
+            "Cannot merge documents {} with different position_encoding ({:?} and {:?})",
+            into.relative_path,
+            into.position_encoding,
+            doc.position_encoding
+        ),
+        _ => {}
+    }
+    if into.language.is_empty() {
+        into.language = doc.language;
+    }
+    if into.text.is_empty() {
+        into.text = doc.text;
+    }
+    into.occurrences.extend(doc.occurrences);
+    let mut symbols: HashMap<String, usize> = into
+        .symbols
+        .iter()
+        .enumerate()
+        .map(|(i, info)| (info.symbol.clone(), i))
+        .collect();
+    for info in doc.symbols {
+        match symbols.entry(info.symbol.clone()) {
+            Entry::Occupied(entry) => {
+                merge_symbol_information(&mut into.symbols[*entry.get()], info)
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(into.symbols.len());
+                into.symbols.push(info);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Merge two descriptions of the same external symbol: scalar fields come
+/// from the first input that sets them, list entries are unioned.
+pub fn merge_symbol_information(into: &mut SymbolInformation, info: SymbolInformation) {
+    for doc in info.documentation {
+        if !into.documentation.contains(&doc) {
+            into.documentation.push(doc);
+        }
+    }
+    for rel in info.relationships {
+        if !into.relationships.contains(&rel) {
+            into.relationships.push(rel);
+        }
+    }
+    if into.kind.value() == 0 {
+        into.kind = info.kind;
+    }
+    if into.display_name.is_empty() {
+        into.display_name = info.display_name;
+    }
+    if into.signature_documentation.is_none() {
+        into.signature_documentation = info.signature_documentation;
+    }
+    if into.enclosing_symbol.is_empty() {
+        into.enclosing_symbol = info.enclosing_symbol;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "facebook")]
+    use proto_rust::scip::PositionEncoding;
+
+    use super::*;
+    #[cfg(not(feature = "facebook"))]
+    use crate::proto::scip::PositionEncoding;
+
+    fn document(language: &str, text: &str, encoding: PositionEncoding) -> Document {
+        let mut doc = Document::new();
+        doc.relative_path = "a.go".to_string();
+        doc.language = language.to_string();
+        doc.text = text.to_string();
+        doc.position_encoding = encoding.into();
+        doc
+    }
+
+    #[test]
+    fn test_merge_document_keeps_first_non_empty_fields() {
+        let index = |doc| {
+            let mut index = Index::new();
+            index.documents.push(doc);
+            index
+        };
+        let with_symbol = |mut doc: Document, documentation: &str| {
+            let mut info = SymbolInformation::new();
+            info.symbol = "local 0".to_string();
+            info.documentation.push(documentation.to_string());
+            doc.symbols.push(info);
+            doc
+        };
+        let merged = merge_indexes(vec![
+            index(document(
+                "",
+                "",
+                PositionEncoding::UnspecifiedPositionEncoding,
+            )),
+            index(with_symbol(
+                document(
+                    "go",
+                    "package a\n",
+                    PositionEncoding::UTF16CodeUnitOffsetFromLineStart,
+                ),
+                "first",
+            )),
+            index(with_symbol(
+                document(
+                    "python",
+                    "import a\n",
+                    PositionEncoding::UTF16CodeUnitOffsetFromLineStart,
+                ),
+                "second",
+            )),
+        ])
+        .unwrap();
+        assert_eq!(merged.documents.len(), 1);
+        let doc = &merged.documents[0];
+        assert_eq!(doc.language, "go");
+        assert_eq!(doc.text, "package a\n");
+        assert_eq!(
+            doc.position_encoding.enum_value(),
+            Ok(PositionEncoding::UTF16CodeUnitOffsetFromLineStart)
+        );
+        assert_eq!(doc.symbols.len(), 1);
+        assert_eq!(doc.symbols[0].documentation, vec!["first", "second"]);
+
+        let err = merge_indexes(vec![
+            index(document(
+                "go",
+                "",
+                PositionEncoding::UTF16CodeUnitOffsetFromLineStart,
+            )),
+            index(document(
+                "go",
+                "",
+                PositionEncoding::UTF8CodeUnitOffsetFromLineStart,
+            )),
+        ])
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("different position_encoding"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_common_project_root() {
+        let (root, prefixes) =
+            common_project_root(&["file:///repo/a/", "file:///repo/b/c", "file:///repo/"]);
+        assert_eq!(root, "file:///repo/");
+        assert_eq!(prefixes, vec!["a/", "b/c/", ""]);
+
+        let (root, prefixes) = common_project_root(&["file:///x", "file:///y"]);
+        assert_eq!(root, "file:///");
+        assert_eq!(prefixes, vec!["x/", "y/"]);
+    }
+}
@@ -6,6 +6,8 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
@@ -26,6 +28,11 @@ struct IdKey<T> {
 struct Key<T> {
     key: T,
 }
+#[derive(Serialize, Clone, Eq, PartialEq, Hash)]
+struct KeyValue<K, V> {
+    key: K,
+    value: V,
+}
 
 #[derive(Serialize, Clone, Eq, PartialEq, Hash)]
 struct FileLang {
@@ -40,7 +47,13 @@ struct FileRange {
 #[derive(Serialize, Clone, Eq, PartialEq, Hash)]
 struct EnclosingRange {
     range: ScipId,
-    enclosingRange: ScipId,
+    #[serde(rename = "enclosingRange")]
+    enclosing_range: ScipId,
+}
+#[derive(Serialize, Clone, Eq, PartialEq, Hash)]
+struct GeneratedRange {
+    range: ScipId,
+    generated: ScipId,
 }
 #[derive(Serialize, Clone, Eq, PartialEq, Hash)]
 struct SymbolLocation {
@@ -100,6 +113,8 @@ enum Node {
     IsImplementation(Key<IsImplementation>),
     EnclosingSymbol(Key<EnclosingSymbol>),
     FileLanguage(IdKey<FileLang>),
+    FileDigest(KeyValue<ScipId, Box<str>>),
+    FileContent(KeyValue<ScipId, Box<str>>),
     SymbolKind(Key<SymbolAndKind>),
     Definition(Key<SymbolLocation>),
     Reference(Key<SymbolLocation>),
@@ -107,6 +122,7 @@ enum Node {
     File(IdKey<Box<str>>),
     FileRange(IdKey<FileRange>),
     EnclosingRange(IdKey<EnclosingRange>),
+    GeneratedRange(Key<GeneratedRange>),
     LocalName(IdKey<Box<str>>),
     Symbol(IdKey<Box<str>>),
     Documentation(IdKey<Box<str>>),
@@ -120,6 +136,7 @@ pub struct GleanJSONOutput {
     symbol_documentation: Vec<IdKey<SymbolDocs>>,
     file_ranges: Vec<IdKey<FileRange>>,
     enclosing_ranges: Vec<IdKey<EnclosingRange>>,
+    generated_ranges: Vec<Key<GeneratedRange>>,
     symbols: Vec<IdKey<Box<str>>>,
     definitions: Vec<Key<SymbolLocation>>,
     references: Vec<Key<SymbolLocation>>,
@@ -132,6 +149,8 @@ pub struct GleanJSONOutput {
     display_names: Vec<IdKey<Box<str>>>,
     display_name_symbols: Vec<Key<DisplayNameSymbol>>,
     file_lines: Vec<Key<FileLines>>,
+    file_digests: Vec<KeyValue<ScipId, Box<str>>>,
+    file_contents: Vec<KeyValue<ScipId, Box<str>>>,
 }
 
 impl<I> From<I> for GleanJSONOutput
@@ -146,9 +165,12 @@ where
                 Node::IsImplementation(node) => output.is_implementation.push(node),
                 Node::EnclosingSymbol(node) => output.enclosing_symbols.push(node),
                 Node::FileLanguage(node) => output.file_langs.push(node),
+                Node::FileDigest(node) => output.file_digests.push(node),
+                Node::FileContent(node) => output.file_contents.push(node),
                 Node::File(node) => output.src_files.push(node),
                 Node::FileRange(node) => output.file_ranges.push(node),
                 Node::EnclosingRange(node) => output.enclosing_ranges.push(node),
+                Node::GeneratedRange(node) => output.generated_ranges.push(node),
                 Node::SymbolKind(node) => output.symbol_kinds.push(node),
                 Node::Definition(node) => output.definitions.push(node),
                 Node::Reference(node) => output.references.push(node),
@@ -210,10 +232,18 @@ impl GleanJSONOutput {
             id,
             key: EnclosingRange {
                 range,
-                enclosingRange: enclosing_range,
+                enclosing_range,
             },
         });
     }
+    /// `range`, in the file a line directive names, was emitted at
+    /// `generated`.
+    pub fn generated_range(&mut self, range: ScipId, generated: ScipId) {
+        self.generated_ranges.push(Key {
+            key: GeneratedRange { range, generated },
+        });
+    }
+
     pub fn symbol(&mut self, symbol_id: ScipId, symbol: Box<str>) {
         self.symbols.push(IdKey {
             id: symbol_id,
@@ -314,6 +344,21 @@ impl GleanJSONOutput {
         })
     }
 
+    /// The SHA-256 digest of the file's contents, in hex.
+    pub fn file_digest(&mut self, file_id: ScipId, digest: Box<str>) {
+        self.file_digests.push(KeyValue {
+            key: file_id,
+            value: digest,
+        })
+    }
+
+    pub fn file_content(&mut self, file_id: ScipId, content: Box<str>) {
+        self.file_contents.push(KeyValue {
+            key: file_id,
+            value: content,
+        })
+    }
+
     pub fn total_facts_count(&self) -> usize {
         self.src_files.len()
             + self.file_langs.len()
@@ -321,6 +366,7 @@ impl GleanJSONOutput {
             + self.symbol_documentation.len()
             + self.file_ranges.len()
             + self.enclosing_ranges.len()
+            + self.generated_ranges.len()
             + self.symbols.len()
             + self.definitions.len()
             + self.references.len()
@@ -333,6 +379,116 @@ impl GleanJSONOutput {
             + self.display_names.len()
             + self.display_name_symbols.len()
             + self.file_lines.len()
+            + self.file_digests.len()
+            + self.file_contents.len()
+    }
+
+    /// Number of facts per predicate, keyed by the versioned predicate name
+    /// used in the JSON output (e.g. `src.File.1`). Predicates without facts
+    /// are omitted.
+    pub fn predicate_counts(&self) -> BTreeMap<&'static str, usize> {
+        [
+            ("src.File.1", self.src_files.len()),
+            ("src.FileLines.1", self.file_lines.len()),
+            ("src.FileDigest.1", self.file_digests.len()),
+            ("src.FileContent.1", self.file_contents.len()),
+            ("scip.Symbol.1", self.symbols.len()),
+            ("scip.LocalName.1", self.local_names.len()),
+            ("scip.Documentation.1", self.documentation.len()),
+            ("scip.FileLanguage.1", self.file_langs.len()),
+            ("scip.FileRange.1", self.file_ranges.len()),
+            ("scip.EnclosingRange.1", self.enclosing_ranges.len()),
+            ("scip.GeneratedRange.1", self.generated_ranges.len()),
+            ("scip.Definition.1", self.definitions.len()),
+            ("scip.Reference.1", self.references.len()),
+            (
+                "scip.SymbolDocumentation.1",
+                self.symbol_documentation.len(),
+            ),
+            ("scip.SymbolName.1", self.symbol_names.len()),
+            ("scip.IsImplementation.1", self.is_implementation.len()),
+            ("scip.EnclosingSymbol.1", self.enclosing_symbols.len()),
+            ("scip.SymbolKind.1", self.symbol_kinds.len()),
+            ("scip.Metadata.1", self.metadata.len()),
+            ("scip.DisplayName.1", self.display_names.len()),
+            ("scip.DisplayNameSymbol.1", self.display_name_symbols.len()),
+        ]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .collect()
+    }
+
+    /// Paths of the `src.File` facts in this output, sorted. For a shard,
+    /// these are the source files whose facts it (at least partially) holds.
+    pub fn source_files(&self) -> Vec<&str> {
+        let mut files: Vec<&str> = self.src_files.iter().map(|f| &*f.key).collect();
+        files.sort_unstable();
+        files
+    }
+
+    /// Resolve fact ids into an id-free view of the symbols and their
+    /// locations, so two conversions can be compared.
+    pub fn resolve(&self) -> ResolvedFacts {
+        let by_id = |facts: &[IdKey<Box<str>>]| -> HashMap<ScipId, Box<str>> {
+            facts.iter().map(|f| (f.id, f.key.clone())).collect()
+        };
+        let files = by_id(&self.src_files);
+        let symbols = by_id(&self.symbols);
+        let documentation = by_id(&self.documentation);
+        let display_names = by_id(&self.display_names);
+        let file_ranges: HashMap<ScipId, &FileRange> =
+            self.file_ranges.iter().map(|f| (f.id, &f.key)).collect();
+
+        let mut resolved = ResolvedFacts::default();
+        for path in files.values() {
+            resolved.files.entry(path.clone()).or_default();
+        }
+        for symbol in symbols.values() {
+            resolved.symbols.entry(symbol.clone()).or_default();
+        }
+
+        let mut locate = |locations: &[Key<SymbolLocation>], is_definition: bool| {
+            for loc in locations {
+                let file_range = file_ranges[&loc.key.location];
+                let file = resolved
+                    .files
+                    .entry(files[&file_range.file].clone())
+                    .or_default();
+                let location = Location {
+                    symbol: symbols[&loc.key.symbol].clone(),
+                    range: file_range.range.clone(),
+                };
+                if is_definition {
+                    file.definitions.insert(location);
+                } else {
+                    file.references.insert(location);
+                }
+            }
+        };
+        locate(&self.definitions, true);
+        locate(&self.references, false);
+
+        for fact in &self.symbol_kinds {
+            if let Some(kind) = SymbolKind::from_u8(fact.key.kind) {
+                let symbol = resolved.symbols.entry(symbols[&fact.key.symbol].clone());
+                symbol.or_default().kinds.insert(format!("{:?}", kind));
+            }
+        }
+        for fact in &self.display_name_symbols {
+            let symbol = resolved.symbols.entry(symbols[&fact.key.symbol].clone());
+            symbol
+                .or_default()
+                .display_names
+                .insert(display_names[&fact.key.display_name].clone());
+        }
+        for fact in &self.symbol_documentation {
+            let symbol = resolved.symbols.entry(symbols[&fact.key.symbol].clone());
+            symbol
+                .or_default()
+                .documentation
+                .insert(documentation[&fact.key.docs].clone());
+        }
+        resolved
     }
 
     /// Consumes self, returns a list of GleanJSONOutput shards that are approximately of size `shard_size`
@@ -365,6 +521,12 @@ impl GleanJSONOutput {
             .iter()
             .map(|x| (x.id, x))
             .collect::<HashMap<_, _>>();
+        // File contents go to the shard of the first of their file's ranges.
+        let mut file_contents = self
+            .file_contents
+            .into_iter()
+            .map(|x| (x.key, x))
+            .collect::<HashMap<_, _>>();
 
         let mut source_nodes: Vec<Node> = Vec::new();
         source_nodes.extend(self.symbol_names.into_iter().map(Node::SymbolName));
@@ -379,10 +541,12 @@ impl GleanJSONOutput {
                 .map(Node::EnclosingSymbol),
         );
         source_nodes.extend(self.file_langs.into_iter().map(Node::FileLanguage));
+        source_nodes.extend(self.file_digests.into_iter().map(Node::FileDigest));
         source_nodes.extend(self.symbol_kinds.into_iter().map(Node::SymbolKind));
         source_nodes.extend(self.definitions.into_iter().map(Node::Definition));
         source_nodes.extend(self.references.into_iter().map(Node::Reference));
         source_nodes.extend(self.enclosing_ranges.into_iter().map(Node::EnclosingRange));
+        source_nodes.extend(self.generated_ranges.into_iter().map(Node::GeneratedRange));
         source_nodes.extend(
             self.symbol_documentation
                 .into_iter()
@@ -429,20 +593,38 @@ impl GleanJSONOutput {
                             let file = *files.get(&file_language.key.file).unwrap();
                             to_visit.push(Node::File(file.clone()));
                         }
+                        Node::FileDigest(file_digest) => {
+                            let file = *files.get(&file_digest.key).unwrap();
+                            to_visit.push(Node::File(file.clone()));
+                        }
+                        Node::FileContent(file_content) => {
+                            let file = *files.get(&file_content.key).unwrap();
+                            to_visit.push(Node::File(file.clone()));
+                        }
                         Node::FileRange(file_range) => {
                             let file = *files.get(&file_range.key.file).unwrap();
                             to_visit.push(Node::File(file.clone()));
+                            if let Some(content) = file_contents.remove(&file_range.key.file) {
+                                to_visit.push(Node::FileContent(content));
+                            }
                         }
                         Node::EnclosingRange(enclosing_range) => {
                             let EnclosingRange {
                                 range,
-                                enclosingRange: enclosing_range,
+                                enclosing_range,
                             } = &enclosing_range.key;
                             let range_idkey = *file_ranges.get(range).unwrap();
                             let enclosing_range_idkey = *file_ranges.get(enclosing_range).unwrap();
                             to_visit.push(Node::FileRange(range_idkey.clone()));
                             to_visit.push(Node::FileRange(enclosing_range_idkey.clone()));
                         }
+                        Node::GeneratedRange(generated_range) => {
+                            let GeneratedRange { range, generated } = &generated_range.key;
+                            let range_idkey = *file_ranges.get(range).unwrap();
+                            let generated_idkey = *file_ranges.get(generated).unwrap();
+                            to_visit.push(Node::FileRange(range_idkey.clone()));
+                            to_visit.push(Node::FileRange(generated_idkey.clone()));
+                        }
                         Node::SymbolKind(symbol_kind) => {
                             let symbol = *symbols.get(&symbol_kind.key.symbol).unwrap();
                             to_visit.push(Node::Symbol(symbol.clone()));
@@ -470,77 +652,101 @@ impl GleanJSONOutput {
             }
         }
 
+        // Contents of files without ranges, with their file.
+        for (file_id, content) in file_contents {
+            if current_graph.len() >= shard_size {
+                shards.push(current_graph.into());
+
+                current_graph = HashSet::new();
+            }
+            let file = *files.get(&file_id).unwrap();
+            current_graph.insert(Node::File(file.clone()));
+            current_graph.insert(Node::FileContent(content));
+        }
+
         shards.push(current_graph.into());
 
         shards
     }
 
+    /// Hands each predicate's facts to `sink`, in dependency order.
+    fn emit(self, sink: &mut impl PredicateSink) -> std::io::Result<()> {
+        // Match the ordering in scipDependencyOrder
+        sink.predicate("src.File", self.src_files)?;
+        sink.predicate("src.FileLines", self.file_lines)?;
+        sink.predicate("src.FileDigest", self.file_digests)?;
+        sink.predicate("src.FileContent", self.file_contents)?;
+        sink.predicate("scip.Symbol", self.symbols)?;
+        sink.predicate("scip.LocalName", self.local_names)?;
+        sink.predicate("scip.Documentation", self.documentation)?;
+        sink.predicate("scip.FileLanguage", self.file_langs)?;
+        sink.predicate("scip.FileRange", self.file_ranges)?;
+        sink.predicate("scip.EnclosingRange", self.enclosing_ranges)?;
+        sink.predicate("scip.GeneratedRange", self.generated_ranges)?;
+        sink.predicate("scip.Definition", self.definitions)?;
+        sink.predicate("scip.Reference", self.references)?;
+        sink.predicate("scip.SymbolDocumentation", self.symbol_documentation)?;
+        sink.predicate("scip.SymbolName", self.symbol_names)?;
+        sink.predicate("scip.IsImplementation", self.is_implementation)?;
+        sink.predicate("scip.EnclosingSymbol", self.enclosing_symbols)?;
+        sink.predicate("scip.SymbolKind", self.symbol_kinds)?;
+        sink.predicate("scip.Metadata", self.metadata)?;
+        sink.predicate("scip.DisplayName", self.display_names)?;
+        sink.predicate("scip.DisplayNameSymbol", self.display_name_symbols)?;
+        Ok(())
+    }
+
+    pub fn write_as(self, format: OutputFormat, w: impl std::io::Write) -> std::io::Result<()> {
+        match format {
+            OutputFormat::Json => self.write(w),
+            OutputFormat::Ndjson => self.write_ndjson(w),
+        }
+    }
+
     pub fn write(self, mut w: impl std::io::Write) -> std::io::Result<()> {
-        fn sub(
-            mut w: impl std::io::Write,
-            name: &str,
-            mut items: Vec<impl Serialize>,
-            is_first_line: &mut bool,
-        ) -> std::io::Result<()> {
-            if items.is_empty() {
-                return Ok(());
-            }
+        struct JsonArraySink<W> {
+            w: W,
+            // Track whether we're on the first line of the JSON output
+            // so we can add a trailing comma to the previous line
+            is_first_line: bool,
+        }
 
-            // Reverse item list to match behavior of Haskell code, which puts the last entries first
-            items.reverse();
+        impl<W: std::io::Write> PredicateSink for JsonArraySink<W> {
+            fn predicate(
+                &mut self,
+                name: &str,
+                mut items: Vec<impl Serialize>,
+            ) -> std::io::Result<()> {
+                if items.is_empty() {
+                    return Ok(());
+                }
+
+                // Reverse item list to match behavior of Haskell code, which puts the last entries first
+                items.reverse();
 
-            // Chunk items into groups of 10k to match behavior of Haskell code.
-            for chunk in items.chunks(10000) {
-                // If this isn't the first line, include the trailing comma for the previous line
-                if !*is_first_line {
-                    w.write_all(b",\n")?;
+                // Chunk items into groups of 10k to match behavior of Haskell code.
+                for chunk in items.chunks(10000) {
+                    // If this isn't the first line, include the trailing comma for the previous line
+                    if !self.is_first_line {
+                        self.w.write_all(b",\n")?;
+                    }
+
+                    self.w.write_all(br#"{"facts":"#)?;
+                    serde_json::to_writer(&mut self.w, &chunk)?;
+                    write!(self.w, r#","predicate":"{}.1"}}"#, name)?;
+                    self.is_first_line = false;
                 }
 
-                w.write_all(br#"{"facts":"#)?;
-                serde_json::to_writer(&mut w, &chunk)?;
-                write!(w, r#","predicate":"{}.1"}}"#, name)?;
-                *is_first_line = false;
+                Ok(())
             }
-
-            Ok(())
         }
 
-        // Track whether we're on the first line of the JSON output
-        // so we can add a trailing comma to the previous line
-        // This will be passed by mutable reference to sub()
-        let mut is_first_line = true;
-        let ifl = &mut is_first_line;
-
         w.write_all(b"[")?;
-        // Match the ordering in scipDependencyOrder
-        sub(&mut w, "src.File", self.src_files, ifl)?;
-        sub(&mut w, "src.FileLines", self.file_lines, ifl)?;
-        sub(&mut w, "scip.Symbol", self.symbols, ifl)?;
-        sub(&mut w, "scip.LocalName", self.local_names, ifl)?;
-        sub(&mut w, "scip.Documentation", self.documentation, ifl)?;
-        sub(&mut w, "scip.FileLanguage", self.file_langs, ifl)?;
-        sub(&mut w, "scip.FileRange", self.file_ranges, ifl)?;
-        sub(&mut w, "scip.EnclosingRange", self.enclosing_ranges, ifl)?;
-        sub(&mut w, "scip.Definition", self.definitions, ifl)?;
-        sub(&mut w, "scip.Reference", self.references, ifl)?;
-        sub(
-            &mut w,
-            "scip.SymbolDocumentation",
-            self.symbol_documentation,
-            ifl,
-        )?;
-        sub(&mut w, "scip.SymbolName", self.symbol_names, ifl)?;
-        sub(&mut w, "scip.IsImplementation", self.is_implementation, ifl)?;
-        sub(&mut w, "scip.EnclosingSymbol", self.enclosing_symbols, ifl)?;
-        sub(&mut w, "scip.SymbolKind", self.symbol_kinds, ifl)?;
-        sub(&mut w, "scip.Metadata", self.metadata, ifl)?;
-        sub(&mut w, "scip.DisplayName", self.display_names, ifl)?;
-        sub(
-            &mut w,
-            "scip.DisplayNameSymbol",
-            self.display_name_symbols,
-            ifl,
-        )?;
+        let mut sink = JsonArraySink {
+            w: &mut w,
+            is_first_line: true,
+        };
+        self.emit(&mut sink)?;
         w.write_all(b"]\n")?;
 
         // A buffered writer would otherwise flush on drop, which discards the
@@ -549,4 +755,131 @@ impl GleanJSONOutput {
 
         Ok(())
     }
+
+    /// Newline-delimited JSON: one fact per line, tagged with its versioned
+    /// predicate name, e.g. `{"predicate":"src.File.1","id":1,"key":"a.go"}`.
+    /// Lines are in the same dependency order as `write`, so a streaming
+    /// reader never sees a fact before the facts it refers to. Grouping the
+    /// lines by `predicate` into `{"predicate":...,"facts":[...]}` batches
+    /// yields input for `glean write`.
+    pub fn write_ndjson(self, mut w: impl std::io::Write) -> std::io::Result<()> {
+        #[derive(Serialize)]
+        struct Tagged<'a, T> {
+            predicate: &'a str,
+            #[serde(flatten)]
+            fact: &'a T,
+        }
+
+        struct NdjsonSink<W> {
+            w: W,
+        }
+
+        impl<W: std::io::Write> PredicateSink for NdjsonSink<W> {
+            fn predicate(
+                &mut self,
+                name: &str,
+                mut items: Vec<impl Serialize>,
+            ) -> std::io::Result<()> {
+                // Same fact order as the JSON array output.
+                items.reverse();
+                let predicate = format!("{}.1", name);
+                for fact in &items {
+                    serde_json::to_writer(
+                        &mut self.w,
+                        &Tagged {
+                            predicate: &predicate,
+                            fact,
+                        },
+                    )?;
+                    self.w.write_all(b"\n")?;
+                }
+                Ok(())
+            }
+        }
+
+        self.emit(&mut NdjsonSink { w: &mut w })?;
+        w.flush()?;
+
+        Ok(())
+    }
+}
+
+/// The facts of a `GleanJSONOutput` with ids resolved to their keys.
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct ResolvedFacts {
+    /// Keyed by `src.File` path.
+    pub files: BTreeMap<Box<str>, ResolvedFile>,
+    /// Keyed by `scip.Symbol` key, i.e. the qualified symbol.
+    pub symbols: BTreeMap<Box<str>, ResolvedSymbol>,
+}
+
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct ResolvedFile {
+    pub definitions: BTreeSet<Location>,
+    pub references: BTreeSet<Location>,
+}
+
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct ResolvedSymbol {
+    /// `SymbolKind` variant names. More than one means the converter emitted
+    /// contradictory kinds.
+    pub kinds: BTreeSet<String>,
+    pub display_names: BTreeSet<Box<str>>,
+    pub documentation: BTreeSet<Box<str>>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub symbol: Box<str>,
+    pub range: GleanRange,
+}
+
+impl Location {
+    fn sort_key(&self) -> (u64, u64, u64, u64, &str) {
+        let r = &self.range;
+        (
+            r.line_begin,
+            r.column_begin,
+            r.line_end,
+            r.column_end,
+            &self.symbol,
+        )
+    }
+}
+
+impl Ord for Location {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+impl PartialOrd for Location {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Serialization format for `GleanJSONOutput`.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A JSON array of `{"predicate":...,"facts":[...]}` batches, as read by
+    /// `glean write`.
+    #[default]
+    Json,
+    /// One fact per line, tagged with its predicate.
+    Ndjson,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+/// Receives each predicate's facts from `GleanJSONOutput::emit`.
+trait PredicateSink {
+    fn predicate(&mut self, name: &str, items: Vec<impl Serialize>) -> std::io::Result<()>;
 }
@@ -0,0 +1,552 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! How a document's `relative_path` becomes the path of its `src.File`.
+//!
+//! Paths first go through ordered rewrite rules, given with `--rewrite` or
+//! in the config file, for merging indexes from several build roots. A rule
+//! is written `prefix:FROM=TO` or `regex:PATTERN=REPLACEMENT`, where the
+//! replacement may refer to capture groups as `$1` or `${name}`. Without
+//! `=REPLACEMENT`, documents whose path matches are dropped. The text before
+//! the first `=` is the pattern, so write a literal `=` in a regex as `\x3d`.
+//! The first matching rule applies.
+//!
+//! Then `--strip-prefix` is removed and `--root-prefix` prepended, and the
+//! result is normalized (see `PathNormalization`). Without normalization,
+//! `./a.go`, `a//a.go` and `b/../a.go` become distinct `src.File` facts.
+//!
+//! With `--resolve-project-root`, document paths are first resolved against
+//! the index's `Metadata.project_root`: absolute paths and paths climbing
+//! out of the root and back in are made relative to the root. With
+//! `--repo-root DIR`, or `--find-repo-root` to use the VCS checkout
+//! containing the project root, they are made relative to that directory
+//! instead, so an index of a subproject yields repository-relative paths.
+
+use std::borrow::Cow;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use regex::Regex;
+
+use crate::lsif::LanguageId;
+
+#[derive(Clone, Debug)]
+pub enum PathPattern {
+    Prefix(String),
+    Regex(Regex),
+}
+
+impl PartialEq for PathPattern {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (PathPattern::Prefix(a), PathPattern::Prefix(b)) => a == b,
+            (PathPattern::Regex(a), PathPattern::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for PathPattern {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathRewriteRule {
+    pub pattern: PathPattern,
+    /// `None` drops the documents whose path matches.
+    pub replacement: Option<String>,
+}
+
+impl PathRewriteRule {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (kind, rule) = spec.split_once(':').with_context(|| {
+            format!(
+                "Rewrite rule `{}` must start with `prefix:` or `regex:`",
+                spec
+            )
+        })?;
+        let (pattern, replacement) = match rule.split_once('=') {
+            Some((pattern, replacement)) => (pattern, Some(replacement.to_owned())),
+            None => (rule, None),
+        };
+        let pattern = match kind {
+            "prefix" => PathPattern::Prefix(pattern.to_owned()),
+            "regex" => PathPattern::Regex(
+                Regex::new(pattern)
+                    .with_context(|| format!("Invalid regex in rewrite rule `{}`", spec))?,
+            ),
+            _ => bail!(
+                "Rewrite rule `{}` must start with `prefix:` or `regex:`",
+                spec
+            ),
+        };
+        Ok(PathRewriteRule {
+            pattern,
+            replacement,
+        })
+    }
+
+    /// `None` if the rule does not match `path`, otherwise the rewritten
+    /// path, or `None` within if the document is dropped.
+    fn apply<'a>(&self, path: &'a str) -> Option<Option<Cow<'a, str>>> {
+        match &self.pattern {
+            PathPattern::Prefix(prefix) => {
+                let rest = path.strip_prefix(prefix.as_str())?;
+                Some(
+                    self.replacement
+                        .as_ref()
+                        .map(|replacement| Cow::Owned(format!("{}{}", replacement, rest))),
+                )
+            }
+            PathPattern::Regex(regex) => {
+                if !regex.is_match(path) {
+                    return None;
+                }
+                Some(
+                    self.replacement
+                        .as_ref()
+                        .map(|replacement| regex.replace(path, replacement.as_str())),
+                )
+            }
+        }
+    }
+}
+
+/// What the rules make of a path.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Rewritten<'a> {
+    Path(Cow<'a, str>),
+    Dropped,
+    /// No rule matched, the path is kept as is.
+    Unmatched,
+}
+
+/// An ordered list of rules. The default has none and keeps every path.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathRewrites(Vec<PathRewriteRule>);
+
+impl PathRewrites {
+    pub fn new(rules: Vec<PathRewriteRule>) -> Self {
+        PathRewrites(rules)
+    }
+
+    pub fn parse<S: AsRef<str>>(specs: &[S]) -> Result<Self> {
+        specs
+            .iter()
+            .map(|spec| PathRewriteRule::parse(spec.as_ref()))
+            .collect::<Result<_>>()
+            .map(PathRewrites)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn rewrite<'a>(&self, path: &'a str) -> Rewritten<'a> {
+        self.0
+            .iter()
+            .find_map(|rule| rule.apply(path))
+            .map_or(Rewritten::Unmatched, |rewritten| match rewritten {
+                Some(path) => Rewritten::Path(path),
+                None => Rewritten::Dropped,
+            })
+    }
+}
+
+/// Which documents have their paths normalized: `.` and `..` components
+/// resolved and repeated separators collapsed.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PathNormalization {
+    /// Keep paths as written.
+    Off,
+    /// TypeScript and TSX documents only, whose indexer emits `../` paths.
+    // TODO T240234639: Remove once SCIP stops returning paths with ../
+    Typescript,
+    /// Every document.
+    #[default]
+    All,
+}
+
+impl PathNormalization {
+    fn applies_to(self, lang: LanguageId) -> bool {
+        match self {
+            PathNormalization::Off => false,
+            PathNormalization::Typescript => {
+                matches!(lang, LanguageId::TypeScript | LanguageId::TypeScriptReact)
+            }
+            PathNormalization::All => true,
+        }
+    }
+}
+
+/// What to do with a path whose `..` components climb above its root.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum EscapingPaths {
+    /// Skip the document.
+    #[default]
+    Skip,
+    /// Keep the leading `..` components.
+    Keep,
+    /// Drop the leading `..` components, as if they stopped at the root.
+    Strip,
+}
+
+impl EscapingPaths {
+    /// TypeScript paths were always normalized, skipping escaping ones.
+    /// Paths of other languages were kept as written, so keep them rather
+    /// than drop documents that used to be emitted.
+    pub fn default_for(lang: LanguageId) -> EscapingPaths {
+        if PathNormalization::Typescript.applies_to(lang) {
+            EscapingPaths::Skip
+        } else {
+            EscapingPaths::Keep
+        }
+    }
+}
+
+/// Normalize a filepath by removing .. and . components
+/// Returns None if the path cannot be properly normalized (e.g., too many .. components)
+pub fn normalize_filepath(path: &str) -> Option<String> {
+    normalize_path(path, EscapingPaths::Skip)
+}
+
+/// Resolve `.` and `..` components and collapse repeated and trailing
+/// separators. `escaping` decides what happens to `..` components above the
+/// start of a relative path, or above `/`. `None` if it skips the path.
+pub fn normalize_path(path: &str, escaping: EscapingPaths) -> Option<String> {
+    let absolute = path.starts_with('/');
+    let mut parents = 0;
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if components.pop().is_none() {
+                    match escaping {
+                        EscapingPaths::Skip => return None,
+                        EscapingPaths::Keep => parents += 1,
+                        EscapingPaths::Strip => {}
+                    }
+                }
+            }
+            component => components.push(component),
+        }
+    }
+    let mut normalized = String::with_capacity(path.len());
+    if absolute {
+        normalized.push('/');
+    }
+    for _ in 0..parents {
+        normalized.push_str("../");
+    }
+    normalized.push_str(&components.join("/"));
+    if normalized.len() > 1 && normalized.ends_with('/') {
+        normalized.pop();
+    }
+    Some(normalized)
+}
+
+/// The path of `project_root`, a `file://` URI, with a trailing `/`. `None`
+/// for other schemes, which cannot contain document paths.
+pub fn project_root_path(project_root: &str) -> Option<String> {
+    directory_path(project_root.strip_prefix("file://")?)
+}
+
+/// The absolute, normalized form of the directory `path`, with a trailing
+/// `/`. `None` for relative paths.
+pub fn directory_path(path: &str) -> Option<String> {
+    if !path.starts_with('/') {
+        return None;
+    }
+    let mut path = normalize_path(path, EscapingPaths::Strip)?;
+    if !path.ends_with('/') {
+        path.push('/');
+    }
+    Some(path)
+}
+
+/// The closest ancestor of `dir` (or `dir` itself) that is the root of a
+/// Git, Mercurial or Sapling checkout.
+pub fn find_vcs_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| {
+            [".git", ".hg", ".sl"]
+                .iter()
+                .any(|vcs| dir.join(vcs).exists())
+        })
+        .map(Path::to_path_buf)
+}
+
+/// `path` resolved against the directory `base`, relative to the directory
+/// `root`. Both are absolute and end in `/`. Paths outside `root` start with
+/// `..` components.
+fn relative_to_root(base: &str, root: &str, path: &str) -> String {
+    let path = path.strip_prefix("file://").unwrap_or(path);
+    let absolute = if path.starts_with('/') {
+        normalize_path(path, EscapingPaths::Strip)
+    } else {
+        normalize_path(&format!("{}{}", base, path), EscapingPaths::Strip)
+    }
+    .unwrap_or_default();
+    let root: Vec<&str> = root.split('/').filter(|c| !c.is_empty()).collect();
+    let absolute: Vec<&str> = absolute.split('/').filter(|c| !c.is_empty()).collect();
+    let common = root
+        .iter()
+        .zip(&absolute)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = vec![".."; root.len() - common];
+    relative.extend(&absolute[common..]);
+    relative.join("/")
+}
+
+/// Why a document gets no `src.File` path.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PathSkip {
+    /// A rewrite rule drops it.
+    Dropped,
+    /// Its path escapes the root, under `EscapingPaths::Skip`.
+    Unnormalizable,
+}
+
+/// The transformations from `relative_path` to `src.File` path, for one
+/// input.
+#[derive(Clone, Debug, Default)]
+pub struct PathMapping {
+    /// The path of `Metadata.project_root` to resolve paths against, see
+    /// `project_root_path`.
+    pub project_root: Option<String>,
+    /// The directory resolved paths are made relative to, `project_root`
+    /// when unset. Absolute and ending in `/`.
+    pub repo_root: Option<String>,
+    pub rewrites: PathRewrites,
+    pub strip_prefix: Option<String>,
+    pub path_prefix: Option<String>,
+    pub normalization: PathNormalization,
+    /// `None` skips escaping TypeScript paths and keeps others, see
+    /// `EscapingPaths::default_for`.
+    pub escaping: Option<EscapingPaths>,
+}
+
+impl PathMapping {
+    /// The `src.File` path of a document of language `lang`.
+    pub fn map(&self, relative_path: &str, lang: LanguageId) -> Result<String, PathSkip> {
+        let resolved;
+        let relative_path = match &self.project_root {
+            Some(root) => {
+                let repo_root = self.repo_root.as_deref().unwrap_or(root);
+                resolved = relative_to_root(root, repo_root, relative_path);
+                &resolved
+            }
+            None => relative_path,
+        };
+        let mut filepath = match self.rewrites.rewrite(relative_path) {
+            Rewritten::Path(path) => path.into_owned(),
+            Rewritten::Dropped => return Err(PathSkip::Dropped),
+            Rewritten::Unmatched => relative_path.to_owned(),
+        };
+        if let Some(strip_prefix) = &self.strip_prefix {
+            if let Some(rest) = filepath.strip_prefix(strip_prefix.as_str()) {
+                filepath = rest.to_owned();
+            }
+        }
+        if let Some(path_prefix) = &self.path_prefix {
+            filepath.insert_str(0, path_prefix);
+        }
+        if self.normalization.applies_to(lang) {
+            let escaping = self
+                .escaping
+                .unwrap_or_else(|| EscapingPaths::default_for(lang));
+            filepath = normalize_path(&filepath, escaping).ok_or(PathSkip::Unnormalizable)?;
+        }
+        Ok(filepath)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_apply_in_order() {
+        let rewrites = PathRewrites::parse(&[
+            "prefix:bazel-out/k8-fastbuild/bin/=gen/",
+            "regex:^external/([^/]+)/=third-party/$1/",
+            "prefix:bazel-out/",
+            "prefix:external/",
+        ])
+        .unwrap();
+        let path = |p: &str| Rewritten::Path(Cow::Owned(p.to_owned()));
+        assert_eq!(
+            rewrites.rewrite("bazel-out/k8-fastbuild/bin/a/b.rs"),
+            path("gen/a/b.rs")
+        );
+        assert_eq!(
+            rewrites.rewrite("bazel-out/k8-opt/bin/a.rs"),
+            Rewritten::Dropped
+        );
+        // The regex rule matches before the later drop rule.
+        assert_eq!(
+            rewrites.rewrite("external/serde/src/lib.rs"),
+            path("third-party/serde/src/lib.rs")
+        );
+        assert_eq!(rewrites.rewrite("src/main.rs"), Rewritten::Unmatched);
+        assert_eq!(PathRewrites::default().rewrite("a"), Rewritten::Unmatched);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |spec: &str| PathRewriteRule::parse(spec).unwrap_err().to_string();
+        assert!(err("bazel-out/").contains("must start with"));
+        assert!(err("glob:*.rs=x").contains("must start with"));
+        assert!(err("regex:(=x").contains("Invalid regex"));
+    }
+
+    #[test]
+    fn test_mapping_rewrites_before_prefixes() {
+        let mapping = PathMapping {
+            rewrites: PathRewrites::parse(&["prefix:bazel-out/bin/=out/src/", "prefix:tmp/"])
+                .unwrap(),
+            strip_prefix: Some("out/".to_string()),
+            path_prefix: Some("repo/".to_string()),
+            ..Default::default()
+        };
+        let map = |path| mapping.map(path, LanguageId::Go);
+        assert_eq!(map("bazel-out/bin/a.go").as_deref(), Ok("repo/src/a.go"));
+        assert_eq!(map("out/b.go").as_deref(), Ok("repo/b.go"));
+        assert_eq!(map("tmp/c.go"), Err(PathSkip::Dropped));
+    }
+
+    #[test]
+    fn test_normalize_filepath_no_dots() {
+        assert_eq!(
+            normalize_filepath("foo/bar/baz.go"),
+            Some("foo/bar/baz.go".to_string())
+        );
+    }
+
+    #[test]
+    fn test_normalize_filepath_with_parent() {
+        assert_eq!(
+            normalize_filepath("foo/bar/../baz.go"),
+            Some("foo/baz.go".to_string())
+        );
+    }
+
+    #[test]
+    fn test_normalize_filepath_with_curdir() {
+        assert_eq!(
+            normalize_filepath("foo/./bar/baz.go"),
+            Some("foo/bar/baz.go".to_string())
+        );
+    }
+
+    #[test]
+    fn test_normalize_filepath_too_many_parents() {
+        assert_eq!(normalize_filepath("foo/../../baz.go"), None);
+    }
+
+    #[test]
+    fn test_normalize_path_collapses_separators_and_applies_escape_policy() {
+        let normalize = |path, escaping| normalize_path(path, escaping);
+        assert_eq!(
+            normalize(".//src///a/./b.go", EscapingPaths::Skip).as_deref(),
+            Some("src/a/b.go")
+        );
+        assert_eq!(
+            normalize("/abs//dir/", EscapingPaths::Skip).as_deref(),
+            Some("/abs/dir")
+        );
+        assert_eq!(normalize("../../x/../y.go", EscapingPaths::Skip), None);
+        assert_eq!(
+            normalize("../../x/../y.go", EscapingPaths::Keep).as_deref(),
+            Some("../../y.go")
+        );
+        assert_eq!(
+            normalize("../../x/../y.go", EscapingPaths::Strip).as_deref(),
+            Some("y.go")
+        );
+    }
+
+    #[test]
+    fn test_normalization_modes() {
+        let mapping = |normalization| PathMapping {
+            normalization,
+            ..Default::default()
+        };
+        let path = "a/./b//../c.go";
+        let typescript = mapping(PathNormalization::Typescript);
+        assert_eq!(typescript.map(path, LanguageId::Go).as_deref(), Ok(path));
+        assert_eq!(
+            typescript.map(path, LanguageId::TypeScript).as_deref(),
+            Ok("a/c.go")
+        );
+        let all = mapping(PathNormalization::All);
+        assert_eq!(all.map(path, LanguageId::Go).as_deref(), Ok("a/c.go"));
+        let off = mapping(PathNormalization::Off);
+        assert_eq!(off.map(path, LanguageId::TypeScript).as_deref(), Ok(path));
+
+        // By default, only escaping TypeScript paths skip their document.
+        assert_eq!(
+            all.map("a/../../b.go", LanguageId::Go).as_deref(),
+            Ok("../b.go")
+        );
+        assert_eq!(
+            all.map("a/../../b.ts", LanguageId::TypeScript),
+            Err(PathSkip::Unnormalizable)
+        );
+    }
+
+    #[test]
+    fn test_resolve_against_project_root() {
+        let root = project_root_path("file:///repo//svc").unwrap();
+        assert_eq!(root, "/repo/svc/");
+        assert_eq!(project_root_path("https://example.com/repo"), None);
+        let mapping = PathMapping {
+            project_root: Some(root),
+            normalization: PathNormalization::All,
+            escaping: Some(EscapingPaths::Keep),
+            ..Default::default()
+        };
+        let map = |path| mapping.map(path, LanguageId::Go);
+        assert_eq!(map("a.go").as_deref(), Ok("a.go"));
+        assert_eq!(map("../svc/./a.go").as_deref(), Ok("a.go"));
+        assert_eq!(map("/repo/svc/b/c.go").as_deref(), Ok("b/c.go"));
+        assert_eq!(map("file:///repo/svc/d.go").as_deref(), Ok("d.go"));
+        assert_eq!(map("../lib/x.go").as_deref(), Ok("../lib/x.go"));
+        assert_eq!(
+            map("/usr/include/stdio.h").as_deref(),
+            Ok("../../usr/include/stdio.h")
+        );
+    }
+
+    #[test]
+    fn test_relocate_to_repo_root() {
+        let mapping = PathMapping {
+            project_root: project_root_path("file:///sandbox/repo/svc/"),
+            repo_root: directory_path("/sandbox/repo"),
+            ..Default::default()
+        };
+        let map = |path| mapping.map(path, LanguageId::Go);
+        assert_eq!(map("a.go").as_deref(), Ok("svc/a.go"));
+        assert_eq!(map("../lib/b.go").as_deref(), Ok("lib/b.go"));
+        assert_eq!(map("/sandbox/repo/c.go").as_deref(), Ok("c.go"));
+        assert_eq!(directory_path("relative/dir"), None);
+    }
+
+    #[test]
+    fn test_find_vcs_root() {
+        let repo = tempfile::tempdir().unwrap();
+        let project = repo.path().join("svc/api");
+        std::fs::create_dir_all(&project).unwrap();
+        assert_ne!(find_vcs_root(&project).as_deref(), Some(repo.path()));
+        std::fs::create_dir(repo.path().join(".git")).unwrap();
+        assert_eq!(find_vcs_root(&project).as_deref(), Some(repo.path()));
+    }
+}
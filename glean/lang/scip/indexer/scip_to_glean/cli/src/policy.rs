@@ -0,0 +1,86 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! How the converter reacts to documents and occurrences it cannot decode.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Context;
+use anyhow::Result;
+use serde::Serialize;
+
+#[derive(clap::Args, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FailurePolicy {
+    #[arg(
+        long,
+        default_value_t = 50,
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        help = "Abort when more than this percentage of an input's documents fail to decode"
+    )]
+    pub max_failure_percent: u8,
+
+    #[arg(
+        long,
+        conflicts_with = "skip_bad_occurrences",
+        help = "Fail on the first document or occurrence that cannot be decoded"
+    )]
+    pub strict: bool,
+
+    #[arg(
+        long,
+        help = "Skip only the offending occurrence when an occurrence cannot be decoded, keeping the rest of its document"
+    )]
+    pub skip_bad_occurrences: bool,
+}
+
+impl Default for FailurePolicy {
+    fn default() -> Self {
+        Self {
+            max_failure_percent: 50,
+            strict: false,
+            skip_bad_occurrences: false,
+        }
+    }
+}
+
+impl FailurePolicy {
+    /// Whether `failed` out of `total` documents exceeds the threshold.
+    pub fn exceeds_threshold(&self, failed: usize, total: usize) -> bool {
+        total > 0 && failed * 100 > total * self.max_failure_percent as usize
+    }
+}
+
+/// A document or occurrence that was dropped from the output.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedItem {
+    /// The SCIP file the item was read from.
+    pub input: String,
+    /// The document's `relative_path`.
+    pub document: String,
+    /// The occurrence's symbol; absent when the whole document was skipped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    pub reason: String,
+}
+
+pub fn write_skipped_items(path: &Path, items: &[SkippedItem]) -> Result<()> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .with_context(|| format!("Error creating errors file {}", path.display()))?;
+    let mut w = std::io::BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut w, items)?;
+    w.write_all(b"\n")?;
+    w.flush()?;
+    Ok(())
+}
@@ -0,0 +1,214 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Validation of decoded ranges against a file's line table.
+//!
+//! `GleanRange` is 1-based with an inclusive end column, so a range on a line
+//! of `n` characters may span columns `1..=n`. Line lengths come from the same
+//! bytes as `src.FileLines` and count bytes, which is an upper bound for
+//! UTF-8 and UTF-16 column units alike, so valid ranges are never flagged.
+
+use crate::GleanRange;
+
+/// What to do with ranges that fall outside the file's contents.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RangeValidation {
+    /// Do not check ranges.
+    #[default]
+    Off,
+    /// Count out-of-bounds ranges, but emit them unchanged.
+    Report,
+    /// Clip out-of-bounds ranges to the file contents. Ranges starting past
+    /// the end of the file are dropped.
+    Clip,
+    /// Drop out-of-bounds ranges, and with them their occurrence.
+    Drop,
+}
+
+/// Outcome of validating a range under a `RangeValidation` policy.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeCheck {
+    InBounds(GleanRange),
+    /// Out of bounds and emitted anyway (`Report`) or clipped (`Clip`).
+    Adjusted(GleanRange),
+    Dropped,
+}
+
+/// Per-line content lengths of a file, excluding line terminators.
+pub struct LineTable {
+    content_lengths: Vec<u64>,
+    ends_in_newline: bool,
+}
+
+impl LineTable {
+    /// Build from `src.FileLines` lengths, which include the newline.
+    pub fn new(lengths: &[u64], ends_in_newline: bool) -> Self {
+        let last = lengths.len().saturating_sub(1);
+        let content_lengths = lengths
+            .iter()
+            .enumerate()
+            .map(|(idx, len)| {
+                if idx < last || ends_in_newline {
+                    len.saturating_sub(1)
+                } else {
+                    *len
+                }
+            })
+            .collect();
+        Self {
+            content_lengths,
+            ends_in_newline,
+        }
+    }
+
+    /// Number of addressable lines. A file ending in a newline has an empty
+    /// final line that positions at end-of-file may point to.
+    fn line_count(&self) -> u64 {
+        self.content_lengths.len() as u64 + u64::from(self.ends_in_newline)
+    }
+
+    /// Length of the 1-based `line`, or 0 for the empty final line.
+    fn line_length(&self, line: u64) -> u64 {
+        line.checked_sub(1)
+            .and_then(|idx| self.content_lengths.get(idx as usize))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn contains(&self, range: &GleanRange) -> bool {
+        let lines = self.line_count();
+        // `decode_scip_range` widens empty SCIP ranges to one column, so a
+        // position just past the last character must be accepted.
+        let is_position =
+            range.line_begin == range.line_end && range.column_begin == range.column_end;
+        let max_column_end = self.line_length(range.line_end) + u64::from(is_position);
+        range.line_begin >= 1
+            && range.line_begin <= range.line_end
+            && range.line_end <= lines
+            && range.column_begin <= self.line_length(range.line_begin) + 1
+            && range.column_end <= max_column_end
+    }
+
+    pub fn check(&self, range: GleanRange, policy: RangeValidation) -> RangeCheck {
+        match policy {
+            RangeValidation::Off => RangeCheck::InBounds(range),
+            _ if self.contains(&range) => RangeCheck::InBounds(range),
+            RangeValidation::Report => RangeCheck::Adjusted(range),
+            RangeValidation::Drop => RangeCheck::Dropped,
+            RangeValidation::Clip => self
+                .clip(range)
+                .map_or(RangeCheck::Dropped, RangeCheck::Adjusted),
+        }
+    }
+
+    fn clip(&self, range: GleanRange) -> Option<GleanRange> {
+        let lines = self.line_count();
+        if range.line_begin > lines {
+            return None;
+        }
+        let line_begin = range.line_begin.max(1);
+        let line_end = range.line_end.clamp(line_begin, lines);
+        let column_begin = range
+            .column_begin
+            .min(self.line_length(line_begin) + 1)
+            .max(1);
+        // Glean columns are 1-based, even on the empty final line.
+        let mut column_end = range.column_end.min(self.line_length(line_end)).max(1);
+        if line_begin == line_end {
+            column_end = column_end.max(column_begin);
+        }
+        Some(GleanRange {
+            line_begin,
+            column_begin,
+            line_end,
+            column_end,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(line_begin: u64, column_begin: u64, line_end: u64, column_end: u64) -> GleanRange {
+        GleanRange {
+            line_begin,
+            column_begin,
+            line_end,
+            column_end,
+        }
+    }
+
+    // "let a = 1\n" (9 + newline), "\n", "x\n"
+    fn table() -> LineTable {
+        LineTable::new(&[10, 1, 2], true)
+    }
+
+    #[test]
+    fn test_in_bounds_ranges_are_kept() {
+        let table = table();
+        for r in [
+            range(1, 5, 1, 5),
+            range(1, 1, 1, 9),
+            range(1, 5, 3, 1),
+            // empty range at end of line 1
+            range(1, 10, 1, 10),
+            // end-of-file position on the empty final line
+            range(4, 1, 4, 1),
+        ] {
+            assert_eq!(
+                table.check(r.clone(), RangeValidation::Drop),
+                RangeCheck::InBounds(r)
+            );
+        }
+    }
+
+    #[test]
+    fn test_column_past_end_of_line() {
+        let table = table();
+        let past_eol = range(1, 5, 1, 20);
+        assert_eq!(
+            table.check(past_eol.clone(), RangeValidation::Report),
+            RangeCheck::Adjusted(past_eol.clone())
+        );
+        assert_eq!(
+            table.check(past_eol.clone(), RangeValidation::Drop),
+            RangeCheck::Dropped
+        );
+        assert_eq!(
+            table.check(past_eol.clone(), RangeValidation::Clip),
+            RangeCheck::Adjusted(range(1, 5, 1, 9))
+        );
+        assert_eq!(
+            table.check(past_eol.clone(), RangeValidation::Off),
+            RangeCheck::InBounds(past_eol)
+        );
+    }
+
+    #[test]
+    fn test_line_past_end_of_file() {
+        let table = table();
+        assert_eq!(
+            table.check(range(2, 1, 9, 3), RangeValidation::Clip),
+            RangeCheck::Adjusted(range(2, 1, 4, 1))
+        );
+        assert_eq!(
+            table.check(range(7, 1, 7, 3), RangeValidation::Clip),
+            RangeCheck::Dropped
+        );
+    }
+
+    #[test]
+    fn test_no_trailing_newline() {
+        // "ab\n", "cd" — the last line has no terminator to subtract.
+        let table = LineTable::new(&[3, 2], false);
+        assert!(table.contains(&range(2, 1, 2, 2)));
+        assert!(!table.contains(&range(2, 1, 2, 3)));
+        assert!(!table.contains(&range(3, 1, 3, 0)));
+    }
+}
@@ -0,0 +1,550 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! `scip-to-glean from-json`: rebuild a SCIP index from the facts written by
+//! `GleanJSONOutput`, so they can be viewed with standard SCIP tooling.
+//!
+//! Reads the JSON array and the NDJSON formats. Shards of one conversion can
+//! be passed together, as they share fact ids. What the facts do not record
+//! cannot be recovered: document text (unless converted with
+//! `--emit-file-contents`), `Metadata.project_root`, symbol
+//! roles other than definition, and `SymbolKind`s without a SCIP
+//! `SymbolInformation.Kind` counterpart. Ranges are reproduced as they were
+//! emitted, i.e. after `//line` remapping and clamping.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Document;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Index;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Metadata;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Occurrence;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Relationship;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::SymbolInformation;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::ToolInfo as ScipToolInfo;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::symbol_information;
+use protobuf::EnumOrUnknown;
+use protobuf::Message;
+use protobuf::MessageField;
+use scip_symbol::ScipSymbol;
+use scip_symbol::parse_scip_symbol;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use tracing::info;
+
+use crate::GleanRange;
+use crate::ToolInfo;
+use crate::lsif::LanguageId;
+use crate::lsif::SymbolKind;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Document;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Index;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Metadata;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Occurrence;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Relationship;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::SymbolInformation;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::ToolInfo as ScipToolInfo;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::symbol_information;
+
+#[derive(clap::Args, Debug)]
+pub struct FromJsonArgs {
+    #[arg(
+        short,
+        long,
+        required = true,
+        help = "Glean JSON or NDJSON fact files, e.g. every shard of one conversion"
+    )]
+    pub input: Vec<PathBuf>,
+
+    #[arg(short, long, help = "Path of the reconstructed SCIP index")]
+    pub output: PathBuf,
+}
+
+pub fn from_json(args: &FromJsonArgs) -> Result<()> {
+    let mut facts = FactSet::default();
+    for input in &args.input {
+        facts
+            .read_file(input)
+            .with_context(|| format!("Error reading facts from {}", input.display()))?;
+    }
+    let index = facts.into_index()?;
+    info!(
+        "Reconstructed {} documents and {} external symbols",
+        index.documents.len(),
+        index.external_symbols.len()
+    );
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&args.output)
+        .with_context(|| format!("Error creating output file {}", args.output.display()))?;
+    let mut w = std::io::BufWriter::new(file);
+    index
+        .write_to_writer(&mut w)
+        .with_context(|| format!("Error writing output file {}", args.output.display()))?;
+    w.flush()?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct IdKey<T> {
+    id: u64,
+    key: T,
+}
+
+#[derive(Deserialize)]
+struct Key<T> {
+    key: T,
+}
+
+#[derive(Deserialize)]
+struct KeyValue<K, V> {
+    key: K,
+    value: V,
+}
+
+#[derive(Deserialize)]
+struct FileLang {
+    file: u64,
+    language: u8,
+}
+
+#[derive(Deserialize)]
+struct FileRange {
+    file: u64,
+    range: GleanRange,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EnclosingRange {
+    range: u64,
+    enclosing_range: u64,
+}
+
+#[derive(Deserialize)]
+struct SymbolLocation {
+    location: u64,
+    symbol: u64,
+}
+
+#[derive(Deserialize)]
+struct SymbolDocs {
+    docs: u64,
+    symbol: u64,
+}
+
+#[derive(Deserialize)]
+struct IsImplementation {
+    symbol: u64,
+    implemented: u64,
+}
+
+#[derive(Deserialize)]
+struct EnclosingSymbol {
+    symbol: u64,
+    enclosing: u64,
+}
+
+#[derive(Deserialize)]
+struct SymbolAndKind {
+    kind: u8,
+    symbol: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DisplayNameSymbol {
+    display_name: u64,
+    symbol: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetadataFact {
+    text_encoding: i32,
+    tool_info: Option<ToolInfo>,
+    version: i32,
+}
+
+/// One batch of the JSON array format.
+#[derive(Deserialize)]
+struct Batch {
+    predicate: String,
+    facts: Vec<serde_json::Value>,
+}
+
+/// Facts collected from one or more files, keyed by fact id.
+#[derive(Default)]
+pub struct FactSet {
+    files: BTreeMap<u64, String>,
+    file_langs: HashMap<u64, u8>,
+    file_contents: HashMap<u64, String>,
+    file_ranges: BTreeMap<u64, FileRange>,
+    /// Range id to enclosing range id.
+    enclosing_ranges: HashMap<u64, u64>,
+    symbols: HashMap<u64, String>,
+    /// (range id, symbol id, is definition)
+    locations: Vec<(u64, u64, bool)>,
+    documentation: HashMap<u64, String>,
+    symbol_documentation: Vec<SymbolDocs>,
+    symbol_kinds: Vec<SymbolAndKind>,
+    display_names: HashMap<u64, String>,
+    display_name_symbols: Vec<DisplayNameSymbol>,
+    enclosing_symbols: Vec<EnclosingSymbol>,
+    implementations: Vec<IsImplementation>,
+    metadata: Option<MetadataFact>,
+}
+
+fn parse<T: DeserializeOwned>(fact: serde_json::Value) -> Result<T> {
+    Ok(serde_json::from_value(fact)?)
+}
+
+impl FactSet {
+    pub fn read_file(&mut self, path: &Path) -> Result<()> {
+        let file = std::fs::File::open(path)?;
+        self.read(std::io::BufReader::new(file))
+    }
+
+    /// Read facts in either output format: a JSON array of batches, or one
+    /// tagged fact per line.
+    pub fn read(&mut self, mut reader: impl BufRead) -> Result<()> {
+        let is_array = reader
+            .fill_buf()?
+            .iter()
+            .find(|b| !b.is_ascii_whitespace())
+            .is_some_and(|b| *b == b'[');
+        if is_array {
+            let batches: Vec<Batch> = serde_json::from_reader(reader)?;
+            for batch in batches {
+                for fact in batch.facts {
+                    self.add(&batch.predicate, fact)?;
+                }
+            }
+        } else {
+            for (number, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let mut fact: serde_json::Map<String, serde_json::Value> =
+                    serde_json::from_str(&line)
+                        .with_context(|| format!("Invalid JSON on line {}", number + 1))?;
+                let predicate = match fact.remove("predicate") {
+                    Some(serde_json::Value::String(predicate)) => predicate,
+                    _ => return Err(anyhow!("Missing predicate on line {}", number + 1)),
+                };
+                self.add(&predicate, fact.into())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add one fact of `predicate`, e.g. `scip.Definition.1`.
+    pub fn add(&mut self, predicate: &str, fact: serde_json::Value) -> Result<()> {
+        let name = predicate
+            .rsplit_once('.')
+            .filter(|(_, version)| version.parse::<u32>().is_ok())
+            .map_or(predicate, |(name, _)| name);
+        match name {
+            "src.File" => {
+                let f: IdKey<String> = parse(fact)?;
+                self.files.insert(f.id, f.key);
+            }
+            "scip.FileLanguage" => {
+                let f: IdKey<FileLang> = parse(fact)?;
+                self.file_langs.insert(f.key.file, f.key.language);
+            }
+            "src.FileContent" => {
+                let f: KeyValue<u64, String> = parse(fact)?;
+                self.file_contents.insert(f.key, f.value);
+            }
+            "scip.FileRange" => {
+                let f: IdKey<FileRange> = parse(fact)?;
+                self.file_ranges.insert(f.id, f.key);
+            }
+            "scip.EnclosingRange" => {
+                let f: IdKey<EnclosingRange> = parse(fact)?;
+                self.enclosing_ranges
+                    .insert(f.key.range, f.key.enclosing_range);
+            }
+            "scip.Symbol" => {
+                let f: IdKey<String> = parse(fact)?;
+                self.symbols.insert(f.id, f.key);
+            }
+            "scip.Definition" | "scip.Reference" => {
+                let f: Key<SymbolLocation> = parse(fact)?;
+                self.locations
+                    .push((f.key.location, f.key.symbol, name == "scip.Definition"));
+            }
+            "scip.Documentation" => {
+                let f: IdKey<String> = parse(fact)?;
+                self.documentation.insert(f.id, f.key);
+            }
+            "scip.SymbolDocumentation" => {
+                self.symbol_documentation
+                    .push(parse::<IdKey<SymbolDocs>>(fact)?.key);
+            }
+            "scip.SymbolKind" => self.symbol_kinds.push(parse::<Key<_>>(fact)?.key),
+            "scip.DisplayName" => {
+                let f: IdKey<String> = parse(fact)?;
+                self.display_names.insert(f.id, f.key);
+            }
+            "scip.DisplayNameSymbol" => {
+                self.display_name_symbols.push(parse::<Key<_>>(fact)?.key);
+            }
+            "scip.EnclosingSymbol" => self.enclosing_symbols.push(parse::<Key<_>>(fact)?.key),
+            "scip.IsImplementation" => self.implementations.push(parse::<Key<_>>(fact)?.key),
+            "scip.Metadata" => self.metadata = Some(parse::<Key<_>>(fact)?.key),
+            // Derived from the symbol string, or not representable in SCIP.
+            "src.FileLines"
+            | "src.FileDigest"
+            | "scip.LocalName"
+            | "scip.SymbolName"
+            | "scip.GeneratedRange" => {}
+            _ => return Err(anyhow!("Unknown predicate `{}`", predicate)),
+        }
+        Ok(())
+    }
+
+    pub fn into_index(self) -> Result<Index> {
+        let mut index = Index::new();
+        if let Some(metadata) = &self.metadata {
+            let mut m = Metadata::new();
+            m.version = EnumOrUnknown::from_i32(metadata.version);
+            m.text_document_encoding = EnumOrUnknown::from_i32(metadata.text_encoding);
+            m.tool_info = metadata
+                .tool_info
+                .as_ref()
+                .map_or_else(MessageField::none, |t| {
+                    let mut tool_info = ScipToolInfo::new();
+                    tool_info.name = t.tool_name.clone();
+                    tool_info.version = t.version.clone();
+                    tool_info.arguments = t.tool_args.clone();
+                    MessageField::some(tool_info)
+                });
+            index.metadata = MessageField::some(m);
+        }
+
+        // File ids are allocated in the order documents were decoded.
+        let mut documents: BTreeMap<u64, Document> = BTreeMap::new();
+        let mut file_ids: HashMap<&str, u64> = HashMap::new();
+        for (id, path) in &self.files {
+            let mut doc = Document::new();
+            doc.relative_path = path.clone();
+            doc.language = self
+                .file_langs
+                .get(id)
+                .and_then(|lang| LanguageId::from_u8(*lang))
+                .map_or("", LanguageId::name)
+                .to_owned();
+            doc.text = self.file_contents.get(id).cloned().unwrap_or_default();
+            documents.insert(*id, doc);
+            file_ids.insert(path, *id);
+        }
+
+        let scip_symbol = |id: u64| -> Result<(String, Option<u64>)> {
+            let key = self
+                .symbols
+                .get(&id)
+                .ok_or_else(|| anyhow!("Unknown symbol id {}", id))?;
+            Ok(unqualify_symbol(key, &file_ids))
+        };
+
+        // Occurrences, in range id order. A symbol's information goes to the
+        // file of its first definition, else of its first reference.
+        let mut definition_files: HashMap<u64, u64> = HashMap::new();
+        let mut home_files: HashMap<u64, u64> = HashMap::new();
+        let mut locations = self.locations.clone();
+        locations.sort_unstable();
+        for (range_id, symbol_id, is_definition) in locations {
+            let file_range = self
+                .file_ranges
+                .get(&range_id)
+                .ok_or_else(|| anyhow!("Unknown range id {}", range_id))?;
+            let doc = documents
+                .get_mut(&file_range.file)
+                .ok_or_else(|| anyhow!("Unknown file id {}", file_range.file))?;
+            let mut occ = Occurrence::new();
+            occ.symbol = scip_symbol(symbol_id)?.0;
+            occ.range = scip_range(&file_range.range);
+            if is_definition {
+                occ.symbol_roles = 1;
+            }
+            if let Some(enclosing) = self
+                .enclosing_ranges
+                .get(&range_id)
+                .and_then(|id| self.file_ranges.get(id))
+            {
+                occ.enclosing_range = scip_range(&enclosing.range);
+            }
+            doc.occurrences.push(occ);
+            if is_definition {
+                definition_files.entry(symbol_id).or_insert(file_range.file);
+            }
+            home_files.entry(symbol_id).or_insert(file_range.file);
+        }
+        home_files.extend(definition_files);
+
+        let mut infos: BTreeMap<u64, SymbolInformation> = BTreeMap::new();
+        for id in self.symbols.keys() {
+            let mut info = SymbolInformation::new();
+            info.symbol = scip_symbol(*id)?.0;
+            infos.insert(*id, info);
+        }
+        for fact in &self.symbol_documentation {
+            let text = self
+                .documentation
+                .get(&fact.docs)
+                .ok_or_else(|| anyhow!("Unknown documentation id {}", fact.docs))?;
+            info_mut(&mut infos, fact.symbol)?
+                .documentation
+                .push(text.clone());
+        }
+        for fact in &self.display_name_symbols {
+            let name = self
+                .display_names
+                .get(&fact.display_name)
+                .ok_or_else(|| anyhow!("Unknown display name id {}", fact.display_name))?;
+            info_mut(&mut infos, fact.symbol)?.display_name = name.clone();
+        }
+        for fact in &self.enclosing_symbols {
+            let enclosing = scip_symbol(fact.enclosing)?.0;
+            info_mut(&mut infos, fact.symbol)?.enclosing_symbol = enclosing;
+        }
+        for fact in &self.implementations {
+            let mut rel = Relationship::new();
+            rel.symbol = scip_symbol(fact.implemented)?.0;
+            rel.is_implementation = true;
+            info_mut(&mut infos, fact.symbol)?.relationships.push(rel);
+        }
+        let mut kinds_seen = HashMap::new();
+        for fact in &self.symbol_kinds {
+            if kinds_seen.insert(fact.symbol, fact.kind).is_some() {
+                // Contradictory kinds: the first one wins, like in Glean
+                // queries that take any kind.
+                continue;
+            }
+            let (symbol, _) = scip_symbol(fact.symbol)?;
+            let Some(kind) = SymbolKind::from_u8(fact.kind) else {
+                continue;
+            };
+            if kind != default_kind(&symbol) {
+                if let Some(kind) = symbol_kind_to_scip_kind(kind) {
+                    info_mut(&mut infos, fact.symbol)?.kind = kind.into();
+                }
+            }
+        }
+        for (id, info) in infos {
+            // Symbols without occurrences come from `Index.external_symbols`,
+            // which the converter turns into facts even without information.
+            let mut bare = SymbolInformation::new();
+            bare.symbol = info.symbol.clone();
+            if info == bare && home_files.contains_key(&id) {
+                continue;
+            }
+            let local_file = scip_symbol(id)?.1;
+            match local_file.or_else(|| home_files.get(&id).copied()) {
+                Some(file) => documents.get_mut(&file).unwrap().symbols.push(info),
+                None => index.external_symbols.push(info),
+            }
+        }
+
+        index.documents = documents.into_values().collect();
+        Ok(index)
+    }
+}
+
+fn info_mut(
+    infos: &mut BTreeMap<u64, SymbolInformation>,
+    id: u64,
+) -> Result<&mut SymbolInformation> {
+    infos
+        .get_mut(&id)
+        .ok_or_else(|| anyhow!("Unknown symbol id {}", id))
+}
+
+/// Undo `qualify_scip_symbol`: local symbol keys are prefixed by their file
+/// path. Returns the SCIP symbol, and the file id for locals.
+fn unqualify_symbol(key: &str, file_ids: &HashMap<&str, u64>) -> (String, Option<u64>) {
+    if let Some((path, local)) = key.rsplit_once("/local ") {
+        if let Some(id) = file_ids.get(path) {
+            return (format!("local {}", local), Some(*id));
+        }
+    }
+    (key.to_owned(), None)
+}
+
+/// Inverse of `decode_scip_range` for ranges it did not have to fix up.
+fn scip_range(range: &GleanRange) -> Vec<i32> {
+    let line_begin = range.line_begin as i32 - 1;
+    let column_begin = range.column_begin as i32 - 1;
+    if range.line_begin == range.line_end {
+        vec![line_begin, column_begin, range.column_end as i32]
+    } else {
+        vec![
+            line_begin,
+            column_begin,
+            range.line_end as i32 - 1,
+            range.column_end as i32,
+        ]
+    }
+}
+
+/// The kind the converter assigns without `SymbolInformation.kind`.
+fn default_kind(symbol: &str) -> SymbolKind {
+    match parse_scip_symbol(symbol) {
+        ScipSymbol::Local { .. } => SymbolKind::SkVariable,
+        ScipSymbol::Global { descriptors, .. } => descriptors
+            .last()
+            .map_or(SymbolKind::SkUnknown, |d| SymbolKind::new(d.kind.clone())),
+    }
+}
+
+/// Inverse of `scip_kind_to_symbol_kind`.
+fn symbol_kind_to_scip_kind(kind: SymbolKind) -> Option<symbol_information::Kind> {
+    use SymbolKind::*;
+    use symbol_information::Kind;
+    Some(match kind {
+        SkVariable => Kind::Variable,
+        SkConstant => Kind::Constant,
+        SkClass => Kind::Class,
+        SkFunction => Kind::Function,
+        SkConstructor => Kind::Constructor,
+        SkMethod => Kind::Method,
+        SkModule => Kind::Module,
+        SkInterface => Kind::Interface,
+        SkEnum => Kind::Enum,
+        _ => return None,
+    })
+}
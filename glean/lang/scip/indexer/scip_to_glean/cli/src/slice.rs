@@ -0,0 +1,194 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! `scip-to-glean slice`: cut a SCIP index down to a few documents, for
+//! attaching minimal repros to bug reports.
+//!
+//! Besides the matching documents, the reduced index keeps what their
+//! conversion depends on: external symbols they refer to, and the global
+//! `SymbolInformation` of dropped documents (which carries kinds and
+//! documentation), moved to `external_symbols`.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Document;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Index;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::SymbolInformation;
+use protobuf::Message;
+use scip_symbol::ScipSymbol;
+use scip_symbol::parse_scip_symbol;
+use tracing::info;
+
+use crate::angle::Env;
+use crate::filter::DocumentFilter;
+use crate::filter::DocumentFilterArgs;
+use crate::lsif::LanguageId;
+use crate::merge::merge_symbol_information;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Document;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Index;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::SymbolInformation;
+use crate::read_scip_file;
+
+#[derive(clap::Args, Debug)]
+pub struct SliceArgs {
+    #[arg(short, long)]
+    pub input: PathBuf,
+
+    #[arg(short, long, help = "Path of the reduced SCIP index")]
+    pub output: PathBuf,
+
+    #[command(flatten)]
+    pub filter: DocumentFilterArgs,
+
+    #[arg(
+        long,
+        value_name = "SUBSTRING",
+        help = "Only keep documents with an occurrence of a symbol containing one of these substrings"
+    )]
+    pub symbol: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Infer the language of documents that do not set one from their file name or extension"
+    )]
+    pub infer_language: bool,
+
+    #[arg(
+        long,
+        help = "The default language to use for files without a recognized extension."
+    )]
+    pub language: Option<String>,
+}
+
+pub fn slice(args: &SliceArgs) -> Result<()> {
+    let index = read_scip_file(&args.input)
+        .with_context(|| format!("Error opening input file {}", args.input.display()))?;
+    let num_docs = index.documents.len();
+    let default_language = args
+        .language
+        .as_ref()
+        .and_then(|s| LanguageId::new(s).known());
+    // Resolve languages like the converter does, so `--only-language`
+    // selects the same documents in both.
+    let env = Env::new();
+    let reduced = slice_index(index, &args.filter.build()?, &args.symbol, |doc| {
+        env.infer_lang_for_doc(default_language, args.infer_language, doc, None)
+    });
+    info!(
+        "Kept {} of {} documents and {} external symbols",
+        reduced.documents.len(),
+        num_docs,
+        reduced.external_symbols.len()
+    );
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&args.output)
+        .with_context(|| format!("Error creating output file {}", args.output.display()))?;
+    let mut w = std::io::BufWriter::new(file);
+    reduced
+        .write_to_writer(&mut w)
+        .with_context(|| format!("Error writing output file {}", args.output.display()))?;
+    w.flush()?;
+    Ok(())
+}
+
+fn slice_index(
+    index: Index,
+    filter: &DocumentFilter,
+    symbols: &[String],
+    language_of: impl Fn(&Document) -> LanguageId,
+) -> Index {
+    let (kept, dropped): (Vec<_>, Vec<_>) = index.documents.into_iter().partition(|doc| {
+        filter.matches(&doc.relative_path, language_of(doc))
+            && (symbols.is_empty()
+                || doc.occurrences.iter().any(|occ| {
+                    symbols
+                        .iter()
+                        .any(|symbol| occ.symbol.contains(symbol.as_str()))
+                }))
+    });
+
+    // Global symbols with SymbolInformation outside the kept documents.
+    let mut available: HashMap<String, SymbolInformation> = HashMap::new();
+    let infos = dropped
+        .into_iter()
+        .flat_map(|doc| doc.symbols)
+        .chain(index.external_symbols);
+    for info in infos {
+        if !is_global(&info.symbol) {
+            continue;
+        }
+        match available.get_mut(&info.symbol) {
+            Some(into) => merge_symbol_information(into, info),
+            None => {
+                available.insert(info.symbol.clone(), info);
+            }
+        }
+    }
+
+    let described: HashSet<&str> = kept
+        .iter()
+        .flat_map(|doc| &doc.symbols)
+        .map(|info| info.symbol.as_str())
+        .collect();
+    let mut to_visit: Vec<String> = kept
+        .iter()
+        .flat_map(|doc| {
+            let occurrences = doc.occurrences.iter().map(|occ| &occ.symbol);
+            let related = doc.symbols.iter().flat_map(referenced_symbols);
+            occurrences.chain(related)
+        })
+        .filter(|symbol| is_global(symbol) && !described.contains(symbol.as_str()))
+        .cloned()
+        .collect();
+    let mut external_symbols = BTreeMap::new();
+    while let Some(symbol) = to_visit.pop() {
+        if let Some(info) = available.remove(&symbol) {
+            to_visit.extend(
+                referenced_symbols(&info)
+                    .filter(|symbol| !described.contains(symbol.as_str()))
+                    .cloned(),
+            );
+            external_symbols.insert(symbol, info);
+        }
+    }
+
+    let mut reduced = Index::new();
+    reduced.metadata = index.metadata;
+    reduced.documents = kept;
+    reduced.external_symbols = external_symbols.into_values().collect();
+    reduced
+}
+
+fn is_global(symbol: &str) -> bool {
+    !symbol.is_empty() && matches!(parse_scip_symbol(symbol), ScipSymbol::Global { .. })
+}
+
+/// Symbols `info` points at through relationships or its enclosing symbol.
+fn referenced_symbols(info: &SymbolInformation) -> impl Iterator<Item = &String> {
+    info.relationships
+        .iter()
+        .map(|rel| &rel.symbol)
+        .chain(Some(&info.enclosing_symbol).filter(|symbol| !symbol.is_empty()))
+}
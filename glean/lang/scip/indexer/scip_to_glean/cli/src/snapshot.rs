@@ -0,0 +1,459 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! `scip-to-glean snapshot`: print each document's source with its
+//! occurrences marked underneath, like `scip snapshot`, for reviewing what
+//! an indexer emitted and for human-readable golden tests.
+//!
+//! Source lines are indented by the width of the language's line comment,
+//! so the markers below line up with the columns they annotate:
+//!
+//! ```text
+//!   func Foo() {}
+//! //     ^^^ definition scip-go gomod m v1 `m`/Foo().
+//! ```
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Document;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Occurrence;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::PositionEncoding;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::SymbolRole;
+use tracing::info;
+use tracing::warn;
+
+use crate::angle::Env;
+use crate::angle::occurrence_enclosing_range;
+use crate::angle::occurrence_range;
+use crate::filter::DocumentFilterArgs;
+use crate::lsif::LanguageId;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Document;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Occurrence;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::PositionEncoding;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::SymbolRole;
+use crate::read_scip_file;
+use crate::sources::SourceProvider;
+
+#[derive(clap::Args, Debug)]
+pub struct SnapshotArgs {
+    #[arg(short, long)]
+    pub input: PathBuf,
+
+    #[arg(
+        short,
+        long,
+        help = "Write one snapshot per document to <output>/<relative-path> instead of printing them to stdout"
+    )]
+    pub output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Root directory for source files of documents without text, read from <source-root>/<relative-path>"
+    )]
+    pub source_root: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub filter: DocumentFilterArgs,
+
+    #[arg(
+        long,
+        help = "Infer the language of documents that do not set one from their file name or extension"
+    )]
+    pub infer_language: bool,
+
+    #[arg(
+        long,
+        help = "The default language to use for files without a recognized extension."
+    )]
+    pub language: Option<String>,
+}
+
+pub fn snapshot(args: &SnapshotArgs) -> Result<()> {
+    let index = read_scip_file(&args.input)
+        .with_context(|| format!("Error opening input file {}", args.input.display()))?;
+    let filter = args.filter.build()?;
+    let default_language = args
+        .language
+        .as_ref()
+        .and_then(|s| LanguageId::new(s).known());
+    let env = Env::new();
+
+    let mut stdout = std::io::stdout().lock();
+    let mut rendered = 0;
+    for doc in &index.documents {
+        let lang = env.infer_lang_for_doc(
+            default_language,
+            args.infer_language,
+            doc,
+            args.source_root
+                .as_ref()
+                .map(|root| root as &dyn SourceProvider),
+        );
+        if !filter.matches(&doc.relative_path, lang) {
+            continue;
+        }
+        let text = if !doc.text.is_empty() {
+            doc.text.clone()
+        } else if let Some(source_root) = &args.source_root {
+            let path = source_root.join(&doc.relative_path);
+            match std::fs::read(&path) {
+                Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                Err(err) => {
+                    warn!(
+                        "Skipping {}: cannot read {}: {}",
+                        doc.relative_path,
+                        path.display(),
+                        err
+                    );
+                    continue;
+                }
+            }
+        } else {
+            warn!(
+                "Skipping {}: document has no text, pass --source-root",
+                doc.relative_path
+            );
+            continue;
+        };
+        let snapshot = render_document(doc, &text, comment_prefix(lang));
+
+        match &args.output {
+            Some(dir) => {
+                let Some(path) = output_path(dir, &doc.relative_path) else {
+                    warn!(
+                        "Skipping {}: path escapes the output directory",
+                        doc.relative_path
+                    );
+                    continue;
+                };
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let mut file = File::create(&path)
+                    .with_context(|| format!("Error creating snapshot {}", path.display()))?;
+                file.write_all(snapshot.as_bytes())?;
+            }
+            None => {
+                writeln!(stdout, "{} {}", comment_prefix(lang), doc.relative_path)?;
+                stdout.write_all(snapshot.as_bytes())?;
+            }
+        }
+        rendered += 1;
+    }
+    info!(
+        "Rendered {} of {} documents",
+        rendered,
+        index.documents.len()
+    );
+    Ok(())
+}
+
+/// `<dir>/<relative_path>`, unless the path is absolute or climbs out of
+/// `dir`.
+fn output_path(dir: &Path, relative_path: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative_path);
+    relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        .then(|| dir.join(relative))
+}
+
+/// The line comment syntax markers are written behind.
+fn comment_prefix(lang: LanguageId) -> &'static str {
+    use LanguageId::*;
+    match lang {
+        Python | Ruby | Shell | Perl | Perl6 | R | Powershell | YAML | Makefile | Dockerfile
+        | Elixir | Ini => "#",
+        Haskell | Lua | SQL => "--",
+        Erlang | LaTeX | TeX | BibTeX => "%",
+        Clojure => ";;",
+        WindowsBat => "::",
+        VisualBasic => "'",
+        _ => "//",
+    }
+}
+
+/// Render `text` with a marker line under each occurrence, in the order
+/// occurrences start. Occurrences past the end of `text`, and those without a
+/// usable range, are listed at the end, so they are visible rather than
+/// dropped.
+pub fn render_document(doc: &Document, text: &str, comment: &str) -> String {
+    let encoding = doc
+        .position_encoding
+        .enum_value()
+        .unwrap_or(PositionEncoding::UnspecifiedPositionEncoding);
+    let lines: Vec<&str> = text.lines().collect();
+    let mut occurrences = Vec::new();
+    let mut rangeless = Vec::new();
+    for occ in &doc.occurrences {
+        let range = occurrence_range(occ);
+        let start = match range[..] {
+            [line, column, ..] => usize::try_from(line).ok().zip(usize::try_from(column).ok()),
+            _ => None,
+        };
+        match start {
+            Some((line, column)) => occurrences.push((line, column, range, occ)),
+            None => rangeless.push((range, occ)),
+        }
+    }
+    // Definitions first among occurrences at the same position.
+    occurrences.sort_by_key(|(line, column, _, occ)| {
+        (*line, *column, !is_role(occ, SymbolRole::Definition))
+    });
+
+    let indent = " ".repeat(comment.len());
+    let mut out = String::new();
+    let mut next = occurrences.iter().peekable();
+    for (idx, line) in lines.iter().enumerate() {
+        writeln!(out, "{}{}", indent, line).unwrap();
+        while let Some((_, column, range, occ)) = next.next_if(|(l, _, _, _)| *l == idx) {
+            let start = char_column(line, *column, encoding);
+            let end = match range[..] {
+                [_, _, end] => char_column(line, usize::try_from(end).unwrap_or(0), encoding),
+                _ => line.chars().count(),
+            };
+            let width = end.saturating_sub(start).max(1);
+            writeln!(
+                out,
+                "{}{}{} {}",
+                comment,
+                " ".repeat(start),
+                "^".repeat(width),
+                describe(occ, range)
+            )
+            .unwrap();
+        }
+    }
+    for (line, column, range, occ) in next {
+        writeln!(
+            out,
+            "{} out of bounds {}:{} {}",
+            comment,
+            line,
+            column,
+            describe(occ, range)
+        )
+        .unwrap();
+    }
+    for (range, occ) in rangeless {
+        writeln!(
+            out,
+            "{} no range {:?} {}",
+            comment,
+            range,
+            describe(occ, &range)
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// The character index of the code unit offset `column` in `line`.
+fn char_column(line: &str, column: usize, encoding: PositionEncoding) -> usize {
+    let unit_len = |c: char| match encoding {
+        PositionEncoding::UTF16CodeUnitOffsetFromLineStart => c.len_utf16(),
+        PositionEncoding::UTF32CodeUnitOffsetFromLineStart => 1,
+        // Most indexers leave the encoding unset and count bytes.
+        _ => c.len_utf8(),
+    };
+    let mut units = 0;
+    for (idx, c) in line.chars().enumerate() {
+        if units >= column {
+            return idx;
+        }
+        units += unit_len(c);
+    }
+    // Past the end of the line, keep counting one character per unit.
+    line.chars().count() + column.saturating_sub(units)
+}
+
+fn is_role(occ: &Occurrence, role: SymbolRole) -> bool {
+    occ.symbol_roles & role as i32 != 0
+}
+
+/// `definition` or `reference`, the other roles set, the symbol, where a
+/// multi-line `range` ends, and the enclosing range, if any.
+fn describe(occ: &Occurrence, range: &[i32]) -> String {
+    let mut description = String::from(if is_role(occ, SymbolRole::Definition) {
+        "definition"
+    } else {
+        "reference"
+    });
+    let roles: Vec<&str> = [
+        (SymbolRole::Import, "import"),
+        (SymbolRole::WriteAccess, "write"),
+        (SymbolRole::ReadAccess, "read"),
+        (SymbolRole::Generated, "generated"),
+        (SymbolRole::Test, "test"),
+        (SymbolRole::ForwardDefinition, "forward"),
+    ]
+    .into_iter()
+    .filter(|(role, _)| is_role(occ, *role))
+    .map(|(_, name)| name)
+    .collect();
+    if !roles.is_empty() {
+        write!(description, "[{}]", roles.join(",")).unwrap();
+    }
+    write!(description, " {}", occ.symbol).unwrap();
+    if let [_, _, end_line, end_column] = range[..] {
+        write!(description, " (ends {}:{})", end_line, end_column).unwrap();
+    }
+    match occurrence_enclosing_range(occ)[..] {
+        [line, start, end] => write!(
+            description,
+            " (encloses {}:{}-{}:{})",
+            line, start, line, end
+        )
+        .unwrap(),
+        [start_line, start, end_line, end] => write!(
+            description,
+            " (encloses {}:{}-{}:{})",
+            start_line, start, end_line, end
+        )
+        .unwrap(),
+        _ => {}
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "facebook")]
+    use proto_rust::scip::MultiLineRange;
+    #[cfg(feature = "facebook")]
+    use proto_rust::scip::SingleLineRange;
+
+    use super::*;
+    #[cfg(not(feature = "facebook"))]
+    use crate::proto::scip::MultiLineRange;
+    #[cfg(not(feature = "facebook"))]
+    use crate::proto::scip::SingleLineRange;
+
+    fn occurrence(symbol: &str, range: Vec<i32>, roles: i32) -> Occurrence {
+        let mut occ = Occurrence::new();
+        occ.symbol = symbol.to_string();
+        occ.range = range;
+        occ.symbol_roles = roles;
+        occ
+    }
+
+    #[test]
+    fn test_render_document() {
+        let mut doc = Document::new();
+        doc.occurrences = vec![
+            occurrence("local 0", vec![2, 4, 5], SymbolRole::ReadAccess as i32),
+            occurrence("pkg/Foo().", vec![0, 5, 8], 1),
+            occurrence(
+                "local 0",
+                vec![1, 1, 2, 5],
+                1 | SymbolRole::WriteAccess as i32,
+            ),
+            occurrence("pkg/Gone.", vec![7, 0, 1], 0),
+        ];
+        let text = "func Foo() {\n\tx := 1\n\t_ = x\n}\n";
+        assert_eq!(
+            render_document(&doc, text, "//"),
+            "  func Foo() {
+//     ^^^ definition pkg/Foo().
+  \tx := 1
+// ^^^^^^ definition[write] local 0 (ends 2:5)
+  \t_ = x
+//    ^ reference[read] local 0
+  }
+// out of bounds 7:0 reference pkg/Gone.
+"
+        );
+    }
+
+    #[test]
+    fn test_render_document_typed_ranges() {
+        let mut single = occurrence("pkg/Foo().", vec![], 1);
+        let mut slr = SingleLineRange::new();
+        slr.line = 0;
+        slr.start_character = 5;
+        slr.end_character = 8;
+        single.set_single_line_range(slr);
+        let mut enclosing = MultiLineRange::new();
+        enclosing.start_line = 0;
+        enclosing.start_character = 0;
+        enclosing.end_line = 1;
+        enclosing.end_character = 1;
+        single.set_multi_line_enclosing_range(enclosing);
+        let mut multi = occurrence("local 0", vec![], 0);
+        let mut mlr = MultiLineRange::new();
+        mlr.start_line = 0;
+        mlr.start_character = 0;
+        mlr.end_line = 1;
+        mlr.end_character = 1;
+        multi.set_multi_line_range(mlr);
+        let mut doc = Document::new();
+        doc.occurrences = vec![
+            single,
+            multi,
+            occurrence("pkg/Bar.", vec![], 0),
+            occurrence("pkg/Baz.", vec![-1, 0, 2], 0),
+        ];
+        assert_eq!(
+            render_document(&doc, "func Foo() {\n}\n", "//"),
+            "  func Foo() {
+//^^^^^^^^^^^^ reference local 0 (ends 1:1)
+//     ^^^ definition pkg/Foo(). (encloses 0:0-1:1)
+  }
+// no range [] reference pkg/Bar.
+// no range [-1, 0, 2] reference pkg/Baz.
+"
+        );
+    }
+
+    #[test]
+    fn test_char_column_respects_encoding() {
+        let line = "é𝄞x";
+        assert_eq!(
+            char_column(line, 6, PositionEncoding::UTF8CodeUnitOffsetFromLineStart),
+            2
+        );
+        assert_eq!(
+            char_column(line, 3, PositionEncoding::UTF16CodeUnitOffsetFromLineStart),
+            2
+        );
+        assert_eq!(
+            char_column(line, 2, PositionEncoding::UTF32CodeUnitOffsetFromLineStart),
+            2
+        );
+        assert_eq!(
+            char_column(line, 5, PositionEncoding::UTF32CodeUnitOffsetFromLineStart),
+            5
+        );
+    }
+
+    #[test]
+    fn test_output_path_stays_in_directory() {
+        let dir = Path::new("/out");
+        assert_eq!(
+            output_path(dir, "a/b.go"),
+            Some(PathBuf::from("/out/a/b.go"))
+        );
+        assert_eq!(output_path(dir, "../b.go"), None);
+        assert_eq!(output_path(dir, "/etc/passwd"), None);
+    }
+}
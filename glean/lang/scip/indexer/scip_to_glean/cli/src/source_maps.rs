@@ -0,0 +1,414 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Attributing ranges in compiled JavaScript to the sources it was compiled
+//! from, with version 3 source maps.
+//!
+//! A document's map is named by its last `//# sourceMappingURL=` comment:
+//! a base64 `data:` URL, or a path relative to the document. Without one, a
+//! sibling `<document>.map` is used. Map files are read from the sources of
+//! the input, see `crate::sources`. Source paths are resolved against the
+//! map's location and `sourceRoot`; a URL scheme such as `webpack://` is
+//! dropped.
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use base64::Engine;
+use tracing::warn;
+
+use crate::GleanRange;
+use crate::line_directives::LineRemapper;
+use crate::lsif::LanguageId;
+use crate::paths::EscapingPaths;
+use crate::paths::normalize_path;
+use crate::sources::SourceProvider;
+
+const BASE64_DIGITS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A generated position and the source position it was compiled from, all
+/// 0-based.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Mapping {
+    generated_column: u32,
+    source: u32,
+    line: u32,
+    column: u32,
+}
+
+#[derive(Debug)]
+pub struct SourceMap {
+    /// Source paths, relative to the directory of the generated file.
+    /// `None` for sources outside of it, e.g. absolute paths.
+    sources: Vec<Option<String>>,
+    /// The mappings of each generated line, by generated column.
+    lines: Vec<Vec<Mapping>>,
+}
+
+/// The source map of a document of language `lang`, if it has one.
+pub fn source_map_remapper(
+    lang: LanguageId,
+    sources: Option<&dyn SourceProvider>,
+    relative_path: &str,
+    source: &[u8],
+) -> Option<Box<dyn LineRemapper>> {
+    if !matches!(
+        lang,
+        LanguageId::JavaScript
+            | LanguageId::JavaScriptReact
+            | LanguageId::TypeScript
+            | LanguageId::TypeScriptReact
+    ) {
+        return None;
+    }
+    match SourceMap::load(sources, relative_path, source) {
+        Ok(map) => map.map(|map| Box::new(map) as Box<dyn LineRemapper>),
+        Err(err) => {
+            warn!("Ignoring source map of {}: {:#}", relative_path, err);
+            None
+        }
+    }
+}
+
+impl SourceMap {
+    /// The map the document at `relative_path` with contents `source` names,
+    /// or its sibling `.map` file. `Ok(None)` if there is neither.
+    pub fn load(
+        sources: Option<&dyn SourceProvider>,
+        relative_path: &str,
+        source: &[u8],
+    ) -> Result<Option<SourceMap>> {
+        let text = String::from_utf8_lossy(source);
+        let url = text
+            .lines()
+            .rev()
+            .find_map(|line| {
+                let line = line.trim();
+                line.strip_prefix("//# sourceMappingURL=")
+                    .or_else(|| line.strip_prefix("//@ sourceMappingURL="))
+            })
+            .map(str::trim);
+        let (json, map_dir) = match url {
+            Some(url) if url.starts_with("data:") => {
+                let (_, data) = url
+                    .split_once(";base64,")
+                    .ok_or_else(|| anyhow!("Only base64 data URLs are supported"))?;
+                let json = base64::engine::general_purpose::STANDARD
+                    .decode(data)
+                    .context("Invalid base64 in data URL")?;
+                (json, "")
+            }
+            Some(url) => {
+                let Some(sources) = sources else {
+                    return Ok(None);
+                };
+                let path =
+                    normalize_path(&join(directory(relative_path), url), EscapingPaths::Skip)
+                        .ok_or_else(|| {
+                            anyhow!("Source map `{}` is outside the source root", url)
+                        })?;
+                let json = sources
+                    .read(&path)
+                    .with_context(|| format!("Cannot read source map {}", path))?;
+                (json, directory(url))
+            }
+            None => {
+                let Some(sources) = sources else {
+                    return Ok(None);
+                };
+                match sources.read(&format!("{}.map", relative_path)) {
+                    Ok(json) => (json, ""),
+                    Err(_) => return Ok(None),
+                }
+            }
+        };
+        Self::parse(&json, map_dir).map(Some)
+    }
+
+    /// Parse the JSON map `json`, whose directory relative to the generated
+    /// file is `map_dir`.
+    pub fn parse(json: &[u8], map_dir: &str) -> Result<SourceMap> {
+        let map: serde_json::Value = serde_json::from_slice(json).context("Invalid JSON")?;
+        if map["version"] != 3 {
+            bail!("Unsupported source map version {}", map["version"]);
+        }
+        if map.get("sections").is_some() {
+            bail!("Indexed source maps are not supported");
+        }
+        let source_root = map["sourceRoot"].as_str().unwrap_or("");
+        let sources = map["sources"]
+            .as_array()
+            .ok_or_else(|| anyhow!("Missing `sources`"))?
+            .iter()
+            .map(|source| {
+                let source = source.as_str()?;
+                let path = join(
+                    &join(map_dir, strip_scheme(source_root)),
+                    strip_scheme(source),
+                );
+                if path.starts_with('/') {
+                    return None;
+                }
+                normalize_path(&path, EscapingPaths::Keep)
+            })
+            .collect();
+        let mappings = map["mappings"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Missing `mappings`"))?;
+        Ok(SourceMap {
+            sources,
+            lines: parse_mappings(mappings)?,
+        })
+    }
+
+    /// The source mapping covering the 0-based generated position.
+    fn lookup(&self, line: u64, column: u64) -> Option<Mapping> {
+        let mappings = self.lines.get(usize::try_from(line).ok()?)?;
+        let idx = mappings.partition_point(|m| u64::from(m.generated_column) <= column);
+        mappings.get(idx.checked_sub(1)?).copied()
+    }
+}
+
+impl LineRemapper for SourceMap {
+    /// Generated ranges are physical already.
+    fn remap_range(&self, range: GleanRange, _symbol_hint: Option<&str>) -> GleanRange {
+        range
+    }
+
+    fn original_location(
+        &self,
+        range: &GleanRange,
+        _symbol_hint: Option<&str>,
+    ) -> Option<(&str, GleanRange)> {
+        let line = range.line_begin.checked_sub(1)?;
+        let column = range.column_begin.checked_sub(1)?;
+        let start = self.lookup(line, column)?;
+        let source = self.sources.get(start.source as usize)?.as_deref()?;
+        let line_begin = u64::from(start.line) + 1;
+        let column_begin = u64::from(start.column) + column - u64::from(start.generated_column) + 1;
+
+        // Map the last character too, if it is in the same source and after
+        // the start. Otherwise keep the generated length.
+        let end_column = range.column_end.saturating_sub(1);
+        let end = self
+            .lookup(range.line_end.saturating_sub(1), end_column)
+            .filter(|end| end.source == start.source)
+            .map(|end| {
+                (
+                    u64::from(end.line) + 1,
+                    u64::from(end.column) + end_column - u64::from(end.generated_column) + 1,
+                )
+            })
+            .filter(|end| *end >= (line_begin, column_begin));
+        let (line_end, column_end) = end.unwrap_or_else(|| {
+            let width = if range.line_end == range.line_begin {
+                range.column_end.saturating_sub(range.column_begin)
+            } else {
+                0
+            };
+            (line_begin, column_begin + width)
+        });
+        Some((
+            source,
+            GleanRange {
+                line_begin,
+                column_begin,
+                line_end,
+                column_end,
+            },
+        ))
+    }
+
+    fn always_attribute(&self) -> bool {
+        true
+    }
+}
+
+/// Decode the `mappings` field: `;`-separated generated lines of
+/// `,`-separated base64 VLQ segments, each relative to the previous one.
+fn parse_mappings(mappings: &str) -> Result<Vec<Vec<Mapping>>> {
+    let mut lines = Vec::new();
+    let (mut source, mut line, mut column) = (0i64, 0i64, 0i64);
+    for generated_line in mappings.split(';') {
+        let mut generated_column = 0i64;
+        let mut segments = Vec::new();
+        for segment in generated_line.split(',').filter(|s| !s.is_empty()) {
+            let fields = decode_vlq(segment)
+                .ok_or_else(|| anyhow!("Invalid mapping segment `{}`", segment))?;
+            generated_column += fields[0];
+            // One-field segments map to no source.
+            if let [_, source_delta, line_delta, column_delta, ..] = fields[..] {
+                source += source_delta;
+                line += line_delta;
+                column += column_delta;
+                segments.push(Mapping {
+                    generated_column: u32::try_from(generated_column)?,
+                    source: u32::try_from(source)?,
+                    line: u32::try_from(line)?,
+                    column: u32::try_from(column)?,
+                });
+            }
+        }
+        segments.sort_by_key(|m| m.generated_column);
+        lines.push(segments);
+    }
+    Ok(lines)
+}
+
+/// The signed values of a base64 VLQ segment.
+fn decode_vlq(segment: &str) -> Option<Vec<i64>> {
+    let mut values = Vec::new();
+    let mut value = 0i64;
+    let mut shift = 0;
+    for byte in segment.bytes() {
+        let digit = BASE64_DIGITS.iter().position(|d| *d == byte)? as i64;
+        value += (digit & 0b11111) << shift;
+        if digit & 0b100000 != 0 {
+            shift += 5;
+            if shift > 60 {
+                return None;
+            }
+        } else {
+            values.push(if value & 1 == 1 {
+                -(value >> 1)
+            } else {
+                value >> 1
+            });
+            value = 0;
+            shift = 0;
+        }
+    }
+    (shift == 0 && !values.is_empty()).then_some(values)
+}
+
+fn directory(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+fn join(dir: &str, path: &str) -> String {
+    if dir.is_empty() || path.starts_with('/') {
+        path.to_owned()
+    } else {
+        format!("{}/{}", dir.trim_end_matches('/'), path)
+    }
+}
+
+/// `path` without a `scheme://` prefix and the slashes after it.
+fn strip_scheme(path: &str) -> &str {
+    match path.split_once("://") {
+        Some((_, rest)) => rest.trim_start_matches('/'),
+        None => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(line_begin: u64, column_begin: u64, line_end: u64, column_end: u64) -> GleanRange {
+        GleanRange {
+            line_begin,
+            column_begin,
+            line_end,
+            column_end,
+        }
+    }
+
+    #[test]
+    fn test_decode_vlq() {
+        assert_eq!(decode_vlq("AAAA"), Some(vec![0, 0, 0, 0]));
+        assert_eq!(decode_vlq("SAAQ"), Some(vec![9, 0, 0, 8]));
+        assert_eq!(decode_vlq("D"), Some(vec![-1]));
+        assert_eq!(decode_vlq("2HwB"), Some(vec![123, 24]));
+        assert_eq!(decode_vlq("g"), None);
+        assert_eq!(decode_vlq("A!"), None);
+    }
+
+    #[test]
+    fn test_original_location() {
+        // `function greet() {}` on line 2 of ../src/greet.ts, compiled to
+        // line 1 with the name at column 9 in both.
+        let map = SourceMap::parse(
+            br#"{"version":3,"sourceRoot":"","sources":["../src/greet.ts"],"names":[],"mappings":";AACA,SAAS,KAAK"}"#,
+            "",
+        )
+        .unwrap();
+        assert_eq!(map.sources, vec![Some("../src/greet.ts".to_string())]);
+        let (source, original) = map.original_location(&range(2, 10, 2, 14), None).unwrap();
+        assert_eq!(source, "../src/greet.ts");
+        assert_eq!(original, range(2, 10, 2, 14));
+        assert_eq!(map.original_location(&range(1, 1, 1, 1), None), None);
+        assert_eq!(
+            map.remap_range(range(2, 10, 2, 14), None),
+            range(2, 10, 2, 14)
+        );
+    }
+
+    #[test]
+    fn test_source_paths() {
+        let map = SourceMap::parse(
+            br#"{"version":3,"sourceRoot":"webpack:///","sources":["./src/a.ts","/abs/b.ts"],"mappings":""}"#,
+            "maps",
+        )
+        .unwrap();
+        assert_eq!(map.sources, vec![Some("maps/src/a.ts".to_string()), None]);
+        let map = SourceMap::parse(
+            br#"{"version":3,"sourceRoot":"lib","sources":["webpack:///b.ts","../c.ts"],"mappings":""}"#,
+            "",
+        )
+        .unwrap();
+        assert_eq!(
+            map.sources,
+            vec![Some("lib/b.ts".to_string()), Some("c.ts".to_string())]
+        );
+        assert!(SourceMap::parse(br#"{"version":2,"sources":[],"mappings":""}"#, "").is_err());
+    }
+
+    #[test]
+    fn test_load_inline_and_sibling_maps() {
+        let json = br#"{"version":3,"sources":["a.ts"],"mappings":"AAAA"}"#;
+        let inline = format!(
+            "x;\n//# sourceMappingURL=data:application/json;charset=utf-8;base64,{}\n",
+            base64::engine::general_purpose::STANDARD.encode(json)
+        );
+        let map = SourceMap::load(None, "dist/a.js", inline.as_bytes())
+            .unwrap()
+            .unwrap();
+        assert_eq!(map.sources, vec![Some("a.ts".to_string())]);
+
+        let root = tempfile::tempdir().unwrap();
+        let root_dir = root.path().to_path_buf();
+        let sources: Option<&dyn SourceProvider> = Some(&root_dir);
+        std::fs::create_dir_all(root.path().join("dist/maps")).unwrap();
+        std::fs::write(root.path().join("dist/maps/a.js.map"), json).unwrap();
+        let named = b"x;\n//# sourceMappingURL=maps/a.js.map\n";
+        let map = SourceMap::load(sources, "dist/a.js", named)
+            .unwrap()
+            .unwrap();
+        assert_eq!(map.sources, vec![Some("maps/a.ts".to_string())]);
+
+        std::fs::write(root.path().join("dist/b.js.map"), json).unwrap();
+        assert!(
+            SourceMap::load(sources, "dist/b.js", b"x;\n")
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            SourceMap::load(sources, "dist/c.js", b"x;\n")
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            SourceMap::load(None, "dist/b.js", b"x;\n")
+                .unwrap()
+                .is_none()
+        );
+    }
+}
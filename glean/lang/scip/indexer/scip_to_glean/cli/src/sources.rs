@@ -0,0 +1,473 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Where the contents of documents without `text` are read from, for
+//! `src.FileLines`, line directives and source maps: a directory
+//! (`--source-root`), a tar archive (`--source-archive`), or a tree of a Git
+//! repository (`--source-tree`). The repository is the one containing
+//! `--source-root`, or `--source-repo`, which may be bare. Files are found
+//! in a `--source-repo` tree at the project root's path in the repository,
+//! i.e. relative to `--repo-root`, or at the top if that is not given.
+//!
+//! Files that cannot be read are recorded in the conversion stats, and
+//! `MissingSourcesPolicy` decides whether too many of them fail the
+//! conversion, since a misconfigured source root otherwise just yields
+//! facts without line information.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::ChildStdin;
+use std::process::ChildStdout;
+use std::process::Command;
+use std::process::Stdio;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+
+use crate::stats::ConversionStats;
+
+pub trait SourceProvider {
+    /// The contents of the file at `relative_path`. Fails with
+    /// `ErrorKind::NotFound` if there is no such file.
+    fn read(&self, relative_path: &str) -> std::io::Result<Vec<u8>>;
+}
+
+/// Files under a directory.
+impl SourceProvider for PathBuf {
+    fn read(&self, relative_path: &str) -> std::io::Result<Vec<u8>> {
+        std::fs::read(self.join(relative_path))
+    }
+}
+
+/// Open the sources of an input: `archive` if given, else Git tree-ish
+/// `tree` of `repo` (the repository, and the project root's directory in it)
+/// or of the repository containing `source_root`, else `source_root`.
+pub fn open_sources(
+    source_root: Option<&Path>,
+    archive: Option<&Path>,
+    repo: Option<(&Path, &str)>,
+    tree: Option<&str>,
+) -> Result<Option<Box<dyn SourceProvider>>> {
+    Ok(match (archive, repo, source_root, tree) {
+        (Some(archive), _, _, _) => Some(Box::new(TarArchive::open(archive)?)),
+        (None, Some((git_dir, dir_in_repo)), _, Some(tree)) => {
+            Some(Box::new(GitTree::open_repo(git_dir, tree, dir_in_repo)?))
+        }
+        (None, None, Some(source_root), Some(tree)) => {
+            Some(Box::new(GitTree::open(source_root, tree)?))
+        }
+        (None, None, Some(source_root), None) => Some(Box::new(source_root.to_path_buf())),
+        (None, Some(_), _, None) => bail!("--source-repo needs --source-tree"),
+        (None, None, None, Some(_)) => {
+            bail!("--source-tree needs --source-repo or a source root in a Git repository")
+        }
+        (None, _, None, None) => None,
+    })
+}
+
+/// The regular files of an uncompressed tar archive, read into memory.
+pub struct TarArchive {
+    files: HashMap<String, Vec<u8>>,
+}
+
+const TAR_BLOCK: usize = 512;
+
+impl TarArchive {
+    pub fn open(path: &Path) -> Result<TarArchive> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Error reading source archive {}", path.display()))?;
+        Self::parse(&bytes).with_context(|| format!("Invalid source archive {}", path.display()))
+    }
+
+    /// Parse ustar archives, with GNU and pax long names.
+    pub fn parse(bytes: &[u8]) -> Result<TarArchive> {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            bail!("Compressed archives are not supported, decompress it first");
+        }
+        let mut files = HashMap::new();
+        let mut long_name: Option<String> = None;
+        let mut offset = 0;
+        while offset + TAR_BLOCK <= bytes.len() {
+            let header = &bytes[offset..offset + TAR_BLOCK];
+            if header.iter().all(|b| *b == 0) {
+                break;
+            }
+            if &header[257..262] != b"ustar" {
+                bail!("Not a ustar archive at offset {}", offset);
+            }
+            let size = parse_octal(&header[124..136])
+                .ok_or_else(|| anyhow!("Invalid size at offset {}", offset))?;
+            let start = offset + TAR_BLOCK;
+            let data = bytes
+                .get(start..start + size)
+                .ok_or_else(|| anyhow!("Truncated entry at offset {}", offset))?;
+            offset = start + size.div_ceil(TAR_BLOCK) * TAR_BLOCK;
+
+            match header[156] {
+                // GNU long name of the next entry.
+                b'L' => long_name = Some(c_string(data).to_owned()),
+                // pax extended header of the next entry.
+                b'x' => {
+                    if let Some(path) = pax_path(data) {
+                        long_name = Some(path.to_owned());
+                    }
+                }
+                b'0' | 0 => {
+                    let name = long_name.take().unwrap_or_else(|| {
+                        let name = c_string(&header[..100]);
+                        match c_string(&header[345..500]) {
+                            "" => name.to_owned(),
+                            prefix => format!("{}/{}", prefix, name),
+                        }
+                    });
+                    files.insert(name.trim_start_matches("./").to_owned(), data.to_vec());
+                }
+                _ => long_name = None,
+            }
+        }
+        Ok(TarArchive { files })
+    }
+}
+
+impl SourceProvider for TarArchive {
+    fn read(&self, relative_path: &str) -> std::io::Result<Vec<u8>> {
+        self.files
+            .get(relative_path)
+            .cloned()
+            .ok_or_else(|| ErrorKind::NotFound.into())
+    }
+}
+
+fn parse_octal(field: &[u8]) -> Option<usize> {
+    let digits = c_string(field).trim_matches(' ');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    usize::from_str_radix(digits, 8).ok()
+}
+
+fn c_string(field: &[u8]) -> &str {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    std::str::from_utf8(&field[..end]).unwrap_or("")
+}
+
+/// The `path` of pax records: `<length> <key>=<value>\n`.
+fn pax_path(data: &[u8]) -> Option<&str> {
+    std::str::from_utf8(data)
+        .ok()?
+        .split_terminator('\n')
+        .find_map(|record| record.split_once(' ')?.1.strip_prefix("path="))
+}
+
+/// Files of a tree of a Git repository, read with `git cat-file --batch`.
+pub struct GitTree {
+    /// The tree, and the path of the source root in it, e.g. `abc123:src/`.
+    prefix: String,
+    batch: RefCell<GitBatch>,
+}
+
+struct GitBatch {
+    // Kept so the process is waited on when dropped.
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Drop for GitBatch {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl GitTree {
+    /// The tree `tree` (e.g. a commit or branch) of the repository
+    /// containing `dir`, with paths relative to `dir`.
+    pub fn open(dir: &Path, tree: &str) -> Result<GitTree> {
+        let dir_in_tree = git(dir, &["rev-parse", "--show-prefix"])?;
+        Self::open_repo(dir, tree, &dir_in_tree)
+    }
+
+    /// The tree `tree` of the repository at `git_dir`, which may be bare,
+    /// with paths relative to its directory `dir_in_tree` (empty, or ending
+    /// in `/`).
+    pub fn open_repo(git_dir: &Path, tree: &str, dir_in_tree: &str) -> Result<GitTree> {
+        let tree_id = git(
+            git_dir,
+            &["rev-parse", "--verify", &format!("{}^{{tree}}", tree)],
+        )?;
+        Self::start(git_dir, format!("{}:{}", tree_id, dir_in_tree))
+    }
+
+    /// Read files from `<prefix><path>` objects of the repository at
+    /// `git_dir`.
+    fn start(git_dir: &Path, prefix: String) -> Result<GitTree> {
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(git_dir)
+            .args(["cat-file", "--batch"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("Failed to run git cat-file")?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(GitTree {
+            prefix,
+            batch: RefCell::new(GitBatch {
+                child,
+                stdin,
+                stdout,
+            }),
+        })
+    }
+}
+
+/// The trimmed output of `git <args>` run in `dir`.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "`git {}` failed in {}: {}",
+            args.join(" "),
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+impl SourceProvider for GitTree {
+    fn read(&self, relative_path: &str) -> std::io::Result<Vec<u8>> {
+        // Requests are newline-terminated.
+        if relative_path.contains('\n') {
+            return Err(ErrorKind::NotFound.into());
+        }
+        let batch = &mut *self.batch.borrow_mut();
+        writeln!(batch.stdin, "{}{}", self.prefix, relative_path)?;
+        batch.stdin.flush()?;
+
+        // `<oid> <type> <size>` and the contents, or `<object> missing`.
+        let mut header = String::new();
+        batch.stdout.read_line(&mut header)?;
+        let header = header.trim_end();
+        if header.ends_with(" missing") || header.ends_with(" ambiguous") {
+            return Err(ErrorKind::NotFound.into());
+        }
+        let fields: Vec<&str> = header.split(' ').collect();
+        let size: usize = match fields[..] {
+            [_, "blob", size] => size
+                .parse()
+                .map_err(|_| std::io::Error::other("Invalid git cat-file output"))?,
+            [_, kind, size] => {
+                // Skip a tree or other non-file object.
+                let size: u64 = size
+                    .parse()
+                    .map_err(|_| std::io::Error::other("Invalid git cat-file output"))?;
+                std::io::copy(
+                    &mut (&mut batch.stdout).take(size + 1),
+                    &mut std::io::sink(),
+                )?;
+                return Err(std::io::Error::other(format!("Not a file but a {}", kind)));
+            }
+            _ => return Err(std::io::Error::other("Invalid git cat-file output")),
+        };
+        let mut contents = vec![0; size + 1];
+        batch.stdout.read_exact(&mut contents)?;
+        contents.pop();
+        Ok(contents)
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingSources {
+    #[default]
+    Warn,
+    Fail,
+}
+
+#[derive(clap::Args, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MissingSourcesPolicy {
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = MissingSources::Warn,
+        help = "Whether to warn or fail when more than --max-missing-sources-percent of the source files of documents without text cannot be read"
+    )]
+    pub on_missing_sources: MissingSources,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        help = "The percentage of source files that may be missing or unreadable before --on-missing-sources applies"
+    )]
+    pub max_missing_sources_percent: u8,
+}
+
+impl MissingSourcesPolicy {
+    /// Summarize the source files `stats` records as unreadable, and fail if
+    /// there are too many of them.
+    pub fn check(&self, stats: &ConversionStats) -> Result<()> {
+        let missing = stats.source_files_missing.count + stats.source_files_unreadable.count;
+        let Some(example) = stats
+            .source_files_missing
+            .paths
+            .first()
+            .or(stats.source_files_unreadable.paths.first())
+        else {
+            return Ok(());
+        };
+        let summary = format!(
+            "{} of {} source files could not be read ({} missing, {} unreadable), e.g. `{}`",
+            missing,
+            stats.source_files_requested,
+            stats.source_files_missing.count,
+            stats.source_files_unreadable.count,
+            example
+        );
+        if missing * 100 <= stats.source_files_requested * self.max_missing_sources_percent as usize
+        {
+            tracing::info!("{}", summary);
+            return Ok(());
+        }
+        match self.on_missing_sources {
+            MissingSources::Warn => {
+                tracing::warn!("{}, check the source root", summary);
+                Ok(())
+            }
+            MissingSources::Fail => bail!("{}, check the source root", summary),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::PathSample;
+
+    /// A ustar archive of `files`.
+    fn tar(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        for (name, contents) in files {
+            let mut header = [0u8; TAR_BLOCK];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[124..135].copy_from_slice(format!("{:011o}", contents.len()).as_bytes());
+            header[156] = b'0';
+            header[257..263].copy_from_slice(b"ustar\0");
+            archive.extend_from_slice(&header);
+            archive.extend_from_slice(contents.as_bytes());
+            archive.resize(archive.len().div_ceil(TAR_BLOCK) * TAR_BLOCK, 0);
+        }
+        archive.resize(archive.len() + 2 * TAR_BLOCK, 0);
+        archive
+    }
+
+    #[test]
+    fn test_tar_archive() {
+        let long = format!("{}/a.go", "dir".repeat(40));
+        let mut bytes = tar(&[("././@LongLink", &long)]);
+        bytes.truncate(bytes.len() - 2 * TAR_BLOCK);
+        bytes[156] = b'L';
+        bytes.extend(tar(&[
+            ("placeholder", "package a\n"),
+            ("./b.go", "package b\n"),
+        ]));
+
+        let archive = TarArchive::parse(&bytes).unwrap();
+        assert_eq!(archive.read(&long).unwrap(), b"package a\n");
+        assert_eq!(archive.read("b.go").unwrap(), b"package b\n");
+        assert_eq!(
+            archive.read("c.go").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert!(TarArchive::parse(&[0x1f, 0x8b, 0]).is_err());
+        assert!(TarArchive::parse(&[1; TAR_BLOCK]).is_err());
+    }
+
+    #[test]
+    fn test_pax_path() {
+        assert_eq!(pax_path(b"20 path=src/long.go\n"), Some("src/long.go"));
+        assert_eq!(pax_path(b"16 mtime=123456\n"), None);
+    }
+
+    #[test]
+    fn test_git_tree() {
+        let repo = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(repo.path())
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        std::fs::create_dir(repo.path().join("src")).unwrap();
+        std::fs::write(repo.path().join("src/a.go"), "package a\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "a"]);
+        std::fs::write(repo.path().join("src/a.go"), "package edited\n").unwrap();
+
+        let tree = GitTree::open(&repo.path().join("src"), "HEAD").unwrap();
+        assert_eq!(tree.read("a.go").unwrap(), b"package a\n");
+        assert_eq!(tree.read("b.go").unwrap_err().kind(), ErrorKind::NotFound);
+        assert!(tree.read("").is_err());
+        assert_eq!(tree.read("a.go").unwrap(), b"package a\n");
+        assert!(GitTree::open(repo.path(), "no-such-rev").is_err());
+
+        let bare = tempfile::tempdir().unwrap();
+        git(&["clone", "-q", "--bare", ".", &bare.path().to_string_lossy()]);
+        let tree = GitTree::open_repo(bare.path(), "HEAD", "src/").unwrap();
+        assert_eq!(tree.read("a.go").unwrap(), b"package a\n");
+        let tree = GitTree::open_repo(bare.path(), "HEAD", "").unwrap();
+        assert_eq!(tree.read("src/a.go").unwrap(), b"package a\n");
+    }
+
+    #[test]
+    fn test_missing_sources_policy() {
+        let stats = ConversionStats {
+            source_files_requested: 4,
+            source_files_missing: PathSample {
+                count: 1,
+                paths: vec!["a.go".to_string()],
+            },
+            ..Default::default()
+        };
+        let policy = |on_missing_sources, max_missing_sources_percent| MissingSourcesPolicy {
+            on_missing_sources,
+            max_missing_sources_percent,
+        };
+        assert!(policy(MissingSources::Warn, 0).check(&stats).is_ok());
+        assert!(policy(MissingSources::Fail, 0).check(&stats).is_err());
+        assert!(policy(MissingSources::Fail, 25).check(&stats).is_ok());
+        assert!(
+            policy(MissingSources::Fail, 0)
+                .check(&ConversionStats::default())
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,148 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Counters describing what a conversion run did, written by `--stats-out`.
+
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Context;
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::lsif::LanguageId;
+use crate::output::GleanJSONOutput;
+
+/// How many example paths a `PathSample` keeps.
+const PATH_SAMPLE_LEN: usize = 100;
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionStats {
+    pub inputs: usize,
+    pub documents: usize,
+    /// Documents dropped because decoding them failed.
+    pub documents_skipped: usize,
+    /// Documents left out by `--include`, `--exclude` or `--only-language`.
+    pub documents_filtered: usize,
+    pub external_symbols: usize,
+    pub occurrences: usize,
+    pub occurrences_skipped_empty_symbol: usize,
+    /// Occurrences with neither a flat nor a typed range.
+    pub occurrences_skipped_missing_range: usize,
+    /// Occurrences dropped under `--skip-bad-occurrences`.
+    pub occurrences_skipped_error: usize,
+    /// Ranges (including enclosing ranges) that `decode_scip_range` had to
+    /// fix up, i.e. negative positions or inverted columns on one line.
+    /// Zero-width ranges are not counted.
+    pub ranges_clamped: usize,
+    /// Ranges outside their file's contents, found by `--validate-ranges`.
+    pub ranges_out_of_bounds: usize,
+    pub ranges_clipped: usize,
+    pub ranges_dropped: usize,
+    /// Occurrences emitted against the file a source map names, or a line
+    /// directive under `--attribute-to-directive-files`.
+    pub ranges_attributed_to_original_files: usize,
+    /// Documents dropped because their normalized path escapes its root.
+    pub paths_failed_normalization: usize,
+    /// Documents dropped by a `--rewrite` rule without replacement.
+    pub documents_dropped_by_rewrites: usize,
+    /// Document paths no `--rewrite` rule matched, kept unchanged.
+    pub paths_unmatched_by_rewrites: PathSample,
+    /// Files whose contents differ from `--expected-digests`, i.e. the
+    /// sources read are not those of the expected revision.
+    pub files_with_unexpected_digests: PathSample,
+    /// Files with known contents but no digest in `--expected-digests`.
+    pub files_without_expected_digests: usize,
+    /// Documents without text whose source was looked up under the source
+    /// root, and those whose source file does not exist or failed to read.
+    pub source_files_requested: usize,
+    pub source_files_missing: PathSample,
+    pub source_files_unreadable: PathSample,
+    /// Files whose contents `--emit-file-contents` left out, for exceeding
+    /// `--max-file-content-bytes` or not being text.
+    pub file_contents_too_large: usize,
+    pub file_contents_binary: usize,
+    pub total_facts: usize,
+    pub facts_by_predicate: BTreeMap<String, usize>,
+    /// Facts attributed to documents, keyed by the document's language.
+    pub facts_by_language: BTreeMap<String, usize>,
+    /// Facts attributed to documents, keyed by qualified file path.
+    pub facts_by_file: BTreeMap<String, usize>,
+}
+
+/// A count of paths, and the first of them as examples. Indexes with many
+/// documents would otherwise bloat the stats with every path.
+#[derive(Serialize, Debug, Default)]
+pub struct PathSample {
+    pub count: usize,
+    pub paths: Vec<String>,
+}
+
+impl PathSample {
+    pub fn push(&mut self, path: &str) {
+        self.count += 1;
+        if self.paths.len() < PATH_SAMPLE_LEN {
+            self.paths.push(path.to_owned());
+        }
+    }
+}
+
+impl ConversionStats {
+    /// Attribute `facts` newly emitted while decoding a document to its file
+    /// and language. Same-path documents accumulate.
+    pub fn record_document_facts(&mut self, filepath: &str, lang: LanguageId, facts: usize) {
+        *self.facts_by_file.entry(filepath.to_owned()).or_default() += facts;
+        *self
+            .facts_by_language
+            .entry(format!("{:?}", lang))
+            .or_default() += facts;
+    }
+
+    /// Record the final per-predicate counts of the complete output.
+    pub fn record_output(&mut self, output: &GleanJSONOutput) {
+        self.total_facts = output.total_facts_count();
+        self.facts_by_predicate = output
+            .predicate_counts()
+            .into_iter()
+            .map(|(predicate, count)| (predicate.to_owned(), count))
+            .collect();
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .with_context(|| format!("Error creating stats file {}", path.display()))?;
+        let mut w = std::io::BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut w, self)?;
+        w.write_all(b"\n")?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_sample_keeps_count_and_first_paths() {
+        let mut sample = PathSample::default();
+        for i in 0..PATH_SAMPLE_LEN + 5 {
+            sample.push(&format!("{}.go", i));
+        }
+        assert_eq!(sample.count, PATH_SAMPLE_LEN + 5);
+        assert_eq!(sample.paths.len(), PATH_SAMPLE_LEN);
+        assert_eq!(sample.paths[0], "0.go");
+    }
+}
@@ -0,0 +1,460 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! `scip-to-glean validate`: lint SCIP indexes without producing facts.
+//!
+//! Intended to run in CI against new indexer versions before their output is
+//! ingested. Problems the converter silently works around (dropped
+//! documents, skipped occurrences) are reported as errors; spec-conformant
+//! but suspicious data is reported as warnings.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::Index;
+#[cfg(feature = "facebook")]
+use proto_rust::scip::SymbolInformation;
+use scip_symbol::validate_scip_symbol;
+use serde::Serialize;
+
+use crate::GleanRange;
+use crate::angle::occurrence_range;
+use crate::angle::qualify_scip_symbol;
+use crate::decode_scip_range;
+use crate::paths::normalize_filepath;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::Index;
+#[cfg(not(feature = "facebook"))]
+use crate::proto::scip::SymbolInformation;
+use crate::read_scip_file;
+
+#[derive(clap::Args, Debug)]
+pub struct ValidateArgs {
+    #[arg(short, long, required = true)]
+    pub input: Vec<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+
+    #[arg(short, long, help = "Write the report to this file instead of stdout")]
+    pub output: Option<PathBuf>,
+
+    #[arg(long, help = "Fail on warnings as well as errors")]
+    pub deny_warnings: bool,
+}
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// One line per issue, followed by a summary.
+    #[default]
+    Text,
+    /// A single JSON document.
+    Json,
+}
+
+#[derive(Serialize, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Serialize, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Check {
+    /// A symbol string that does not follow the SCIP symbol grammar.
+    MalformedSymbol,
+    /// An occurrence with neither a flat nor a typed range.
+    MissingRange,
+    /// A range with the wrong number of elements.
+    InvalidRange,
+    /// Two definitions of the same symbol whose ranges overlap.
+    OverlappingDefinitions,
+    /// `SymbolInformation` for a symbol no occurrence refers to.
+    UnreferencedSymbolInformation,
+    /// A relationship to a symbol that appears nowhere in the index.
+    UnknownRelationshipTarget,
+    /// Several documents with the same `relative_path`. The converter merges
+    /// them, which is intended for indexers that split large files.
+    DuplicateDocument,
+    /// A path `normalize_filepath` rejects, e.g. one escaping the root.
+    UnnormalizablePath,
+}
+
+impl Check {
+    fn severity(self) -> Severity {
+        match self {
+            Check::MalformedSymbol
+            | Check::MissingRange
+            | Check::InvalidRange
+            | Check::UnnormalizablePath => Severity::Error,
+            Check::OverlappingDefinitions
+            | Check::UnreferencedSymbolInformation
+            | Check::UnknownRelationshipTarget
+            | Check::DuplicateDocument => Severity::Warning,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Check::MalformedSymbol => "malformed-symbol",
+            Check::MissingRange => "missing-range",
+            Check::InvalidRange => "invalid-range",
+            Check::OverlappingDefinitions => "overlapping-definitions",
+            Check::UnreferencedSymbolInformation => "unreferenced-symbol-information",
+            Check::UnknownRelationshipTarget => "unknown-relationship-target",
+            Check::DuplicateDocument => "duplicate-document",
+            Check::UnnormalizablePath => "unnormalizable-path",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Issue {
+    pub severity: Severity,
+    pub check: Check,
+    pub input: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    pub message: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    pub inputs: usize,
+    pub documents: usize,
+    pub occurrences: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    /// Number of issues per check name.
+    pub counts: BTreeMap<&'static str, usize>,
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    fn push(
+        &mut self,
+        check: Check,
+        input: &str,
+        document: Option<&str>,
+        symbol: Option<&str>,
+        message: String,
+    ) {
+        match check.severity() {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+        }
+        *self.counts.entry(check.name()).or_default() += 1;
+        self.issues.push(Issue {
+            severity: check.severity(),
+            check,
+            input: input.to_owned(),
+            document: document.map(str::to_owned),
+            symbol: symbol.map(str::to_owned),
+            message,
+        });
+    }
+
+    pub fn write(&self, format: ReportFormat, mut w: impl Write) -> Result<()> {
+        match format {
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut w, self)?;
+                writeln!(w)?;
+            }
+            ReportFormat::Text => {
+                for issue in &self.issues {
+                    let severity = match issue.severity {
+                        Severity::Error => "error",
+                        Severity::Warning => "warning",
+                    };
+                    write!(w, "{}[{}] {}", severity, issue.check.name(), issue.input)?;
+                    if let Some(document) = &issue.document {
+                        write!(w, ":{}", document)?;
+                    }
+                    if let Some(symbol) = &issue.symbol {
+                        write!(w, ": `{}`", symbol)?;
+                    }
+                    writeln!(w, ": {}", issue.message)?;
+                }
+                writeln!(
+                    w,
+                    "inputs: {}, documents: {}, occurrences: {}, errors: {}, warnings: {}",
+                    self.inputs, self.documents, self.occurrences, self.errors, self.warnings
+                )?;
+            }
+        }
+        w.flush()?;
+        Ok(())
+    }
+}
+
+pub fn validate(args: &ValidateArgs) -> Result<ValidationReport> {
+    let mut report = ValidationReport::default();
+    for input in &args.input {
+        let index = read_scip_file(input)?;
+        validate_index(&mut report, input, &index);
+    }
+    Ok(report)
+}
+
+/// Run `validate`, write the report to `--output` or stdout, and fail if
+/// the index has errors, or warnings under `--deny-warnings`.
+pub fn run_validate(args: &ValidateArgs) -> Result<()> {
+    let report = validate(args)?;
+    match &args.output {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Error creating report file {}", path.display()))?;
+            report.write(args.format, std::io::BufWriter::new(file))?;
+        }
+        None => report.write(args.format, std::io::stdout().lock())?,
+    }
+    if report.errors > 0 || (args.deny_warnings && report.warnings > 0) {
+        return Err(anyhow!(
+            "Validation failed: {} errors, {} warnings",
+            report.errors,
+            report.warnings
+        ));
+    }
+    Ok(())
+}
+
+fn validate_index(report: &mut ValidationReport, path: &Path, index: &Index) {
+    let input = path.display().to_string();
+    report.inputs += 1;
+    report.documents += index.documents.len();
+
+    // Symbols (qualified like `Env` does, so locals are per-file) that some
+    // occurrence refers to, and those that are declared in any way.
+    let mut referenced: HashSet<Box<str>> = HashSet::new();
+    let mut known: HashSet<Box<str>> = HashSet::new();
+    let mut malformed: HashSet<&str> = HashSet::new();
+    let mut check_symbol = |report: &mut ValidationReport, document: Option<&str>, symbol| {
+        if let Err(reason) = validate_scip_symbol(symbol) {
+            if malformed.insert(symbol) {
+                report.push(
+                    Check::MalformedSymbol,
+                    &input,
+                    document,
+                    Some(symbol),
+                    reason,
+                );
+            }
+        }
+    };
+
+    let mut paths: HashMap<&str, usize> = HashMap::new();
+    for doc in &index.documents {
+        *paths.entry(&doc.relative_path).or_default() += 1;
+        let document = Some(doc.relative_path.as_str());
+
+        if normalize_filepath(&doc.relative_path).is_none() {
+            report.push(
+                Check::UnnormalizablePath,
+                &input,
+                document,
+                None,
+                "path cannot be normalized, e.g. it escapes the project root".to_owned(),
+            );
+        }
+
+        let mut definitions: HashMap<&str, Vec<GleanRange>> = HashMap::new();
+        for occ in &doc.occurrences {
+            report.occurrences += 1;
+            if occ.symbol.is_empty() {
+                // scip-go emits empty occurrences; the converter skips them.
+                continue;
+            }
+            check_symbol(report, document, &occ.symbol);
+            referenced.insert(qualify_scip_symbol(&occ.symbol, &doc.relative_path));
+
+            let range = match decode_scip_range(&occurrence_range(occ)) {
+                Ok(Some(range)) => range,
+                Ok(None) => {
+                    report.push(
+                        Check::MissingRange,
+                        &input,
+                        document,
+                        Some(&occ.symbol),
+                        "occurrence has no range".to_owned(),
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    report.push(
+                        Check::InvalidRange,
+                        &input,
+                        document,
+                        Some(&occ.symbol),
+                        format!("{:#}", e),
+                    );
+                    continue;
+                }
+            };
+            if occ.symbol_roles & 1 != 0 {
+                definitions.entry(&occ.symbol).or_default().push(range);
+            }
+        }
+
+        let mut overlapping: Vec<_> = definitions
+            .into_iter()
+            .filter_map(|(symbol, ranges)| find_overlap(ranges).map(|(a, b)| (symbol, a, b)))
+            .collect();
+        overlapping.sort_by_key(|(symbol, _, _)| *symbol);
+        for (symbol, a, b) in overlapping {
+            report.push(
+                Check::OverlappingDefinitions,
+                &input,
+                document,
+                Some(symbol),
+                format!(
+                    "definitions at {}:{}-{}:{} and {}:{}-{}:{} overlap",
+                    a.line_begin,
+                    a.column_begin,
+                    a.line_end,
+                    a.column_end,
+                    b.line_begin,
+                    b.column_begin,
+                    b.line_end,
+                    b.column_end
+                ),
+            );
+        }
+
+        for info in &doc.symbols {
+            if info.symbol.is_empty() {
+                continue;
+            }
+            check_symbol(report, document, &info.symbol);
+            known.insert(qualify_scip_symbol(&info.symbol, &doc.relative_path));
+        }
+    }
+    for info in &index.external_symbols {
+        if info.symbol.is_empty() {
+            continue;
+        }
+        check_symbol(report, None, &info.symbol);
+        known.insert(qualify_scip_symbol(&info.symbol, ""));
+    }
+    known.extend(referenced.iter().cloned());
+
+    let mut duplicates: Vec<_> = paths.into_iter().filter(|(_, n)| *n > 1).collect();
+    duplicates.sort_unstable();
+    for (path, count) in duplicates {
+        report.push(
+            Check::DuplicateDocument,
+            &input,
+            Some(path),
+            None,
+            format!("{} documents share this path and will be merged", count),
+        );
+    }
+
+    for doc in &index.documents {
+        let document = Some(doc.relative_path.as_str());
+        for info in &doc.symbols {
+            if info.symbol.is_empty() {
+                continue;
+            }
+            if !referenced.contains(&qualify_scip_symbol(&info.symbol, &doc.relative_path)) {
+                report.push(
+                    Check::UnreferencedSymbolInformation,
+                    &input,
+                    document,
+                    Some(&info.symbol),
+                    "SymbolInformation for a symbol without occurrences".to_owned(),
+                );
+            }
+            check_relationships(report, &input, document, &doc.relative_path, info, &known);
+        }
+    }
+    for info in &index.external_symbols {
+        check_relationships(report, &input, None, "", info, &known);
+    }
+}
+
+fn check_relationships(
+    report: &mut ValidationReport,
+    input: &str,
+    document: Option<&str>,
+    filepath: &str,
+    info: &SymbolInformation,
+    known: &HashSet<Box<str>>,
+) {
+    for rel in &info.relationships {
+        if !known.contains(&qualify_scip_symbol(&rel.symbol, filepath)) {
+            report.push(
+                Check::UnknownRelationshipTarget,
+                input,
+                document,
+                Some(&info.symbol),
+                format!("relationship to unknown symbol `{}`", rel.symbol),
+            );
+        }
+    }
+}
+
+/// Returns the first pair of overlapping ranges, if any.
+fn find_overlap(mut ranges: Vec<GleanRange>) -> Option<(GleanRange, GleanRange)> {
+    let start = |r: &GleanRange| (r.line_begin, r.column_begin);
+    let end = |r: &GleanRange| (r.line_end, r.column_end);
+    ranges.sort_by_key(start);
+    // The range reaching furthest so far; any later range starting before
+    // its end overlaps it.
+    let mut furthest: Option<GleanRange> = None;
+    for range in ranges {
+        if let Some(prev) = &furthest {
+            if start(&range) <= end(prev) {
+                return Some((prev.clone(), range));
+            }
+        }
+        if furthest.as_ref().is_none_or(|prev| end(&range) > end(prev)) {
+            furthest = Some(range);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(line_begin: u64, column_begin: u64, line_end: u64, column_end: u64) -> GleanRange {
+        GleanRange {
+            line_begin,
+            column_begin,
+            line_end,
+            column_end,
+        }
+    }
+
+    #[test]
+    fn test_find_overlap() {
+        assert!(find_overlap(vec![range(1, 1, 1, 5), range(2, 1, 2, 5)]).is_none());
+        assert!(find_overlap(vec![range(1, 1, 1, 5), range(1, 6, 1, 9)]).is_none());
+        assert_eq!(
+            find_overlap(vec![range(3, 1, 3, 4), range(1, 1, 5, 1)]),
+            Some((range(1, 1, 5, 1), range(3, 1, 3, 4)))
+        );
+        // Identical definitions overlap.
+        assert!(find_overlap(vec![range(1, 1, 1, 5), range(1, 1, 1, 5)]).is_some());
+    }
+}
@@ -398,6 +398,141 @@ fn parse_descriptors(descriptors_str: &str) -> Vec<Descriptor> {
     result
 }
 
+/// Checks a SCIP symbol string against the grammar in the SCIP specification.
+///
+/// `parse_scip_symbol` is deliberately lenient and recovers from malformed
+/// input by skipping characters; this function instead reports the first
+/// violation, so indexer output can be linted.
+///
+/// # Examples
+/// ```
+/// use scip_symbol::validate_scip_symbol;
+///
+/// assert!(validate_scip_symbol("rust-analyzer cargo std v1.0 io/IsTerminal#").is_ok());
+/// assert!(validate_scip_symbol("rust-analyzer cargo std v1.0").is_err());
+/// ```
+pub fn validate_scip_symbol(symbol: &str) -> Result<(), String> {
+    if symbol.is_empty() {
+        return Err("empty symbol".to_string());
+    }
+    if let Some(("local", id)) = symbol.split_once(' ') {
+        if id.is_empty() {
+            return Err("local symbol without an id".to_string());
+        }
+        if !id.bytes().all(is_simple_identifier_byte) {
+            return Err(format!("invalid local id `{}`", id));
+        }
+        return Ok(());
+    }
+
+    let mut rest = symbol;
+    for field in [
+        "scheme",
+        "package manager",
+        "package name",
+        "package version",
+    ] {
+        let (value, remaining) = parse_space_escaped_field(rest);
+        if value.is_empty() {
+            return Err(format!("empty {}", field));
+        }
+        // An empty remainder without a trailing delimiter means the field ran
+        // to the end of the symbol.
+        if remaining.is_empty() && !rest.ends_with(' ') {
+            return Err(format!("missing fields after {}", field));
+        }
+        rest = remaining;
+    }
+
+    validate_descriptors(rest)
+}
+
+fn is_simple_identifier_byte(b: u8) -> bool {
+    matches!(b, b'_' | b'+' | b'-' | b'$') || b.is_ascii_alphanumeric()
+}
+
+/// Strictly validates an identifier at `pos`, returning the position after it.
+fn validate_identifier(s: &str, pos: usize) -> Result<usize, String> {
+    let bytes = s.as_bytes();
+    if bytes.get(pos) == Some(&b'`') {
+        let mut i = pos + 1;
+        while i < bytes.len() {
+            if bytes[i] == b'`' {
+                if bytes.get(i + 1) == Some(&b'`') {
+                    i += 2;
+                    continue;
+                }
+                if i == pos + 1 {
+                    return Err("empty escaped identifier".to_string());
+                }
+                return Ok(i + 1);
+            }
+            i += 1;
+        }
+        return Err("unterminated escaped identifier".to_string());
+    }
+
+    let len = bytes[pos..]
+        .iter()
+        .take_while(|b| is_simple_identifier_byte(**b))
+        .count();
+    if len == 0 {
+        return Err(match s[pos..].chars().next() {
+            Some(ch) => format!("unexpected character `{}` in descriptors", ch),
+            None => "missing identifier".to_string(),
+        });
+    }
+    Ok(pos + len)
+}
+
+fn expect_byte(s: &str, pos: usize, expected: u8) -> Result<usize, String> {
+    if s.as_bytes().get(pos) == Some(&expected) {
+        Ok(pos + 1)
+    } else {
+        Err(format!("expected `{}` in descriptors", expected as char))
+    }
+}
+
+fn validate_descriptors(descriptors_str: &str) -> Result<(), String> {
+    if descriptors_str.is_empty() {
+        return Err("no descriptors".to_string());
+    }
+
+    let bytes = descriptors_str.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'[' => {
+                i = validate_identifier(descriptors_str, i + 1)?;
+                i = expect_byte(descriptors_str, i, b']')?;
+            }
+            b'(' => {
+                i = validate_identifier(descriptors_str, i + 1)?;
+                i = expect_byte(descriptors_str, i, b')')?;
+            }
+            _ => {
+                i = validate_identifier(descriptors_str, i)?;
+                match bytes.get(i) {
+                    Some(b'/' | b'#' | b'.' | b':' | b'!') => i += 1,
+                    Some(b'(') => {
+                        let close = descriptors_str[i..]
+                            .find(')')
+                            .ok_or_else(|| "unterminated method disambiguator".to_string())?;
+                        i = expect_byte(descriptors_str, i + close + 1, b'.')?;
+                    }
+                    Some(_) => {
+                        let ch = descriptors_str[i..].chars().next().unwrap_or_default();
+                        return Err(format!("unexpected descriptor suffix `{}`", ch));
+                    }
+                    None => return Err("descriptor without a suffix".to_string()),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -826,4 +961,50 @@ mod tests {
         assert_eq!(descriptors[0].name, "TION");
         assert_eq!(descriptors[0].kind, DescriptorKind::Term);
     }
+
+    #[test]
+    fn test_validate_well_formed_symbols() {
+        for symbol in [
+            "local 42",
+            "rust-analyzer cargo std v1.0 io/IsTerminal#",
+            "scip-go gomod . . `generated`/fromOriginal().",
+            "semanticdb maven . . android/os/PowerManager#wakeUp(+1).",
+            "scip-typescript npm pkg 1.0 src/`a.ts`/Foo#[T]bar().(x)",
+            "rust-analyzer cargo core 1.0 macros/assert!",
+            "scip-ruby gem . . `with``tick`#",
+            "cxx . . $ main(9b28c5).",
+            "my  scheme . . . Foo#",
+        ] {
+            assert_eq!(validate_scip_symbol(symbol), Ok(()), "{symbol}");
+        }
+    }
+
+    #[test]
+    fn test_validate_malformed_symbols() {
+        for (symbol, reason) in [
+            ("", "empty symbol"),
+            ("local ", "local symbol without an id"),
+            (
+                "rust-analyzer cargo std v1.0",
+                "missing fields after package version",
+            ),
+            ("rust-analyzer cargo std v1.0 ", "no descriptors"),
+            ("scip . . . Foo", "descriptor without a suffix"),
+            ("scip . . . `Foo#", "unterminated escaped identifier"),
+            (
+                "scip . . . Foo#%Bar.",
+                "unexpected character `%` in descriptors",
+            ),
+            ("scip . . . ROTÅTION.", "unexpected descriptor suffix `Å`"),
+            ("scip . . . foo(", "unterminated method disambiguator"),
+            ("scip . . . foo()", "expected `.` in descriptors"),
+            ("scip . . . [T", "expected `]` in descriptors"),
+        ] {
+            assert_eq!(
+                validate_scip_symbol(symbol),
+                Err(reason.to_string()),
+                "{symbol}"
+            );
+        }
+    }
 }